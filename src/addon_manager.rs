@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::extract_addon::{VpkFile, extract_files, vpk_files};
+use crate::gameinfo_history::{Snapshot, SnapshotRestore};
+use crate::install_addon::{InstallReport, addon_name_from_vpk};
+use crate::list_addons::{InstalledAddon, installed_addons};
+use crate::manage_profiles::{ProfileActivated, ProfileDiff, ProfileSaved};
+use crate::order_addons::{AddonMove, Direction};
+use crate::pug_mode::{PugStatus, PugSwitch};
+use crate::rename_addon::Renamed;
+use crate::toggle_addon::Toggled;
+use crate::transaction::PlannedStep;
+use crate::uninstall_addon::Uninstalled;
+use crate::verify_vpk::{Verification, verify_vpk};
+
+// The addon manager for other programs. Every operation returns what it did or an
+// `EnforcerError`, and prints nothing: what DEBUG and `with_verbose` trace goes to the `log` crate.
+// With `with_dry_run`, nothing is changed and each outcome holds the steps that would have been taken.
+//
+//	let manager = AddonManager::open("/games/Left 4 Dead 2")?;
+//	let report = manager.install("downloads/tank_skin.vpk", None)?;
+//	manager.rename(&report.name, "tank_skin")?;
+#[derive(Debug, Clone)]
+pub struct AddonManager {
+	game_dir: PathBuf,
+	force: bool,
+	dry_run: bool,
+	verbose: bool,
+}
+
+impl AddonManager {
+	// Manages the game in `game_dir`, the "Left 4 Dead 2" directory holding left4dead2/gameinfo.txt
+	pub fn open(game_dir: impl Into<PathBuf>) -> Result<AddonManager, EnforcerError> {
+		let game_dir = game_dir.into();
+		if !game_dir.is_dir() {
+			return Err(EnforcerError::GameNotFound(format!("{} is not a directory", game_dir.display())));
+		}
+		let gameinfo_path = game_dir.join("left4dead2/gameinfo.txt");
+		if !gameinfo_path.is_file() {
+			return Err(EnforcerError::GameinfoMissing(gameinfo_path));
+		}
+		Ok(AddonManager { game_dir, force: false, dry_run: false, verbose: false })
+	}
+
+	// Finds the game like the command line does, through L4D2_DIR or the Steam library
+	pub fn locate() -> Result<AddonManager, EnforcerError> {
+		AddonManager::open(find_game_dir()?)
+	}
+
+	// Logs what each step does at the debug level, like `--verbose`
+	pub fn with_verbose(mut self, verbose: bool) -> AddonManager {
+		self.verbose = verbose;
+		self
+	}
+
+	// Only plans the changes, like `--dry-run`
+	pub fn with_dry_run(mut self, dry_run: bool) -> AddonManager {
+		self.dry_run = dry_run;
+		self
+	}
+
+	// Installs addons that fail verification, like `install --force`
+	pub fn with_force(mut self, force: bool) -> AddonManager {
+		self.force = force;
+		self
+	}
+
+	pub fn game_dir(&self) -> &Path {
+		&self.game_dir
+	}
+
+	pub fn gameinfo_path(&self) -> PathBuf {
+		self.game_dir.join("left4dead2/gameinfo.txt")
+	}
+
+	// Installs or updates the VPK `file`. Without a `name`, the addon is named after the
+	// title in its addoninfo.txt. A corrupt VPK is refused with `VpkCorrupt`, unless `with_force`.
+	pub fn install(&self, file: impl AsRef<Path>, name: Option<&str>) -> Result<InstallReport, EnforcerError> {
+		let file = file.as_ref();
+		let name = match name {
+			Some(name) => name.to_string(),
+			None => addon_name_from_vpk(&file.to_string_lossy(), self.verbose)?,
+		};
+		install_addon::install(&self.game_dir, file, &name, self.force, self.dry_run, self.verbose)
+	}
+
+	// Checks the files of a VPK against their CRC32 and, for version 2, the MD5 sections.
+	// The game is not needed, and damaged parts are listed rather than being an error.
+	pub fn verify(&self, file: impl AsRef<Path>) -> Result<Verification, EnforcerError> {
+		verify_vpk(file.as_ref(), self.verbose)
+	}
+
+	// The files of a VPK matching `patterns` (paths, directories or globs like
+	// "materials/vgui/**"), all of them without patterns
+	pub fn files(&self, file: impl AsRef<Path>, patterns: &[String]) -> Result<Vec<VpkFile>, EnforcerError> {
+		vpk_files(file.as_ref(), patterns)
+	}
+
+	// Extracts the files of a VPK matching `patterns` below `output`, and returns their paths
+	// with the steps a dry run planned
+	pub fn extract(
+		&self,
+		file: impl AsRef<Path>,
+		patterns: &[String],
+		output: impl AsRef<Path>,
+	) -> Result<(Vec<PathBuf>, Vec<PlannedStep>), EnforcerError> {
+		extract_files(file.as_ref(), patterns, output.as_ref(), self.dry_run, self.verbose)
+	}
+
+	// Where an installed addon's VPK is
+	pub fn addon_vpk(&self, name: &str) -> PathBuf {
+		self.game_dir.join(name).join("pak01_dir.vpk")
+	}
+
+	pub fn uninstall(&self, name: &str) -> Result<Uninstalled, EnforcerError> {
+		uninstall_addon::uninstall(&self.game_dir, name, self.dry_run, self.verbose)
+	}
+
+	pub fn rename(&self, name: &str, new_name: &str) -> Result<Renamed, EnforcerError> {
+		rename_addon::rename(&self.game_dir, name, new_name, self.dry_run, self.verbose)
+	}
+
+	// The addons of gameinfo.txt in load order. With `details`, their addoninfo.txt and
+	// manifest entry are read too.
+	pub fn list(&self, details: bool) -> Result<Vec<InstalledAddon>, EnforcerError> {
+		installed_addons(&self.game_dir, details, self.verbose)
+	}
+
+	pub fn pug_status(&self) -> Result<PugStatus, EnforcerError> {
+		pug_mode::pug_status(&self.game_dir, self.verbose)
+	}
+
+	// The gameinfo.txt before and after the vanilla one `pug_toggle` would rebuild, if any
+	pub fn pug_rebuild(&self) -> Result<Option<(String, String)>, EnforcerError> {
+		pug_mode::pug_rebuild(&self.game_dir, self.verbose)
+	}
+
+	// Enables PuG mode when it is disabled and the other way round
+	pub fn pug_toggle(&self) -> Result<PugSwitch, EnforcerError> {
+		pug_mode::pug_switch(&self.game_dir, self.dry_run, self.verbose)
+	}
+
+	// What `reset` would write: the backup, or a vanilla gameinfo.txt rebuilt without any addon
+	pub fn plan_reset(&self) -> Result<ResetPlan, EnforcerError> {
+		plan_reset(&self.game_dir, self.verbose)
+	}
+
+	// Puts the vanilla gameinfo.txt of `plan` in place, unless gameinfo.txt was changed since
+	pub fn reset(&self, plan: &ResetPlan) -> Result<Vec<PlannedStep>, EnforcerError> {
+		gameinfo_reset(&self.game_dir, plan, self.dry_run, self.verbose)
+	}
+
+	// Enables or disables addons, keeping their files
+	pub fn set_enabled(&self, names: &[String], enable: bool) -> Result<Toggled, EnforcerError> {
+		toggle_addon::set_addons_enabled(&self.game_dir, names, enable, self.dry_run, self.verbose)
+	}
+
+	// Moves an addon within the load order
+	pub fn move_addon(&self, name: &str, direction: Direction) -> Result<AddonMove, EnforcerError> {
+		order_addons::move_addon(&self.game_dir, name, direction, self.dry_run, self.verbose)
+	}
+
+	pub fn profiles(&self) -> Result<Vec<String>, EnforcerError> {
+		manage_profiles::list_profiles(&self.game_dir, self.verbose)
+	}
+
+	// Saves the enabled addons as a profile
+	pub fn create_profile(&self, name: &str) -> Result<ProfileSaved, EnforcerError> {
+		manage_profiles::create_profile(&self.game_dir, name, None, self.dry_run, self.verbose)
+	}
+
+	// What activating a profile would change
+	pub fn diff_profile(&self, name: &str) -> Result<ProfileDiff, EnforcerError> {
+		manage_profiles::diff_profile(&self.game_dir, name, None, self.verbose)
+	}
+
+	pub fn activate_profile(&self, name: &str) -> Result<ProfileActivated, EnforcerError> {
+		manage_profiles::activate_profile(&self.game_dir, name, self.dry_run, self.verbose)
+	}
+
+	// Returns the steps a dry run planned
+	pub fn export_profile(&self, name: &str, output: impl AsRef<Path>) -> Result<Vec<PlannedStep>, EnforcerError> {
+		manage_profiles::export_profile(&self.game_dir, name, output.as_ref(), self.dry_run, self.verbose)
+	}
+
+	// The gameinfo.txt snapshots, newest first
+	pub fn snapshots(&self) -> Result<Vec<Snapshot>, EnforcerError> {
+		gameinfo_history::list_snapshots(&self.game_dir, self.verbose)
+	}
+
+	// A snapshot with the current gameinfo.txt and the snapshot's contents
+	pub fn snapshot_diff(&self, id: &str) -> Result<(Snapshot, String, String), EnforcerError> {
+		gameinfo_history::snapshot_diff(&self.game_dir, id, self.verbose)
+	}
+
+	pub fn restore_snapshot(&self, id: &str) -> Result<SnapshotRestore, EnforcerError> {
+		gameinfo_history::restore_snapshot(&self.game_dir, id, self.dry_run, self.verbose)
+	}
+}
@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::Gameinfo;
+use crate::transaction::Transaction;
+use crate::vpk_getdata::AddonInfo;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::SystemTime;
+
+// The manifest is kept next to gameinfo.txt and records what this tool installed,
+// so that its state does not have to be guessed from gameinfo.txt alone
+const MANIFEST_FILE: &str = "addon_manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+	pub version: u32,
+	// Whether the vanilla gameinfo.txt was put in place by `pug --switch`,
+	// unknown for manifests written before the first switch
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub pug_mode: Option<bool>,
+	// In load order, like the SearchPaths of gameinfo.txt
+	#[serde(default)]
+	pub addons: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+	pub name: String,
+	// What the addon was installed from, if it was installed by this tool
+	#[serde(default)]
+	pub source: Option<String>,
+	// Hashes of the installed pak01_dir.vpk
+	#[serde(default)]
+	pub md5: Option<String>,
+	#[serde(default)]
+	pub sha256: Option<String>,
+	// RFC 3339, UTC
+	#[serde(default)]
+	pub installed_at: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub updated_at: Option<String>,
+	#[serde(default)]
+	pub info: Option<AddonInfo>,
+	pub enabled: bool,
+}
+
+impl Default for Manifest {
+	fn default() -> Manifest {
+		Manifest { version: MANIFEST_VERSION, pug_mode: None, addons: Vec::new() }
+	}
+}
+
+impl Manifest {
+	pub fn path(game_dir: &Path) -> PathBuf {
+		game_dir.join("left4dead2").join(MANIFEST_FILE)
+	}
+
+	// Reads the manifest, an empty one when it does not exist yet
+	pub fn load(game_dir: &Path, verbose: bool) -> Result<Manifest, EnforcerError> {
+		let path = Manifest::path(game_dir);
+		if !path.exists() {
+			if var_os("DEBUG").is_some() || verbose {
+				log::debug!("No addon manifest at {:?} yet", path);
+			}
+			return Ok(Manifest::default());
+		}
+		Manifest::read(&path).map_err(|e| {
+			let err = format!(
+				"Unable to use the addon manifest ({}): {} \n\tRun repair to rebuild it.",
+				path.display(),
+				e
+			);
+			EnforcerError::Other(err)
+		})
+	}
+
+	// Parses the manifest file, the error being a plain description of what is wrong with it
+	pub fn read(path: &Path) -> Result<Manifest, String> {
+		let contents = read_to_string(path).map_err(|e| e.to_string())?;
+		let manifest: Manifest = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+		if manifest.version > MANIFEST_VERSION {
+			return Err(format!(
+				"it was written by a newer version of {} (format {})",
+				env!("CARGO_PKG_NAME"),
+				manifest.version
+			));
+		}
+		Ok(manifest)
+	}
+
+	// Writes the manifest as part of `tx`, so it changes together with gameinfo.txt
+	pub fn save(&self, game_dir: &Path, tx: &mut Transaction) -> Result<(), EnforcerError> {
+		let mut contents = serde_json::to_string_pretty(self)?;
+		contents.push('\n');
+		tx.write(&Manifest::path(game_dir), contents)?;
+		Ok(())
+	}
+
+	pub fn entry(&self, name: &str) -> Option<&ManifestEntry> {
+		self.addons.iter().find(|entry| entry.name == name)
+	}
+
+	// Records an installation (or update) of `name` from `source`, `vpk` being the installed file
+	pub fn record_install(&mut self, name: &str, source: Option<String>, vpk: &Path, verbose: bool) -> Result<(), EnforcerError> {
+		let now = timestamp();
+		let mut entry = ManifestEntry::scan(name, vpk, verbose)?;
+		entry.source = source;
+		match self.addons.iter_mut().find(|existing| existing.name == name) {
+			Some(existing) => {
+				entry.installed_at = existing.installed_at.take().or(Some(now.clone()));
+				entry.updated_at = Some(now);
+				entry.enabled = existing.enabled;
+				*existing = entry;
+			},
+			None => {
+				entry.installed_at = Some(now);
+				self.addons.push(entry);
+			},
+		}
+		Ok(())
+	}
+
+	pub fn remove(&mut self, name: &str) {
+		self.addons.retain(|entry| entry.name != name);
+	}
+
+	pub fn rename(&mut self, name: &str, new_name: &str) {
+		if let Some(entry) = self.addons.iter_mut().find(|entry| entry.name == name) {
+			entry.name = new_name.to_string();
+		}
+	}
+
+	// Brings the enabled flags and the order of the entries in line with gameinfo.txt.
+	// Entries are neither added nor dropped here, that is what `repair` is for.
+	pub fn sync(&mut self, gameinfo: &Gameinfo) {
+		let states = gameinfo.addon_states();
+		for entry in &mut self.addons {
+			if let Some((_, enabled)) = states.iter().find(|(addon, _)| *addon == entry.name) {
+				entry.enabled = *enabled;
+			}
+		}
+		// Entries missing from gameinfo.txt go last, in their previous order
+		self.addons.sort_by_key(|entry| states.iter().position(|(addon, _)| *addon == entry.name).unwrap_or(usize::MAX));
+	}
+}
+
+impl ManifestEntry {
+	// An entry for the addon whose installed VPK is `vpk`, without any installation details
+	pub fn scan(name: &str, vpk: &Path, verbose: bool) -> Result<ManifestEntry, EnforcerError> {
+		let (md5, sha256) = vpk_hashes(vpk)?;
+		// A missing or broken addoninfo.txt does not make the addon less installed
+		let info = vpk_getdata::main(&vpk.to_string_lossy().to_string(), verbose).ok();
+		Ok(ManifestEntry {
+			name: name.to_string(),
+			source: None,
+			md5: Some(md5),
+			sha256: Some(sha256),
+			installed_at: None,
+			updated_at: None,
+			info,
+			enabled: true,
+		})
+	}
+}
+
+// MD5 and SHA-256 of a file, read only once
+pub fn vpk_hashes(path: &Path) -> Result<(String, String), std::io::Error> {
+	let mut file = BufReader::new(File::open(path)?);
+	let mut md5 = Md5::new();
+	let mut sha256 = Sha256::new();
+	let mut buffer = [0; 65536];
+	loop {
+		let count = file.read(&mut buffer)?;
+		if count == 0 {
+			break;
+		}
+		md5.update(&buffer[..count]);
+		sha256.update(&buffer[..count]);
+	}
+	Ok((format!("{:x}", md5.finalize()), format!("{:x}", sha256.finalize())))
+}
+
+fn timestamp() -> String {
+	humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
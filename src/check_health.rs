@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::addon_manifest::{Manifest, ManifestEntry};
+use crate::gameinfo::{Gameinfo, is_game_dir};
+use crate::install_addon::validate_addon_name;
+use crate::repair_manifest::RepairReport;
+use crate::transaction::{PlannedStep, Transaction};
+use crate::vanilla_gameinfo::{LineEndings, check_vanilla, normalized_md5};
+use serde::Serialize;
+
+// What `doctor` found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HealthReport {
+	// None when the game could not be found
+	pub game_dir: Option<PathBuf>,
+	// Whether gameinfo.txt is vanilla, see `Vanilla::describe`
+	pub gameinfo: Option<String>,
+	// Both hashes, so that new vanilla files can be added to KNOWN_GAMEINFOS from bug reports
+	pub gameinfo_md5: Option<String>,
+	pub normalized_md5: Option<String>,
+	pub line_endings: Option<String>,
+	pub pug_mode: Option<bool>,
+	pub addons: Option<usize>,
+	pub disabled_addons: Option<usize>,
+	pub findings: Vec<Finding>,
+	// What `repair` did, when one of the fixes was running it
+	pub repair: Option<RepairReport>,
+	// The steps a dry run of the fixes would have performed, those of `repair` being in its report
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub plan: Vec<PlannedStep>,
+}
+
+impl HealthReport {
+	pub fn problems_left(&self) -> usize {
+		self.findings.iter().filter(|finding| !finding.fixed).count()
+	}
+
+	fn problem(mut self, problem: String) -> HealthReport {
+		self.findings.push(Finding { problem, fix: None, fixed: false });
+		self
+	}
+}
+
+// Something `doctor` found wrong
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+	pub problem: String,
+	// What `doctor --fix` does about it, if anything
+	pub fix: Option<String>,
+	pub fixed: bool,
+}
+
+// What `doctor --fix` does about a finding
+enum Fix {
+	// Remove the addon's line from gameinfo.txt, its directory is broken or gone
+	RemoveEntry(String),
+	// List an unreferenced addon directory in gameinfo.txt, disabled
+	ListDisabled(String),
+	// Save the vanilla gameinfo.txt as the backup
+	CreateBackup,
+	// Remove a custom gameinfo.txt copy identical to gameinfo.txt
+	RemoveStaleCustom,
+	// Record the actual PuG mode state in the manifest
+	RecordPugMode(bool),
+	// Rebuild the manifest, see `repair`
+	RepairManifest,
+}
+
+impl Fix {
+	fn describe(&self) -> String {
+		match self {
+			Fix::RemoveEntry(addon) => format!("remove the entry of {} from gameinfo.txt", addon),
+			Fix::ListDisabled(addon) => format!("list {} in gameinfo.txt as a disabled addon", addon),
+			Fix::CreateBackup => "back up the current (vanilla) gameinfo.txt".to_string(),
+			Fix::RemoveStaleCustom => "remove the leftover gameinfo.txt.custom".to_string(),
+			Fix::RecordPugMode(enabled) => format!("record PuG mode as {} in the addon manifest", if *enabled { "enabled" } else { "disabled" }),
+			Fix::RepairManifest => "bring the addon manifest up to date (as repair does)".to_string(),
+		}
+	}
+}
+
+// Checks the game installation, gameinfo.txt, the addon directories, the backup and the PuG mode state,
+// making a report that can be attached to bug reports. With `fix`, every finding that can be fixed is.
+// Without `game_dir` (the game could not be found), that is the only problem reported.
+pub fn check_health(game_dir: Option<&Path>, fix: bool, dry_run: bool, verbose: bool) -> Result<HealthReport, EnforcerError> {
+	// Nothing else can be checked without the game and a readable gameinfo.txt
+	let Some(game_dir) = game_dir else {
+		let problem = "The Left 4 Dead 2 directory could not be found, set L4D2_DIR to point at it".to_string();
+		return Ok(HealthReport::default().problem(problem));
+	};
+	let mut report = HealthReport { game_dir: Some(game_dir.to_path_buf()), ..HealthReport::default() };
+	let Ok(gameinfo_path) = gameinfo_path(game_dir, verbose) else {
+		return Ok(report.problem("gameinfo.txt is missing, verify the game files in Steam".to_string()));
+	};
+	let vanilla = check_vanilla(&gameinfo_path)?;
+	let gameinfo_contents = std::fs::read(&gameinfo_path)?;
+	report.gameinfo = Some(vanilla.describe());
+	report.gameinfo_md5 = Some(calculate_md5(&gameinfo_path)?);
+	report.normalized_md5 = Some(normalized_md5(&gameinfo_contents));
+	report.line_endings = Some(LineEndings::of(&gameinfo_contents).to_string());
+
+	// While PuG mode is enabled, the user's addons live in the custom copy
+	let gameinfo_custom = gameinfo_path.with_extension("txt.custom");
+	let pug_mode = gameinfo_custom.exists();
+	report.pug_mode = Some(pug_mode);
+	let target = if pug_mode { gameinfo_custom.clone() } else { gameinfo_path.clone() };
+	let mut gameinfo = match read_to_string(&target).map_err(|e| e.to_string()).and_then(|contents| Gameinfo::parse(&contents).map_err(|e| e.to_string())) {
+		Ok(gameinfo) => gameinfo,
+		Err(e) => {
+			let problem = format!("{} cannot be parsed: {}", target.file_name().unwrap_or_default().to_string_lossy(), e);
+			return Ok(report.problem(problem));
+		},
+	};
+	let states = gameinfo.addon_states();
+	report.addons = Some(states.len());
+	report.disabled_addons = Some(states.iter().filter(|(_, enabled)| !enabled).count());
+
+	// Each problem along with its fix, if there is one
+	let mut findings: Vec<(String, Option<Fix>)> = Vec::new();
+
+	// Every addon needs its directory and a readable VPK
+	for (addon, _) in &states {
+		let vpk = game_dir.join(addon).join("pak01_dir.vpk");
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Checking {:?}", vpk);
+		}
+		let problem = if !game_dir.join(addon).is_dir() {
+			format!("{} is listed in gameinfo.txt, but its directory is missing", addon)
+		} else if !vpk.is_file() {
+			format!("{} is listed in gameinfo.txt, but its directory has no pak01_dir.vpk", addon)
+		} else if let Err(e) = vpk_getdata::open_vpk(&vpk) {
+			format!("{} cannot be read: {}", addon, e)
+		} else {
+			continue;
+		};
+		findings.push((problem, Some(Fix::RemoveEntry(addon.clone()))));
+	}
+
+	// Addon directories gameinfo.txt no longer points at
+	let mut orphans: Vec<String> = std::fs::read_dir(game_dir)?
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.path().join("pak01_dir.vpk").is_file())
+		.map(|entry| entry.file_name().to_string_lossy().to_string())
+		.filter(|dir| !is_game_dir(dir))
+		.filter(|dir| !states.iter().any(|(addon, _)| addon == dir))
+		.collect();
+	orphans.sort();
+	for orphan in orphans {
+		// A name gameinfo.txt cannot hold has to be renamed by hand first
+		match validate_addon_name(&orphan) {
+			Ok(()) => findings.push((
+				format!("{} looks like an addon directory, but gameinfo.txt does not reference it", orphan),
+				Some(Fix::ListDisabled(orphan)),
+			)),
+			Err(_) => findings.push((
+				format!(
+					"{} looks like an addon directory, but its name cannot go in gameinfo.txt (it is empty or has whitespace or special characters), rename it to list it",
+					orphan
+				),
+				None,
+			)),
+		}
+	}
+
+	// The backup is what reset and PuG mode restore
+	match gameinfo_backup_path(game_dir, verbose) {
+		Err(e) => findings.push((format!("The gameinfo.txt backup cannot be located: {}", e), None)),
+		Ok(backup) if !backup.exists() => findings.push((
+			format!(
+				"There is no gameinfo.txt backup at {}{}",
+				backup.display(),
+				if vanilla.is_vanilla() { "" } else { ", and gameinfo.txt is not vanilla to make one from" }
+			),
+			vanilla.is_vanilla().then_some(Fix::CreateBackup),
+		)),
+		Ok(backup) => {
+			if !check_vanilla(&backup)?.is_vanilla() {
+				findings.push((
+					format!("The gameinfo.txt backup ({}) is not the vanilla file, its MD5 is {}", backup.display(), calculate_md5(&backup)?),
+					None,
+				));
+			}
+		},
+	}
+
+	// PuG mode puts the vanilla gameinfo.txt in place and keeps the user's one aside
+	if pug_mode && !vanilla.is_vanilla() {
+		let identical = read_to_string(&gameinfo_custom)? == read_to_string(&gameinfo_path)?;
+		findings.push((
+			if identical {
+				"PuG mode looks enabled, but gameinfo.txt is the same as gameinfo.txt.custom".to_string()
+			} else {
+				"PuG mode looks enabled, but gameinfo.txt is not vanilla: both gameinfo.txt and gameinfo.txt.custom hold changes, keep the one you want by hand".to_string()
+			},
+			identical.then_some(Fix::RemoveStaleCustom),
+		));
+	}
+	// A missing manifest is created by the next change, a damaged one is left to `repair`
+	let manifest_path = Manifest::path(game_dir);
+	let mut manifest = if manifest_path.exists() {
+		match Manifest::read(&manifest_path) {
+			Ok(manifest) => Some(manifest),
+			Err(e) => {
+				findings.push((format!("The addon manifest cannot be used: {}", e), Some(Fix::RepairManifest)));
+				None
+			},
+		}
+	} else {
+		Some(Manifest::default())
+	};
+	if let Some(recorded) = manifest.as_ref().and_then(|manifest| manifest.pug_mode).filter(|recorded| *recorded != pug_mode) {
+		findings.push((
+			format!("The addon manifest says PuG mode is {}", if recorded { "enabled" } else { "disabled" }),
+			Some(Fix::RecordPugMode(pug_mode)),
+		));
+	}
+
+	report.findings = findings
+		.iter()
+		.map(|(problem, fix)| Finding { problem: problem.clone(), fix: fix.as_ref().map(Fix::describe), fixed: false })
+		.collect();
+	if !fix || findings.iter().all(|(_, fix)| fix.is_none()) {
+		return Ok(report);
+	}
+
+	let fixes: Vec<Fix> = findings.into_iter().filter_map(|(_, fix)| fix).collect();
+	let mut tx = Transaction::new(dry_run, verbose);
+	let mut gameinfo_changed = false;
+	let mut manifest_changed = false;
+	let mut repair = false;
+	for fix in &fixes {
+		match fix {
+			Fix::RemoveEntry(addon) => {
+				gameinfo_changed |= gameinfo.remove_addon(addon);
+				if let Some(manifest) = &mut manifest {
+					manifest.remove(addon);
+				}
+			},
+			Fix::ListDisabled(addon) => {
+				gameinfo.add_addon(addon)?;
+				gameinfo.disable_addon(addon);
+				gameinfo_changed = true;
+				if let Some(manifest) = &mut manifest {
+					manifest.addons.push(ManifestEntry::scan(addon, &game_dir.join(addon).join("pak01_dir.vpk"), verbose)?);
+				}
+			},
+			Fix::CreateBackup => tx.copy(&gameinfo_path, &gameinfo_backup_path(game_dir, verbose)?)?,
+			Fix::RemoveStaleCustom => tx.remove(&gameinfo_custom)?,
+			Fix::RecordPugMode(enabled) => {
+				if let Some(manifest) = &mut manifest {
+					manifest.pug_mode = Some(*enabled);
+					manifest_changed = true;
+				}
+			},
+			Fix::RepairManifest => repair = true,
+		}
+	}
+	if gameinfo_changed {
+		// Once the leftover custom copy is gone, gameinfo.txt is the one holding the addons
+		let target = if fixes.iter().any(|fix| matches!(fix, Fix::RemoveStaleCustom)) { gameinfo_path.clone() } else { target };
+		if target == gameinfo_path {
+			crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &target, "doctor", verbose)?;
+		}
+		tx.write(&target, gameinfo.doc.to_string())?;
+	}
+	if let Some(manifest) = manifest.as_mut().filter(|_| gameinfo_changed || manifest_changed) {
+		manifest.sync(&gameinfo);
+		manifest.save(game_dir, &mut tx)?;
+	}
+	report.plan = tx.commit()?;
+	for finding in report.findings.iter_mut().filter(|finding| finding.fix.is_some()) {
+		finding.fixed = true;
+	}
+	if repair {
+		report.repair = Some(crate::repair_manifest::repair_manifest(game_dir, dry_run, verbose)?);
+	}
+	Ok(report)
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use crate::structured_output::OutputFormat;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
@@ -11,6 +12,9 @@ pub struct Args {
 	pub quiet: bool,
 	#[arg(short, long)]
 	pub verbose: bool,
+	// Print what would be changed instead of changing it
+	#[arg(long, global = true)]
+	pub dry_run: bool,
 	#[arg(short)]
 	pub help: bool,
 	#[arg(long = "help")]
@@ -38,17 +42,49 @@ pub enum SubCommands {
 	// Reset gameinfo.txt using the backup
 	Reset(ResetArgs),
 	Rs(ResetArgs),
+	// Pack a loose addon folder into a VPK
+	Pack(PackArgs),
+	// Show what installed addons (or VPK files) contain
+	Inspect(InspectArgs),
+	// Check installed addons (or VPK files) for corruption
+	Verify(VerifyArgs),
+	// List or extract the files of an installed addon (or a VPK file)
+	Extract(ExtractArgs),
+	// Show files overridden by more than one installed addon
+	Conflicts(ConflictsArgs),
+	// Show or change the load order of installed addons
+	Order(OrderArgs),
+	// Enable disabled addons again
+	Enable(ToggleArgs),
+	// Disable addons without uninstalling them
+	Disable(ToggleArgs),
+	// Named sets of enabled addons
+	Profile(ProfileArgs),
+	// Reconcile gameinfo.txt with the addon manifest
+	Repair(RepairArgs),
+	// Check the installation for problems
+	Doctor(DoctorArgs),
+	// Saved versions of gameinfo.txt
+	Backups(BackupsArgs),
 }
 
 // Arguments for the `install` subcommand
 #[derive(Parser, Debug)]
 pub struct InstallArgs {
-	#[arg(short, long, value_name = "FILE_PATH", num_args = 1.., index = 1)]
-	pub file: Option<String>,
+	// Addon files, directories holding them, or glob patterns; positionally or after -f
+	#[arg(value_name = "FILE_PATH")]
+	pub files: Vec<String>,
+
+	#[arg(short, long, value_name = "FILE_PATH", num_args = 1..)]
+	pub file: Vec<String>,
 
 	#[arg(short, long, value_name = "NAME")]
 	pub name: Option<String>,
 
+	// Install addons that fail verification
+	#[arg(long)]
+	pub force: bool,
+
 	#[arg(short, long)] // TODO
 	pub quiet: bool,
 
@@ -62,6 +98,9 @@ pub struct ListArgs {
 	#[arg(short, long)]
 	pub details: bool,
 
+	#[arg(long, value_enum, default_value_t)]
+	pub format: OutputFormat,
+
 	#[arg(short, long)]
 	pub quiet: bool,
 	
@@ -107,6 +146,9 @@ pub struct PuGArgs {
 	#[arg(short, long)]
 	pub switch: bool,
 
+	#[arg(long, value_enum, default_value_t)]
+	pub format: OutputFormat,
+
 	#[arg(short, long)]
 	pub verbose: bool,
 }
@@ -119,4 +161,222 @@ pub struct ResetArgs {
 
 	#[arg(short, long)]
 	pub verbose: bool,
-}
\ No newline at end of file
+}
+
+// Arguments for the `pack` subcommand
+#[derive(Parser, Debug)]
+pub struct PackArgs {
+	#[arg(value_name = "DIRECTORY")]
+	pub dir: Option<String>,
+
+	#[arg(short, long, value_name = "FILE_PATH")]
+	pub output: Option<String>,
+
+	#[arg(long)]
+	pub v2: bool,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `inspect` subcommand
+#[derive(Parser, Debug)]
+pub struct InspectArgs {
+	// Installed addon names or VPK files, every installed addon if none
+	#[arg(value_name = "NAME_OR_FILE")]
+	pub targets: Vec<String>,
+
+	#[arg(long, value_enum, default_value_t)]
+	pub format: OutputFormat,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `verify` subcommand
+#[derive(Parser, Debug)]
+pub struct VerifyArgs {
+	// Installed addon names or VPK files, every installed addon if none
+	#[arg(value_name = "NAME_OR_FILE")]
+	pub targets: Vec<String>,
+
+	#[arg(long, value_enum, default_value_t)]
+	pub format: OutputFormat,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `extract` subcommand
+#[derive(Parser, Debug)]
+pub struct ExtractArgs {
+	// Installed addon name or VPK file
+	#[arg(value_name = "NAME_OR_FILE")]
+	pub target: Option<String>,
+
+	// Paths, directories or glob patterns of the files, every file if none
+	#[arg(value_name = "PATTERN")]
+	pub patterns: Vec<String>,
+
+	#[arg(short, long, value_name = "DIRECTORY")]
+	pub output: Option<String>,
+
+	#[arg(short, long)]
+	pub list: bool,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `conflicts` subcommand
+#[derive(Parser, Debug)]
+pub struct ConflictsArgs {
+	#[arg(long, value_enum, default_value_t)]
+	pub format: OutputFormat,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+// Arguments for the `order` subcommand
+#[derive(Parser, Debug)]
+pub struct OrderArgs {
+	// Addon to move with --up/--down/--top/--bottom
+	#[arg(value_name = "NAME")]
+	pub name: Option<String>,
+
+	#[arg(short, long)]
+	pub up: bool,
+
+	#[arg(short, long)]
+	pub down: bool,
+
+	#[arg(short, long)]
+	pub top: bool,
+
+	#[arg(short, long)]
+	pub bottom: bool,
+
+	// Text file with the whole load order, one addon name per line
+	#[arg(short, long, value_name = "FILE_PATH")]
+	pub file: Option<String>,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `enable` and `disable` subcommands
+#[derive(Parser, Debug)]
+pub struct ToggleArgs {
+	#[arg(value_name = "NAME")]
+	pub names: Vec<String>,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `profile` subcommand
+#[derive(Parser, Debug)]
+pub struct ProfileArgs {
+	#[command(subcommand)]
+	pub action: ProfileAction,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Actions of the `profile` subcommand
+#[derive(Parser, Debug)]
+pub enum ProfileAction {
+	// List saved profiles
+	List,
+	// Print the addons of a profile
+	Show {
+		#[arg(value_name = "NAME")]
+		name: String,
+	},
+	// Save the currently enabled addons (or the ones listed in a file) as a profile
+	Create {
+		#[arg(value_name = "NAME")]
+		name: String,
+
+		#[arg(short, long, value_name = "FILE_PATH")]
+		file: Option<String>,
+	},
+	// Delete a profile
+	Delete {
+		#[arg(value_name = "NAME")]
+		name: String,
+	},
+	// Show what activating a profile would change (or the differences between two profiles)
+	Diff {
+		#[arg(value_name = "NAME")]
+		name: String,
+
+		#[arg(value_name = "OTHER")]
+		other: Option<String>,
+	},
+	// Write a profile to a file, or print it
+	Export {
+		#[arg(value_name = "NAME")]
+		name: String,
+
+		#[arg(short, long, value_name = "FILE_PATH")]
+		output: Option<String>,
+	},
+	// Enable the addons of a profile, in its order, and disable all others
+	Activate {
+		#[arg(value_name = "NAME")]
+		name: String,
+	},
+}
+
+// Arguments for the `repair` subcommand
+#[derive(Parser, Debug)]
+pub struct RepairArgs {
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `doctor` subcommand
+#[derive(Parser, Debug)]
+pub struct DoctorArgs {
+	// Apply the fixes offered for the problems found
+	#[arg(short, long)]
+	pub fix: bool,
+
+	#[arg(long, value_enum, default_value_t)]
+	pub format: OutputFormat,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Arguments for the `backups` subcommand
+#[derive(Parser, Debug)]
+pub struct BackupsArgs {
+	#[command(subcommand)]
+	pub action: BackupsAction,
+
+	#[arg(short, long)]
+	pub verbose: bool,
+}
+
+// Actions of the `backups` subcommand
+#[derive(Parser, Debug)]
+pub enum BackupsAction {
+	// List the gameinfo.txt snapshots, newest first
+	List {
+		#[arg(long, value_enum, default_value_t)]
+		format: OutputFormat,
+	},
+	// Show what restoring a snapshot would change in gameinfo.txt
+	Diff {
+		#[arg(value_name = "ID")]
+		id: String,
+	},
+	// Put a snapshot back in place of gameinfo.txt
+	Restore {
+		#[arg(value_name = "ID")]
+		id: String,
+	},
+}
@@ -1,6 +1,5 @@
 // SPDX-License-Identifier: LGPL-3.0-only
 pub use clap::Parser;
-pub use colored::Colorize;
 pub use helptext::{Help, sections};
 // use path_dedot::ParseDot;
 pub use md5::{Digest, Md5};
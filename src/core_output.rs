@@ -0,0 +1,739 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use colored::Colorize;
+use crate::check_health::HealthReport;
+use crate::extract_addon::Extraction;
+use crate::find_conflicts::ConflictReport;
+use crate::gameinfo_history::{Snapshot, SnapshotRestore};
+use crate::inspect_addon::{InspectedAddon, Inventory, human_size};
+use crate::install_addon::{InstallStatus, InstallSummary};
+use crate::list_addons::InstalledAddon;
+use crate::manage_profiles::{Profile, ProfileDiff};
+use crate::order_addons::OrderApplied;
+use crate::pug_mode::{PugStatus, PugSwitch};
+use crate::repair_manifest::RepairReport;
+use crate::structured_output::{OutputFormat, write_json, write_tsv_row};
+use crate::transaction::PlannedStep;
+use crate::verify_vpk::{Verification, VerifyOutcome};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Everything the command line prints: the library only returns what it did.
+
+// The library logs what DEBUG and --verbose trace, and the warnings it cannot return
+// (e.g. a transaction rolled back while unwinding)
+struct Logger;
+
+impl log::Log for Logger {
+	fn enabled(&self, metadata: &log::Metadata) -> bool {
+		metadata.target().starts_with("l4addonenforcer")
+	}
+
+	fn log(&self, record: &log::Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		match record.level() {
+			log::Level::Error => eprintln!("{} {}", "Error:".red(), record.args()),
+			log::Level::Warn => eprintln!("{} {}", "Warning:".yellow(), record.args()),
+			log::Level::Info => println!("{}", record.args()),
+			log::Level::Debug | log::Level::Trace => println!("{} {}", "[D]".blue(), record.args()),
+		}
+	}
+
+	fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+// Whether DEBUG and --verbose are set is decided by the library, every record is printed
+pub fn init_logging() {
+	if log::set_logger(&LOGGER).is_ok() {
+		log::set_max_level(log::LevelFilter::Debug);
+	}
+}
+
+// Prints an error for the command line, and returns the exit code to quit with
+pub fn report(err: &(dyn Error + 'static)) -> i32 {
+	eprintln!("{} {}", "Error:".red(), enforcer_error::render(err));
+	if matches!(err.downcast_ref::<EnforcerError>(), Some(EnforcerError::Usage(_))) {
+		println!(
+			"Type {} / {} for more information",
+			"-h".blue(),
+			"--help".blue()
+		);
+	}
+	enforcer_error::exit_code(err)
+}
+
+fn warn(warning: &str) {
+	println!("{} {}", "Warning:".yellow(), warning);
+}
+
+// Prints a unified diff of two versions of a text file, coloured like `git diff`
+pub fn print_diff(old: &str, new: &str, old_name: &str, new_name: &str) {
+	for line in show_diff::unified_diff(old, new, old_name, new_name).lines() {
+		if line.starts_with("+++") || line.starts_with("---") {
+			println!("{}", line.bold());
+		} else if line.starts_with('+') {
+			println!("{}", line.green());
+		} else if line.starts_with('-') {
+			println!("{}", line.red());
+		} else if line.starts_with("@@") {
+			println!("{}", line.cyan());
+		} else {
+			println!("{}", line);
+		}
+	}
+}
+
+// What a dry run would have done: every step, then the changes to text files as diffs
+pub fn print_plan(plan: &[PlannedStep]) {
+	if plan.is_empty() {
+		return;
+	}
+	println!("{}", "Dry run, nothing was changed. Planned operations:".bold());
+	let width = plan.len().to_string().len();
+	for (position, step) in plan.iter().enumerate() {
+		println!("\t{:>width$}. {}", position + 1, step.description);
+	}
+	for (path, old, new) in plan.iter().filter_map(|step| step.diff.as_ref()) {
+		if old != new {
+			let name = path.display().to_string();
+			print_diff(old, new, &name, &format!("{} (planned)", name));
+		}
+	}
+}
+
+// A modified gameinfo.txt without a backup is turned into a vanilla one (reset, pug --switch)
+pub fn print_rebuilt(contents: &str, rebuilt: &str) {
+	warn("gameinfo.txt file seems to be modified, but no backup is present!");
+	println!("Rebuilding a vanilla gameinfo.txt by removing every addon from it:");
+	print_diff(contents, rebuilt, "gameinfo.txt", "gameinfo.txt (vanilla)");
+}
+
+// What `reset` is about to write, before it is confirmed
+pub fn print_reset_plan(plan: &ResetPlan) {
+	if plan.rebuilt {
+		print_rebuilt(&plan.current, &plan.reset);
+	} else {
+		println!("Restoring the backup of gameinfo.txt:");
+		print_diff(&plan.current, &plan.reset, "gameinfo.txt", "gameinfo.txt (backup)");
+	}
+}
+
+// Prints the summary of an installation. A single failing addon is returned as its own error
+// (and so exit code), several ones as a count.
+pub fn print_install_summary(mut summary: InstallSummary) -> Result<(), EnforcerError> {
+	for warning in &summary.warnings {
+		warn(warning);
+	}
+	for report in summary.results.iter().filter_map(|(_, outcome)| outcome.as_ref().ok()) {
+		for warning in &report.warnings {
+			warn(warning);
+		}
+		if report.stayed_disabled {
+			println!("{} {} is disabled, it stays disabled after the update.", "Note:".yellow(), report.name.italic());
+		}
+	}
+
+	println!("{}", "Summary:".bold());
+	for (label, outcome) in &summary.results {
+		match outcome {
+			Ok(report) if report.status == InstallStatus::Updated => {
+				println!("\t{}\t{} ({})", "Updated".blue(), report.name.italic(), label)
+			},
+			Ok(report) => println!("\t{}\t{} ({})", "Installed".green(), report.name.italic(), label),
+			Err(e) => println!("\t{}\t{}: {}", "Failed".red(), label, e.to_string().trim()),
+		}
+	}
+	for (archive, skipped) in &summary.skipped {
+		println!("\t{}\t{}: {}", "Skipped".yellow(), archive, skipped);
+	}
+	if summary.has_corrupt() {
+		println!("{} Corrupt addons can be installed anyway with {}.", "Note:".yellow(), "--force".blue());
+	}
+	print_plan(&summary.plan);
+	let failed = summary.failed();
+	// A single addon keeps its own error, and so its exit code
+	if summary.results.len() == 1 && failed == 1 {
+		if let Some((_, Err(e))) = summary.results.pop() {
+			return Err(e);
+		}
+	}
+	if failed > 0 {
+		let err = format!("{} of {} addons failed to install.", failed, summary.results.len());
+		return Err(EnforcerError::Other(err));
+	}
+	Ok(())
+}
+
+pub fn print_addons(
+	addons: &[InstalledAddon],
+	quiet: bool,
+	verbose: bool,
+	details: bool,
+	format: OutputFormat,
+	buf_writer: &mut impl Write,
+) -> Result<(), EnforcerError> {
+	match format {
+		OutputFormat::Json => return write_json(buf_writer, &addons),
+		OutputFormat::Tsv => return write_addons_tsv(buf_writer, addons),
+		OutputFormat::Text => {},
+	}
+
+	// List the installed custom addons
+	if !quiet {
+		writeln!(buf_writer, "{}", "Installed addons:".bold())?;
+	}
+	// Disabled addons are listed where their commented-out line is
+	for addon in addons {
+		let label = if addon.enabled { addon.name.clone() } else { format!("{} {}", addon.name, "(disabled)".dimmed()) };
+		writeln!(buf_writer, "\t{}", label)?;
+		if !details {
+			continue;
+		}
+		if verbose {
+			writeln!(buf_writer, "{} path: {}", &addon.name.green(), addon.path.join("pak01_dir.vpk").to_string_lossy().to_string().purple())?;
+		}
+		if !addon.vpk_present {
+			continue;
+		}
+		if let Some(info) = &addon.info {
+			for (label, value) in info.fields() {
+				// Multi-line descriptions stay indented under the addon
+				let value = value.lines().map(str::trim).collect::<Vec<_>>().join("\n\t\t\t");
+				writeln!(buf_writer, "\t\t{}: {}", label, value)?;
+			}
+		}
+		if let Some(e) = &addon.info_error {
+			writeln!(buf_writer, "\t\t{}", e.red())?;
+		}
+		if let Some(installed_at) = &addon.installed_at {
+			writeln!(buf_writer, "\t\tInstalled: {}", installed_at)?;
+		}
+		if let Some(updated_at) = &addon.updated_at {
+			writeln!(buf_writer, "\t\tUpdated: {}", updated_at)?;
+		}
+		if let Some(source) = &addon.source {
+			writeln!(buf_writer, "\t\tSource: {}", source)?;
+		}
+	}
+	Ok(())
+}
+
+// One row per addon; the addoninfo.txt keys the other columns do not cover are left out
+fn write_addons_tsv(out: &mut impl Write, addons: &[InstalledAddon]) -> Result<(), EnforcerError> {
+	write_tsv_row(out, &[
+		"load_order", "name", "enabled", "path", "vpk_present",
+		"title", "version", "author", "tagline", "content", "description",
+		"installed_at", "updated_at", "source",
+	])?;
+	for addon in addons {
+		let info = addon.info.clone().unwrap_or_default();
+		write_tsv_row(out, &[
+			addon.load_order.to_string(),
+			addon.name.clone(),
+			addon.enabled.to_string(),
+			addon.path.to_string_lossy().to_string(),
+			addon.vpk_present.to_string(),
+			info.title.unwrap_or_default(),
+			info.version.unwrap_or_default(),
+			info.author.unwrap_or_default(),
+			info.tagline.unwrap_or_default(),
+			info.content.kinds().join(", "),
+			info.description.unwrap_or_default(),
+			addon.installed_at.clone().unwrap_or_default(),
+			addon.updated_at.clone().unwrap_or_default(),
+			addon.source.clone().unwrap_or_default(),
+		])?;
+	}
+	Ok(())
+}
+
+pub fn print_pug_switch(switch: &PugSwitch) {
+	if switch.enabled {
+		println!("PuG Mode is now enabled.");
+	} else {
+		println!("PuG Mode is now disabled.");
+	}
+}
+
+pub fn print_pug_status(status: &PugStatus, format: OutputFormat) -> Result<(), EnforcerError> {
+	let mut out = std::io::stdout().lock();
+	match format {
+		OutputFormat::Json => write_json(&mut out, status)?,
+		OutputFormat::Tsv => {
+			write_tsv_row(&mut out, &["enabled", "recorded", "gameinfo_path", "custom_path"])?;
+			write_tsv_row(&mut out, &[
+				status.enabled.to_string(),
+				status.recorded.map(|recorded| recorded.to_string()).unwrap_or_default(),
+				status.gameinfo_path.to_string_lossy().to_string(),
+				status.custom_path.as_ref().map(|path| path.to_string_lossy().to_string()).unwrap_or_default(),
+			])?;
+		},
+		OutputFormat::Text => {
+			if !status.enabled {
+				println!("PuG Mode is currently disabled.");
+			} else {
+				println!("PuG Mode is currently enabled.");
+			}
+			// The custom copy may have been removed or restored by hand
+			if let Some(recorded) = status.recorded.filter(|recorded| *recorded != status.enabled) {
+				println!(
+					"{} The addon manifest says PuG Mode is {}, run {} to bring it up to date.",
+					"Warning:".yellow(),
+					if recorded { "enabled" } else { "disabled" },
+					"repair".italic()
+				);
+			}
+		},
+	}
+	Ok(())
+}
+
+// Prints the inventory of each target, then fails when some could not be inspected
+pub fn print_inspected(inspected: Vec<Result<InspectedAddon, EnforcerError>>, format: OutputFormat) -> Result<(), EnforcerError> {
+	if inspected.is_empty() && format == OutputFormat::Text {
+		println!("No addons are currently installed.");
+		return Ok(());
+	}
+	let total = inspected.len();
+	let mut addons = Vec::new();
+	let mut failed = 0;
+	for outcome in inspected {
+		match outcome {
+			Ok(addon) => addons.push(addon),
+			Err(e) => {
+				failed += 1;
+				eprintln!("{} {}", "Error:".red(), e);
+			},
+		}
+	}
+	let mut out = std::io::stdout().lock();
+	match format {
+		OutputFormat::Json => write_json(&mut out, &addons)?,
+		// One row per directory, the total being the "(total)" directory
+		OutputFormat::Tsv => {
+			write_tsv_row(&mut out, &["target", "vpk", "categories", "directory", "files", "size"])?;
+			for addon in &addons {
+				let categories = addon.inventory.categories.join(", ");
+				let rows = addon.inventory.dirs.iter().map(|(dir, stats)| (dir.as_str(), stats)).chain([("(total)", &addon.inventory.total)]);
+				for (dir, stats) in rows {
+					write_tsv_row(&mut out, &[
+						addon.target.clone(),
+						addon.vpk.to_string_lossy().to_string(),
+						categories.clone(),
+						dir.to_string(),
+						stats.files.to_string(),
+						stats.size.to_string(),
+					])?;
+				}
+			}
+		},
+		OutputFormat::Text => {
+			for addon in &addons {
+				print_inventory(&addon.target, &addon.inventory);
+			}
+		},
+	}
+	if failed > 0 {
+		let err = format!("{} of {} addons could not be inspected.", failed, total);
+		return Err(EnforcerError::Other(err));
+	}
+	Ok(())
+}
+
+fn print_inventory(label: &str, inventory: &Inventory) {
+	println!("{}", label.bold());
+	let categories = if inventory.categories.is_empty() { "Other".to_string() } else { inventory.categories.join(", ") };
+	println!("\tCategories: {}", categories.green());
+	let width = inventory.dirs.keys().map(|dir| dir.len()).max().unwrap_or(0).max("Directory".len());
+	println!("\t{}  {:>6}  {:>10}", format!("{:<width$}", "Directory").italic(), "Files", "Size");
+	for (dir, stats) in &inventory.dirs {
+		println!("\t{:<width$}  {:>6}  {:>10}", dir, stats.files, human_size(stats.size));
+	}
+	println!(
+		"\t{}  {:>6}  {:>10}",
+		format!("{:<width$}", "Total").bold(),
+		inventory.total.files,
+		human_size(inventory.total.size)
+	);
+}
+
+// The verification of one target, in the JSON output of `verify`
+#[derive(Serialize)]
+struct VerifiedTarget<'a> {
+	target: &'a str,
+	verification: Option<&'a Verification>,
+	error: Option<String>,
+}
+
+// Prints the verification of each target. A single failing target is returned as its own error
+// (and so exit code), several ones as a count.
+pub fn print_verifications(mut verifications: Vec<(String, VerifyOutcome)>, format: OutputFormat) -> Result<(), EnforcerError> {
+	if verifications.is_empty() && format == OutputFormat::Text {
+		println!("No addons are currently installed.");
+		return Ok(());
+	}
+	let mut out = std::io::stdout().lock();
+	match format {
+		OutputFormat::Json => {
+			let targets: Vec<VerifiedTarget> = verifications
+				.iter()
+				.map(|(target, outcome)| VerifiedTarget {
+					target,
+					verification: outcome.as_ref().ok(),
+					error: outcome.as_ref().err().map(|e| e.to_string()),
+				})
+				.collect();
+			write_json(&mut out, &targets)?;
+		},
+		OutputFormat::Tsv => {
+			write_tsv_row(&mut out, &["target", "intact", "vpk", "version", "files", "checksums", "error"])?;
+			for (target, outcome) in &verifications {
+				let row = match outcome {
+					Ok(verification) => [
+						target.clone(),
+						true.to_string(),
+						verification.vpk.to_string_lossy().to_string(),
+						verification.version.to_string(),
+						verification.entries.to_string(),
+						verification.checksums.to_string(),
+						String::new(),
+					],
+					Err(e) => [target.clone(), false.to_string(), String::new(), String::new(), String::new(), String::new(), e.to_string()],
+				};
+				write_tsv_row(&mut out, &row)?;
+			}
+		},
+		OutputFormat::Text => {},
+	}
+	let total = verifications.len();
+	let failed = verifications.iter().filter(|(_, outcome)| outcome.is_err()).count();
+	if total == 1 && failed == 1 {
+		if let Some((_, Err(e))) = verifications.pop() {
+			return Err(e);
+		}
+	}
+	if format == OutputFormat::Text {
+		for (target, outcome) in &verifications {
+			match outcome {
+				Ok(verification) => println!(
+					"{} {} ({} files, {} checksums, VPK v{})",
+					"Intact".green(),
+					target.italic(),
+					verification.entries,
+					verification.checksums,
+					verification.version
+				),
+				Err(e) => eprintln!("{} {}: {}", "Error:".red(), target, e),
+			}
+		}
+	}
+	if failed > 0 {
+		let err = format!("{} of {} addons failed verification.", failed, total);
+		return Err(EnforcerError::Other(err));
+	}
+	Ok(())
+}
+
+pub fn print_extraction(extraction: &Extraction) {
+	let total = human_size(extraction.files.iter().map(|file| file.size).sum());
+	match &extraction.output {
+		None => {
+			for file in &extraction.files {
+				let chunk = file.chunk.map(|chunk| format!(" (chunk {:03})", chunk)).unwrap_or_default();
+				println!("{:>10}  {}{}", human_size(file.size), file.path, chunk.italic());
+			}
+			println!("{} files, {}", extraction.files.len(), total);
+		},
+		// A dry run only planned the extraction
+		Some(output) if extraction.plan.is_empty() => {
+			println!("Extracted {} files ({}) to {}", extraction.files.len(), total, output.display().to_string().italic());
+		},
+		Some(..) => print_plan(&extraction.plan),
+	}
+}
+
+// Prints the conflicting files, grouped by which addon wins over which
+pub fn print_conflicts(report: &ConflictReport, format: OutputFormat) -> Result<(), EnforcerError> {
+	for (addon, reason) in &report.skipped {
+		warn(&format!("Skipping {}: {}", addon, reason));
+	}
+	let mut out = std::io::stdout().lock();
+	match format {
+		OutputFormat::Json => return write_json(&mut out, report),
+		// One row per file, the addons it is overridden in being comma separated
+		OutputFormat::Tsv => {
+			write_tsv_row(&mut out, &["path", "winner", "overridden"])?;
+			for conflict in &report.conflicts {
+				write_tsv_row(&mut out, &[conflict.path.clone(), conflict.addons[0].clone(), conflict.addons[1..].join(", ")])?;
+			}
+			return Ok(());
+		},
+		OutputFormat::Text => {},
+	}
+	let conflicts = &report.conflicts;
+	if conflicts.is_empty() {
+		println!("No conflicts found, no file is provided by more than one addon.");
+		return Ok(());
+	}
+
+	let mut groups: BTreeMap<&[String], Vec<&str>> = BTreeMap::new();
+	for conflict in conflicts {
+		groups.entry(&conflict.addons).or_default().push(&conflict.path);
+	}
+	println!("{} {} file(s) provided by more than one addon:", "Conflicts:".bold(), conflicts.len());
+	for (addons, paths) in groups {
+		println!(
+			"\n{} overrides {} in {} file(s):",
+			addons[0].green(),
+			addons[1..].iter().map(|a| a.yellow().to_string()).collect::<Vec<_>>().join(", "),
+			paths.len()
+		);
+		for path in paths {
+			println!("\t{}", path);
+		}
+	}
+	println!(
+		"\nAddons higher in the {} output take priority over the ones below them, see {} to change it.",
+		"list".italic(),
+		"order".italic()
+	);
+	Ok(())
+}
+
+pub fn print_order(order: &[String]) {
+	let width = order.len().to_string().len();
+	for (position, addon) in order.iter().enumerate() {
+		println!("\t{:>width$}. {}", position + 1, addon);
+	}
+}
+
+pub fn print_load_order(order: &[String]) {
+	if order.is_empty() {
+		println!("No addons are currently installed.");
+		return;
+	}
+	println!("{}", "Load order (highest priority first):".bold());
+	print_order(order);
+}
+
+pub fn print_order_applied(applied: &OrderApplied, file: &str) {
+	if !applied.unlisted.is_empty() {
+		warn(&format!("Not listed in {}, kept at the bottom: {}", file, applied.unlisted.join(", ")));
+	}
+	if !applied.changed {
+		println!("The load order is already as listed in {}.", file);
+		return;
+	}
+	println!("Applied the load order from {}.", file.italic());
+	print_order(&applied.order);
+}
+
+pub fn print_profiles(profiles: &[Profile]) {
+	if profiles.is_empty() {
+		println!("No profiles saved yet, see {}.", "profile create".italic());
+		return;
+	}
+	println!("{}", "Profiles:".bold());
+	for profile in profiles {
+		if profile.active {
+			println!("\t{} {}", profile.name, "(active)".green());
+		} else {
+			println!("\t{} ({} addons)", profile.name, profile.addons.len());
+		}
+	}
+}
+
+pub fn print_profile(name: &str, addons: &[String]) {
+	println!("{}", format!("Profile {} (highest priority first):", name).bold());
+	if addons.is_empty() {
+		println!("\tNo addons enabled.");
+	}
+	print_order(addons);
+}
+
+pub fn print_profile_diff(diff: &ProfileDiff, name: &str, other: Option<&str>) {
+	match other {
+		Some(other) => println!("{}", format!("Going from profile {} to profile {}:", other, name).bold()),
+		None => println!("{}", format!("Activating profile {} would change:", name).bold()),
+	}
+	println!("{}", diff.describe());
+}
+
+pub fn print_profile_activated(diff: &ProfileDiff, name: &str) {
+	if diff.is_empty() {
+		println!("Profile {} is already active.", name.italic());
+		return;
+	}
+	println!("Activated profile {}:", name.italic());
+	println!("{}", diff.describe());
+}
+
+pub fn print_repair(report: &RepairReport) {
+	for warning in &report.warnings {
+		warn(warning);
+	}
+	if report.fixes.is_empty() {
+		println!("gameinfo.txt and the addon manifest agree, nothing to repair.");
+	} else {
+		println!("{}", "Repaired:".bold());
+		for fix in &report.fixes {
+			println!("\t{}", fix);
+		}
+	}
+}
+
+// The report of `doctor` in JSON, with the version that made it
+#[derive(Serialize)]
+struct VersionedHealthReport<'a> {
+	version: &'a str,
+	#[serde(flatten)]
+	report: &'a HealthReport,
+}
+
+// The report of `doctor`, meant to be attached to bug reports
+pub fn print_health(report: &HealthReport, format: OutputFormat) -> Result<(), EnforcerError> {
+	let mut out = std::io::stdout().lock();
+	match format {
+		OutputFormat::Json => {
+			return write_json(&mut out, &VersionedHealthReport { version: env!("CARGO_PKG_VERSION"), report });
+		},
+		// One row per problem, then one per fix `repair` made (which has no problem)
+		OutputFormat::Tsv => {
+			write_tsv_row(&mut out, &["problem", "fix", "fixed"])?;
+			for finding in &report.findings {
+				write_tsv_row(&mut out, &[finding.problem.clone(), finding.fix.clone().unwrap_or_default(), finding.fixed.to_string()])?;
+			}
+			if let Some(repair) = &report.repair {
+				for warning in &repair.warnings {
+					warn(warning);
+				}
+				for fix in &repair.fixes {
+					write_tsv_row(&mut out, &[String::new(), fix.clone(), true.to_string()])?;
+				}
+			}
+			return Ok(());
+		},
+		OutputFormat::Text => {},
+	}
+	println!("{} {}", env!("CARGO_PKG_NAME").bold(), env!("CARGO_PKG_VERSION"));
+	if let Some(game_dir) = &report.game_dir {
+		println!("\tGame directory: {}", game_dir.display());
+	}
+	if let Some(gameinfo) = &report.gameinfo {
+		println!("\tgameinfo.txt: {}", gameinfo);
+	}
+	if let (Some(md5), Some(normalized), Some(line_endings)) = (&report.gameinfo_md5, &report.normalized_md5, &report.line_endings) {
+		println!("\tgameinfo.txt MD5: {} (normalized: {}, {} line endings)", md5, normalized, line_endings);
+	}
+	if let Some(pug_mode) = report.pug_mode {
+		println!("\tPuG mode: {}", if pug_mode { "enabled" } else { "disabled" });
+	}
+	if let (Some(addons), Some(disabled)) = (report.addons, report.disabled_addons) {
+		println!("\tAddons: {} ({} disabled)", addons, disabled);
+	}
+
+	if report.findings.is_empty() {
+		println!("No problems found.");
+		return Ok(());
+	}
+	println!("{}", "Problems found:".bold());
+	for finding in &report.findings {
+		println!("\t{} {}", "-".red(), finding.problem);
+		if let Some(fix) = finding.fix.as_ref().filter(|_| !finding.fixed) {
+			println!("\t  {} {}", "Fix:".green(), fix);
+		}
+	}
+	for fix in report.findings.iter().filter(|finding| finding.fixed).filter_map(|finding| finding.fix.as_ref()) {
+		println!("{} {}", "Fixed:".green(), fix);
+	}
+	if let Some(repair) = &report.repair {
+		print_repair(repair);
+	}
+	let fixable = report.findings.iter().filter(|finding| finding.fix.is_some() && !finding.fixed).count();
+	if fixable > 0 {
+		println!("Run {} to apply the {} available fix(es).", "doctor --fix".italic(), fixable);
+	}
+	Ok(())
+}
+
+// A snapshot in the JSON output of `backups list`
+#[derive(Serialize)]
+struct ListedSnapshot<'a> {
+	#[serde(flatten)]
+	snapshot: &'a Snapshot,
+	addons: Option<usize>,
+}
+
+pub fn print_snapshots(snapshots: &[Snapshot], format: OutputFormat) -> Result<(), EnforcerError> {
+	let mut out = std::io::stdout().lock();
+	match format {
+		OutputFormat::Json => {
+			let listed: Vec<ListedSnapshot> =
+				snapshots.iter().map(|snapshot| ListedSnapshot { snapshot, addons: snapshot.addon_count() }).collect();
+			return write_json(&mut out, &listed);
+		},
+		OutputFormat::Tsv => {
+			write_tsv_row(&mut out, &["id", "taken_at", "command", "addons", "path"])?;
+			for snapshot in snapshots {
+				write_tsv_row(&mut out, &[
+					snapshot.id.clone(),
+					snapshot.taken_at.clone(),
+					snapshot.command.clone(),
+					snapshot.addon_count().map(|count| count.to_string()).unwrap_or_default(),
+					snapshot.path.to_string_lossy().to_string(),
+				])?;
+			}
+			return Ok(());
+		},
+		OutputFormat::Text => {},
+	}
+	if snapshots.is_empty() {
+		println!("No gameinfo.txt snapshots yet, one is saved by every command changing it.");
+		return Ok(());
+	}
+	println!("{}", "gameinfo.txt snapshots (newest first):".bold());
+	let width = snapshots.len().to_string().len();
+	for (position, snapshot) in snapshots.iter().enumerate() {
+		let addons = snapshot.addon_count().map(|count| format!("{} addons", count)).unwrap_or_else(|| "unreadable".to_string());
+		println!(
+			"\t{:>width$}. {}  {}  before {} ({})",
+			position + 1,
+			snapshot.id,
+			snapshot.taken_at,
+			snapshot.command.italic(),
+			addons
+		);
+	}
+	Ok(())
+}
+
+pub fn print_snapshot_diff(snapshot: &Snapshot, current: &str, contents: &str) {
+	if current == contents {
+		println!("gameinfo.txt is the same as snapshot {}.", snapshot.id);
+		return;
+	}
+	print_diff(current, contents, "gameinfo.txt", &format!("{} ({})", snapshot.id, snapshot.taken_at));
+}
+
+pub fn print_snapshot_restore(restore: &SnapshotRestore) {
+	let snapshot = &restore.snapshot;
+	let Some((current, contents)) = &restore.diff else {
+		println!("gameinfo.txt is already the same as snapshot {}.", snapshot.id);
+		return;
+	};
+	print_diff(current, contents, "gameinfo.txt", &format!("{} ({})", snapshot.id, snapshot.taken_at));
+	println!("Restored gameinfo.txt from snapshot {}.", snapshot.id);
+	// Addons uninstalled since the snapshot was taken are listed again, but their files are gone
+	if !restore.missing.is_empty() {
+		println!(
+			"{} The snapshot lists addons whose files are gone: {} \n\tRun {} to remove their entries.",
+			"Warning:".yellow(),
+			restore.missing.join(", "),
+			"doctor --fix".italic()
+		);
+	}
+}
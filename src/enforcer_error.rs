@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+
+// Every error is returned up to the front-end without being printed where it happens:
+// the command line prints it with `report` and exits with its code, the GUI shows `render` in a dialog.
+// Errors that fit no kind below are `Other`, as are foreign errors (I/O, JSON...) for `exit_code`.
+#[derive(Debug, thiserror::Error)]
+pub enum EnforcerError {
+	#[error("{0}")]
+	Other(String),
+
+	// Missing or conflicting command line arguments
+	#[error("{0}")]
+	Usage(String),
+
+	#[error("Failed to find L4D2 install location: {0}")]
+	GameNotFound(String),
+
+	#[error("Unable to locate gameinfo.txt file at {}. Is the game installation broken?", .0.display())]
+	GameinfoMissing(PathBuf),
+
+	#[error(
+		"gameinfo.txt file seems to be modified, but no backup is present, and a custom gameinfo.txt already exists!\n\tRun doctor to find out which one to keep.\n\tYour gameinfo.txt MD5 hash is: {md5}"
+	)]
+	GameinfoModifiedNoBackup { md5: String },
+
+	#[error("{name} not found in the gameinfo.txt file!{}", did_you_mean(.candidates))]
+	AddonNotFound { name: String, candidates: Vec<String> },
+
+	#[error("{0} is disabled! Enable it first.")]
+	AddonDisabled(String),
+
+	#[error("{0} is already installed! \n\t Please choose a different name.")]
+	AlreadyInstalled(String),
+
+	#[error(
+		"Invalid addon name \"{0}\"! \n\tName cannot be empty, contain whitespace, or special characters \n\tthat are known to cause problems with file managers or filesystems."
+	)]
+	InvalidName(String),
+
+	#[error("\"{0}\" is one of the core game components, which cannot be installed, renamed, uninstalled, enabled or disabled!")]
+	CoreComponentProtected(String),
+
+	#[error("Failed to read VPK file {}: {reason}", .path.display())]
+	VpkParse { path: PathBuf, reason: String },
+
+	// Parts of a VPK that do not match their CRC32 or MD5 checksums
+	#[error("VPK file {} is corrupt!{}", .path.display(), list_problems(.problems))]
+	VpkCorrupt { path: PathBuf, problems: Vec<String> },
+}
+
+impl EnforcerError {
+	// The process exit status, listed under EXIT CODES in the help. Failures without a kind exit with 1.
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			EnforcerError::Other(_) => 1,
+			EnforcerError::Usage(_) => 2,
+			EnforcerError::GameNotFound(_) => 3,
+			EnforcerError::GameinfoMissing(_) => 4,
+			EnforcerError::GameinfoModifiedNoBackup { .. } => 5,
+			EnforcerError::AddonNotFound { .. } => 6,
+			EnforcerError::AddonDisabled(_) => 7,
+			EnforcerError::AlreadyInstalled(_) => 8,
+			EnforcerError::InvalidName(_) => 9,
+			EnforcerError::CoreComponentProtected(_) => 10,
+			EnforcerError::VpkParse { .. } => 11,
+			EnforcerError::VpkCorrupt { .. } => 12,
+		}
+	}
+}
+
+fn did_you_mean(candidates: &[String]) -> String {
+	if candidates.is_empty() { String::new() } else { format!("\n\tDid you mean: {}?", candidates.join(", ")) }
+}
+
+// A half-downloaded addon can have every file damaged, the first ones are enough
+fn list_problems(problems: &[String]) -> String {
+	const SHOWN: usize = 10;
+	let mut list: String = problems.iter().take(SHOWN).map(|problem| format!("\n\t{}", problem)).collect();
+	if problems.len() > SHOWN {
+		list += &format!("\n\t... and {} more", problems.len() - SHOWN);
+	}
+	list
+}
+
+impl From<Box<dyn Error>> for EnforcerError {
+	fn from(err: Box<dyn Error>) -> Self {
+		match err.downcast::<EnforcerError>() {
+			Ok(err) => *err,
+			Err(err) => EnforcerError::Other(err.to_string()),
+		}
+	}
+}
+
+impl From<std::io::Error> for EnforcerError {
+	fn from(err: std::io::Error) -> Self {
+		EnforcerError::Other(err.to_string())
+	}
+}
+
+impl From<serde_json::Error> for EnforcerError {
+	fn from(err: serde_json::Error) -> Self {
+		EnforcerError::Other(err.to_string())
+	}
+}
+
+impl From<zip::result::ZipError> for EnforcerError {
+	fn from(err: zip::result::ZipError) -> Self {
+		EnforcerError::Other(err.to_string())
+	}
+}
+
+pub fn exit_code(err: &(dyn Error + 'static)) -> i32 {
+	err.downcast_ref::<EnforcerError>().map_or(1, EnforcerError::exit_code)
+}
+
+// The message of an error, as shown by the GUI
+pub fn render(err: &(dyn Error + 'static)) -> String {
+	err.to_string().trim().to_string()
+}
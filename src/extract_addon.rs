@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::inspect_addon::{read_game, target_vpk};
+use crate::transaction::{PlannedStep, Transaction};
+use crate::vpk_getdata::{EMBEDDED_ARCHIVE, Vpk, entry_path, open_vpk, read_entry};
+use glob::{MatchOptions, Pattern};
+use serde::Serialize;
+use std::path::Component;
+
+// Paths are matched like the game looks them up: case-insensitively.
+// `*` stays within a directory, `**` goes through any number of them.
+const MATCH_OPTIONS: MatchOptions =
+	MatchOptions { case_sensitive: false, require_literal_separator: true, require_literal_leading_dot: false };
+
+// A file held by a VPK
+#[derive(Debug, Clone, Serialize)]
+pub struct VpkFile {
+	// As in the VPK, e.g. "materials/vgui/hud/icon.vtf"
+	pub path: String,
+	pub size: u64,
+	// The chunk its data is in, none when it is in the _dir.vpk itself
+	pub chunk: Option<u16>,
+}
+
+// The files of a VPK matching any of `patterns` (all of them without patterns), sorted by path.
+// A pattern is a path or a glob like "materials/vgui/**", and a directory stands for everything in it.
+pub fn vpk_files(vpk_file: &Path, patterns: &[String]) -> Result<Vec<VpkFile>, EnforcerError> {
+	let patterns = compile_patterns(patterns)?;
+	let (_, vpk) = open_vpk(vpk_file)?;
+	Ok(matching_files(&vpk, &patterns).into_iter().map(|(_, file)| file).collect())
+}
+
+// The matching files along with their directory tree keys
+fn matching_files<'a>(vpk: &'a Vpk, patterns: &[Pattern]) -> Vec<(&'a String, VpkFile)> {
+	let mut files: Vec<(&String, VpkFile)> = vpk
+		.tree
+		.files
+		.iter()
+		.map(|(key, entry)| {
+			let file = VpkFile {
+				path: entry_path(key),
+				size: entry.preload_length as u64 + entry.entry_length as u64,
+				chunk: (entry.entry_length > 0 && entry.archive_index != EMBEDDED_ARCHIVE).then_some(entry.archive_index),
+			};
+			(key, file)
+		})
+		.filter(|(_, file)| patterns.is_empty() || patterns.iter().any(|pattern| pattern.matches_with(&file.path, MATCH_OPTIONS)))
+		.collect();
+	files.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+	files
+}
+
+// Each pattern, and the same pattern followed by "/**" so that a directory matches its contents
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, EnforcerError> {
+	let mut compiled = Vec::new();
+	for pattern in patterns {
+		let normalized = pattern.replace('\\', "/");
+		let normalized = normalized.trim_start_matches("./").trim_matches('/');
+		for text in [normalized.to_string(), format!("{}/**", normalized)] {
+			let glob = Pattern::new(&text)
+				.map_err(|e| EnforcerError::Usage(format!("Invalid pattern \"{}\": {}", pattern, e.msg)))?;
+			compiled.push(glob);
+		}
+	}
+	Ok(compiled)
+}
+
+// Writes the files of a VPK matching `patterns` (see `vpk_files`) below `output`, keeping
+// their paths, and returns where they were written with the steps a dry run planned. Files already there are replaced.
+// Nothing is left behind if one of them cannot be read.
+pub fn extract_files(
+	vpk_file: &Path,
+	patterns: &[String],
+	output: &Path,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<(Vec<PathBuf>, Vec<PlannedStep>), EnforcerError> {
+	let patterns = compile_patterns(patterns)?;
+	let (mut file, vpk) = open_vpk(vpk_file)?;
+	let files = matching_files(&vpk, &patterns);
+
+	let mut tx = Transaction::new(dry_run, verbose);
+	let mut written = Vec::with_capacity(files.len());
+	for (key, vpk_entry) in &files {
+		// A crafted VPK could hold "../" paths, which would land outside of `output`
+		let relative = Path::new(&vpk_entry.path);
+		if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+			return Err(EnforcerError::VpkParse {
+				path: vpk_file.to_path_buf(),
+				reason: format!("{} would be extracted outside of {}", vpk_entry.path, output.display()),
+			});
+		}
+		let data = read_entry(vpk_file, &mut file, &vpk, key)?;
+		let destination = output.join(relative);
+		if let Some(parent) = destination.parent() {
+			tx.create_dir(parent)?;
+		}
+		tx.write(&destination, data)?;
+		written.push(destination);
+	}
+	let plan = tx.commit()?;
+	Ok((written, plan))
+}
+
+// What `extract_addon` did
+#[derive(Debug, Clone)]
+pub struct Extraction {
+	// The matching files
+	pub files: Vec<VpkFile>,
+	// Where they were extracted to, none when they were only listed
+	pub output: Option<PathBuf>,
+	// What a dry run would have done
+	pub plan: Vec<PlannedStep>,
+}
+
+// Lists (`list`) or extracts the files of an installed addon or a VPK file matching `patterns`.
+// `game_dir` is only needed for an installed addon.
+// Without `output`, files are extracted to a directory named after the addon in the current one.
+pub fn extract_addon(
+	game_dir: Option<&Path>,
+	target: &str,
+	patterns: &[String],
+	output: Option<&str>,
+	list: bool,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<Extraction, EnforcerError> {
+	// VPK files can be read without the game being installed
+	let game = read_game(game_dir, verbose)?;
+	let vpk_file = target_vpk(target, game.as_ref())?;
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Reading {:?}", vpk_file);
+	}
+	let files = vpk_files(&vpk_file, patterns)?;
+	if files.is_empty() {
+		let err = if patterns.is_empty() {
+			format!("{} holds no files!", target)
+		} else {
+			format!("No file of {} matches {}!", target, patterns.join(", "))
+		};
+		return Err(EnforcerError::Other(err));
+	}
+	if list {
+		return Ok(Extraction { files, output: None, plan: Vec::new() });
+	}
+
+	let output = match output {
+		Some(output) => PathBuf::from(output),
+		None => PathBuf::from(default_output(target, &vpk_file)),
+	};
+	let (_, plan) = extract_files(&vpk_file, patterns, &output, dry_run, verbose)?;
+	Ok(Extraction { files, output: Some(output), plan })
+}
+
+// The addon name, or the name of the VPK file without "_dir" and ".vpk"
+fn default_output(target: &str, vpk_file: &Path) -> String {
+	if !Path::new(target).is_file() {
+		return target.to_string();
+	}
+	let stem = vpk_file.file_stem().unwrap_or_default().to_string_lossy();
+	let stem = stem.strip_suffix("_dir").or_else(|| stem.strip_suffix("_DIR")).unwrap_or(&stem);
+	stem.to_string()
+}
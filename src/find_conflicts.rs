@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::gameinfo::Gameinfo;
+use crate::vpk_getdata::{entry_path, open_vpk};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Files every addon ships, that are never loaded through SearchPaths
+const ADDON_METADATA: [&str; 3] = ["addoninfo.txt", "addonimage.jpg", "addonimage.vtf"];
+
+// A file provided by more than one addon.
+// `addons` follows the SearchPaths order, so the first one is what the game loads.
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+	pub path: String,
+	pub addons: Vec<String>,
+}
+
+// What `find_conflicts` found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConflictReport {
+	// Sorted by path
+	pub conflicts: Vec<Conflict>,
+	// The addons whose VPK could not be read, with the reason
+	pub skipped: Vec<(String, String)>,
+}
+
+// Reads the directory tree of every installed addon and returns the files found in more than one
+pub fn find_conflicts(game_dir: &Path, verbose: bool) -> Result<ConflictReport, EnforcerError> {
+	let gameinfo = Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?;
+
+	let mut report = ConflictReport::default();
+	let mut providers: HashMap<String, Vec<String>> = HashMap::new();
+	for addon in gameinfo.addons() {
+		let vpk_file = game_dir.join(&addon).join("pak01_dir.vpk");
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Reading {:?}", vpk_file);
+		}
+		let vpk = match open_vpk(&vpk_file) {
+			Ok((_, vpk)) => vpk,
+			Err(e) => {
+				report.skipped.push((addon, e.to_string()));
+				continue;
+			},
+		};
+		for key in vpk.tree.files.keys() {
+			// The game looks files up case-insensitively
+			let path = entry_path(key).to_lowercase();
+			if ADDON_METADATA.contains(&path.as_str()) {
+				continue;
+			}
+			let addons = providers.entry(path).or_default();
+			if !addons.contains(&addon) {
+				addons.push(addon.clone());
+			}
+		}
+	}
+
+	report.conflicts = providers
+		.into_iter()
+		.filter(|(_, addons)| addons.len() > 1)
+		.map(|(path, addons)| Conflict { path, addons })
+		.collect();
+	report.conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+	Ok(report)
+}
@@ -0,0 +1,397 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::keyvalues::{KvBlock, KvDocument, KvItem, KvPair, KvToken, KvValue};
+use std::ops::Range;
+
+// Search paths shipped with the game, these are never treated as addons
+pub const CORE_GAME_PATHS: [&str; 6] = [
+	"update",
+	"left4dead2_dlc3",
+	"left4dead2_dlc2",
+	"left4dead2_dlc1",
+	"hl2",
+	"|gameinfo_path|.",
+];
+
+// Directories of the game itself holding a pak01_dir.vpk, besides the core search paths
+pub const VANILLA_DIRS: [&str; 3] = ["left4dead2", "left4dead2_lv", "platform"];
+
+const SEARCH_PATHS: [&str; 3] = ["GameInfo", "FileSystem", "SearchPaths"];
+
+pub fn is_core_component(name: &str) -> bool {
+	CORE_GAME_PATHS.iter().any(|core| core.eq_ignore_ascii_case(name))
+}
+
+// A directory of the game itself, core search path or not
+pub fn is_game_dir(name: &str) -> bool {
+	is_core_component(name) || VANILLA_DIRS.iter().any(|vanilla| vanilla.eq_ignore_ascii_case(name))
+}
+
+// `Game`, but also combined keys like `game+mod`. `GameBin` and friends are left alone.
+fn is_game_key(key: &str) -> bool {
+	key.split('+').any(|part| part.eq_ignore_ascii_case("Game"))
+}
+
+// A disabled addon: its `Game` line commented out by `disable_addon`
+struct DisabledEntry {
+	// Index of the trivia item (within SearchPaths) holding the comment
+	item: usize,
+	// Byte offsets of the line (without its line break), of the `//` and of the addon name
+	line: Range<usize>,
+	marker: usize,
+	name: Range<usize>,
+	value: String,
+}
+
+// Reads a commented-out `Game <name>` pair (`//Game\t\tname`), returning the offsets
+// of the `//` and of the name within `line`
+fn parse_disabled(line: &str) -> Option<(usize, Range<usize>)> {
+	let marker = line.len() - line.trim_start().len();
+	let body = line[marker..].strip_prefix("//")?;
+	// Anything after another `//` is a comment on the commented-out line
+	let body = &body[..body.find("//").unwrap_or(body.len())];
+	let start = marker + 2;
+	let mut tokens = Vec::new();
+	let mut token_start = None;
+	for (i, c) in body.char_indices().chain([(body.len(), ' ')]) {
+		match (c.is_whitespace(), token_start) {
+			(false, None) => token_start = Some(i),
+			(true, Some(from)) => {
+				tokens.push(start + from..start + i);
+				token_start = None;
+			},
+			_ => {},
+		}
+	}
+	let [key, value] = tokens.as_slice() else {
+		return None;
+	};
+	if !is_game_key(line[key.clone()].trim_matches('"')) {
+		return None;
+	}
+	let quoted = line[value.clone()].len() > 1 && line[value.clone()].starts_with('"') && line[value.clone()].ends_with('"');
+	let name = if quoted { value.start + 1..value.end - 1 } else { value.clone() };
+	if name.is_empty() || is_core_component(&line[name.clone()]) {
+		return None;
+	}
+	Some((marker, name))
+}
+
+pub struct Gameinfo {
+	pub doc: KvDocument,
+}
+
+impl Gameinfo {
+	pub fn parse(contents: &str) -> Result<Gameinfo, EnforcerError> {
+		let doc = KvDocument::parse(contents).map_err(|e| {
+			let err = format!("Failed to parse gameinfo.txt ({})", e);
+			EnforcerError::Other(err)
+		})?;
+		let gameinfo = Gameinfo { doc };
+		if gameinfo.search_paths().is_none() {
+			let err = "gameinfo.txt has no GameInfo/FileSystem/SearchPaths block!".to_string();
+			return Err(EnforcerError::Other(err));
+		}
+		Ok(gameinfo)
+	}
+
+	pub fn read(gameinfo_path: &Path) -> Result<Gameinfo, EnforcerError> {
+		let contents = read_to_string(gameinfo_path)?;
+		Gameinfo::parse(&contents)
+	}
+
+	pub fn search_paths(&self) -> Option<&KvBlock> {
+		self.doc.root.find_block(&SEARCH_PATHS)
+	}
+
+	fn search_paths_mut(&mut self) -> Option<&mut KvBlock> {
+		self.doc.root.find_block_mut(&SEARCH_PATHS)
+	}
+
+	// Item indices (within SearchPaths) and values of all `Game` entries, in load order
+	fn game_items(&self) -> Vec<(usize, String)> {
+		let Some(block) = self.search_paths() else {
+			return Vec::new();
+		};
+		block.items.iter().enumerate()
+			.filter_map(|(i, item)| match item {
+				KvItem::Pair(pair) if is_game_key(&pair.key.text) => pair.value.as_str().map(|v| (i, v.to_string())),
+				_ => None,
+			})
+			.collect()
+	}
+
+	// Commented-out `Game` lines of disabled addons. The first line of a trivia item is the rest
+	// of the line before it (e.g. a comment trailing a pair), so it is never one of them.
+	fn disabled_entries(&self) -> Vec<DisabledEntry> {
+		let Some(block) = self.search_paths() else {
+			return Vec::new();
+		};
+		let mut entries = Vec::new();
+		for (item, trivia) in block.items.iter().enumerate().filter_map(|(i, item)| match item {
+			KvItem::Trivia(trivia) => Some((i, trivia)),
+			KvItem::Pair(_) => None,
+		}) {
+			let mut offset = 0;
+			for (n, raw) in trivia.split('\n').enumerate() {
+				let line = raw.strip_suffix('\r').unwrap_or(raw);
+				if n > 0 {
+					if let Some((marker, name)) = parse_disabled(line) {
+						entries.push(DisabledEntry {
+							item,
+							line: offset..offset + line.len(),
+							marker: offset + marker,
+							name: offset + name.start..offset + name.end,
+							value: line[name].to_string(),
+						});
+					}
+				}
+				offset += raw.len() + 1;
+			}
+		}
+		entries
+	}
+
+	fn find_disabled(&self, name: &str) -> Option<DisabledEntry> {
+		self.disabled_entries().into_iter().find(|entry| entry.value == name)
+	}
+
+	fn trivia_mut(&mut self, item: usize) -> &mut String {
+		match &mut self.search_paths_mut().unwrap().items[item] {
+			KvItem::Trivia(trivia) => trivia,
+			KvItem::Pair(_) => unreachable!(),
+		}
+	}
+
+	// Every `Game` search path, core components included
+	pub fn game_entries(&self) -> Vec<String> {
+		self.game_items().into_iter().map(|(_, name)| name).collect()
+	}
+
+	// Installed custom addons, in load order
+	pub fn addons(&self) -> Vec<String> {
+		self.game_entries().into_iter().filter(|name| !is_core_component(name)).collect()
+	}
+
+	pub fn has_addon(&self, name: &str) -> bool {
+		self.find_game(name).is_some()
+	}
+
+	pub fn is_disabled(&self, name: &str) -> bool {
+		self.find_disabled(name).is_some()
+	}
+
+	// Every addon with whether it is enabled, in the order of their lines in SearchPaths
+	pub fn addon_states(&self) -> Vec<(String, bool)> {
+		let mut states: Vec<((usize, usize), String, bool)> = self
+			.game_items()
+			.into_iter()
+			.filter(|(_, name)| !is_core_component(name))
+			.map(|(item, name)| ((item, 0), name, true))
+			.collect();
+		states.extend(self.disabled_entries().into_iter().map(|entry| ((entry.item, entry.marker), entry.value, false)));
+		states.sort_by_key(|(position, _, _)| *position);
+		states.into_iter().map(|(_, name, enabled)| (name, enabled)).collect()
+	}
+
+	// Installed addons whose names resemble `name`, best matches first. Only used for
+	// suggestions, nothing is ever done to an addon that is not an exact match.
+	pub fn similar_addons(&self, name: &str) -> Vec<String> {
+		let wanted = name.to_lowercase();
+		let mut candidates: Vec<(usize, String)> = self
+			.addon_states()
+			.into_iter()
+			.filter_map(|(addon, _)| {
+				let lower = addon.to_lowercase();
+				let distance = levenshtein(&wanted, &lower);
+				if lower.contains(&wanted) || wanted.contains(&lower) || distance <= (wanted.chars().count() / 3).max(1) {
+					Some((distance, addon))
+				} else {
+					None
+				}
+			})
+			.collect();
+		candidates.sort();
+		candidates.into_iter().map(|(_, addon)| addon).collect()
+	}
+
+	// Error for a name without an exact `Game <name>` entry, listing close candidates
+	pub fn missing_addon(&self, name: &str) -> EnforcerError {
+		if self.is_disabled(name) {
+			return EnforcerError::AddonDisabled(name.to_string());
+		}
+		EnforcerError::AddonNotFound { name: name.to_string(), candidates: self.similar_addons(name) }
+	}
+
+	fn find_game(&self, name: &str) -> Option<usize> {
+		self.game_items().into_iter().find(|(_, value)| value == name).map(|(i, _)| i)
+	}
+
+	// Adds `Game <name>` right above `Game update`, so the addon takes priority over the game files
+	pub fn add_addon(&mut self, name: &str) -> Result<(), EnforcerError> {
+		let items = self.game_items();
+		let Some(&(anchor, _)) = items.iter().find(|(_, value)| value == "update").or(items.first()) else {
+			let err = "gameinfo.txt has no Game search paths to place the addon next to!".to_string();
+			return Err(EnforcerError::Other(err));
+		};
+		let block = self.search_paths_mut().unwrap();
+		let reference = block.pair_at(anchor).unwrap();
+		let pair = KvPair::new(reference.key.clone(), &reference.gap, KvValue::Str(KvToken::new(name)));
+		block.insert_line_before(anchor, pair);
+		Ok(())
+	}
+
+	// Removes the addon's `Game` line, whether it is enabled or disabled
+	pub fn remove_addon(&mut self, name: &str) -> bool {
+		if let Some(index) = self.find_game(name) {
+			return self.search_paths_mut().unwrap().remove_line(index).is_some();
+		}
+		match self.find_disabled(name) {
+			Some(entry) => {
+				// The line break and indentation leading to the line go with it
+				let trivia = self.trivia_mut(entry.item);
+				let start = if trivia[..entry.line.start].ends_with("\r\n") { entry.line.start - 2 } else { entry.line.start - 1 };
+				trivia.replace_range(start..entry.line.end, "");
+				true
+			},
+			None => false,
+		}
+	}
+
+	// Comments out the addon's `Game` line. The addon stays installed, but the game no longer loads it.
+	pub fn disable_addon(&mut self, name: &str) -> bool {
+		match self.find_game(name) {
+			Some(index) => self.search_paths_mut().unwrap().comment_out(index),
+			None => false,
+		}
+	}
+
+	// Brings back the `Game` line of a disabled addon, at the position it was disabled at
+	pub fn enable_addon(&mut self, name: &str) -> Result<bool, EnforcerError> {
+		let Some(entry) = self.find_disabled(name) else {
+			return Ok(false);
+		};
+		self.trivia_mut(entry.item).replace_range(entry.marker..entry.marker + 2, "");
+		// The uncommented line has to become a pair again
+		*self = Gameinfo::parse(&self.doc.to_string())?;
+		Ok(true)
+	}
+
+	// Puts the addon `Game` entries in the given order (a permutation of `addons()`).
+	// The entries trade places between the lines already holding addons,
+	// so core components and everything else in the file stay where they are.
+	pub fn reorder_addons(&mut self, order: &[String]) -> Result<(), EnforcerError> {
+		let slots: Vec<(usize, String)> =
+			self.game_items().into_iter().filter(|(_, name)| !is_core_component(name)).collect();
+		let mut current: Vec<&String> = slots.iter().map(|(_, name)| name).collect();
+		let mut wanted: Vec<&String> = order.iter().collect();
+		current.sort();
+		wanted.sort();
+		if current != wanted {
+			let err = "The new load order has to list every installed addon exactly once!".to_string();
+			return Err(EnforcerError::Other(err));
+		}
+
+		let block = self.search_paths_mut().unwrap();
+		let mut pairs: Vec<KvPair> = slots.iter().map(|(index, _)| block.pair_at(*index).unwrap().clone()).collect();
+		for ((index, _), name) in slots.iter().zip(order) {
+			let position = pairs.iter().position(|pair| pair.value.as_str() == Some(name.as_str())).unwrap();
+			*block.pair_at_mut(*index).unwrap() = pairs.remove(position);
+		}
+		Ok(())
+	}
+
+	// Enables exactly the given installed addons, in the given order, and disables every other one
+	pub fn set_enabled_addons(&mut self, enabled: &[String]) -> Result<(), EnforcerError> {
+		for (addon, is_enabled) in self.addon_states() {
+			let wanted = enabled.contains(&addon);
+			if wanted && !is_enabled {
+				self.enable_addon(&addon)?;
+			} else if !wanted && is_enabled {
+				self.disable_addon(&addon);
+			}
+		}
+		self.reorder_addons(enabled)
+	}
+
+	pub fn rename_addon(&mut self, name: &str, new_name: &str) -> bool {
+		match self.find_game(name) {
+			Some(index) => {
+				let pair = self.search_paths_mut().unwrap().pair_at_mut(index).unwrap();
+				if let KvValue::Str(token) = &mut pair.value {
+					token.text = new_name.to_string();
+				}
+				true
+			},
+			None => match self.find_disabled(name) {
+				Some(entry) => {
+					self.trivia_mut(entry.item).replace_range(entry.name, new_name);
+					true
+				},
+				None => false,
+			},
+		}
+	}
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = diagonal + usize::from(ca != *cb);
+			diagonal = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+		}
+	}
+	row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const GAMEINFO: &str = "\"GameInfo\"\n{\n\tFileSystem\n\t{\n\t\tSearchPaths\n\t\t{\n\t\t\tGame\tion_vocalizer\n\t\t\tGame\tIon_HUD\n\t\t\tGame\tslimhud\n\t\t\tGame\tupdate\n\t\t\tGame\tleft4dead2_dlc3\n\t\t\tGame\tleft4dead2\n\t\t}\n\t}\n}\n";
+
+	#[test]
+	fn levenshtein_counts_edits() {
+		assert_eq!(levenshtein("", ""), 0);
+		assert_eq!(levenshtein("", "hud"), 3);
+		assert_eq!(levenshtein("hud", "hud"), 0);
+		assert_eq!(levenshtein("kitten", "sitting"), 3);
+		assert_eq!(levenshtein("vocalizer", "vocaliser"), 1);
+		assert_eq!(levenshtein("slimhud", "slimhdu"), 2);
+		// Characters, not bytes
+		assert_eq!(levenshtein("größe", "grosse"), 3);
+	}
+
+	#[test]
+	fn similar_addons_suggests_close_names_best_first() {
+		let gameinfo = Gameinfo::parse(GAMEINFO).unwrap();
+		assert_eq!(gameinfo.similar_addons("ion_hud"), ["Ion_HUD"]);
+		assert_eq!(gameinfo.similar_addons("ion"), ["Ion_HUD", "ion_vocalizer"]);
+		assert_eq!(gameinfo.similar_addons("slimhdu"), ["slimhud"]);
+		assert_eq!(gameinfo.similar_addons("vocalizer"), ["ion_vocalizer"]);
+		assert!(gameinfo.similar_addons("campaign").is_empty());
+	}
+
+	#[test]
+	fn add_addon_quotes_names_that_need_it() {
+		let mut gameinfo = Gameinfo::parse(GAMEINFO).unwrap();
+		gameinfo.add_addon("my addon").unwrap();
+		let contents = gameinfo.doc.to_string();
+		assert!(contents.contains("\t\t\tGame\t\"my addon\"\n\t\t\tGame\tupdate\n"), "{}", contents);
+		assert!(Gameinfo::parse(&contents).unwrap().has_addon("my addon"));
+	}
+
+	#[test]
+	fn similar_addons_skips_core_components_but_not_disabled_addons() {
+		let mut gameinfo = Gameinfo::parse(GAMEINFO).unwrap();
+		assert!(gameinfo.similar_addons("updat").is_empty());
+		assert!(gameinfo.disable_addon("slimhud"));
+		assert_eq!(gameinfo.similar_addons("slimhdu"), ["slimhud"]);
+	}
+}
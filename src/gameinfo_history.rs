@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::Gameinfo;
+use crate::transaction::{PlannedStep, Transaction};
+use serde::Serialize;
+use std::time::SystemTime;
+
+// Every command changing gameinfo.txt first saves its previous contents here, next to gameinfo.txt,
+// one file per snapshot named "<YYYYMMDD>-<HHMMSS>-<command>.txt" (UTC), so names sort by age.
+const HISTORY_DIR: &str = "gameinfo_history";
+const SNAPSHOT_EXTENSION: &str = "txt";
+// How many snapshots are kept unless HISTORY_LIMIT says otherwise, the oldest ones go first
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+// A saved version of gameinfo.txt
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+	// The file name without its extension, e.g. "20261018-153012-install"
+	pub id: String,
+	// "2026-10-18 15:30:12 UTC"
+	pub taken_at: String,
+	// The command that was about to change gameinfo.txt
+	pub command: String,
+	pub path: PathBuf,
+}
+
+impl Snapshot {
+	fn from_path(path: PathBuf) -> Option<Snapshot> {
+		let id = path.file_stem()?.to_string_lossy().to_string();
+		let mut parts = id.splitn(3, '-');
+		let (date, time, command) = (parts.next()?, parts.next()?, parts.next()?);
+		if date.len() != 8 || time.len() != 6 || !date.chars().chain(time.chars()).all(|c| c.is_ascii_digit()) {
+			return None;
+		}
+		let taken_at = format!(
+			"{}-{}-{} {}:{}:{} UTC",
+			&date[0..4], &date[4..6], &date[6..8], &time[0..2], &time[2..4], &time[4..6]
+		);
+		// A second snapshot within the same second gets a "-2" appended
+		let command = command.trim_end_matches(|c: char| c.is_ascii_digit()).trim_end_matches('-').to_string();
+		Some(Snapshot { id, taken_at, command, path })
+	}
+
+	pub fn contents(&self) -> Result<String, std::io::Error> {
+		read_to_string(&self.path)
+	}
+
+	// How many addons the snapshot lists, none when it cannot be read
+	pub fn addon_count(&self) -> Option<usize> {
+		let contents = self.contents().ok()?;
+		Gameinfo::parse(&contents).ok().map(|gameinfo| gameinfo.addon_states().len())
+	}
+}
+
+fn history_dir(game_dir: &Path, verbose: bool) -> Result<PathBuf, EnforcerError> {
+	Ok(gameinfo_path(game_dir, verbose)?.with_file_name(HISTORY_DIR))
+}
+
+fn history_limit(verbose: bool) -> usize {
+	let limit = var_os("HISTORY_LIMIT")
+		.and_then(|limit| limit.to_string_lossy().trim().parse().ok())
+		.unwrap_or(DEFAULT_HISTORY_LIMIT);
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("History limit: {}", limit);
+	}
+	limit
+}
+
+// All snapshots, newest first
+pub fn list_snapshots(game_dir: &Path, verbose: bool) -> Result<Vec<Snapshot>, EnforcerError> {
+	let dir = history_dir(game_dir, verbose)?;
+	if !dir.is_dir() {
+		return Ok(Vec::new());
+	}
+	let mut snapshots: Vec<Snapshot> = std::fs::read_dir(&dir)?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == SNAPSHOT_EXTENSION))
+		.filter_map(Snapshot::from_path)
+		.collect();
+	// Snapshots taken within the same second are told apart by their modification time
+	let modified = |snapshot: &Snapshot| std::fs::metadata(&snapshot.path).and_then(|metadata| metadata.modified()).ok();
+	snapshots.sort_by_cached_key(|snapshot| std::cmp::Reverse((snapshot.id[..15].to_string(), modified(snapshot))));
+	Ok(snapshots)
+}
+
+// Saves the current contents of `gameinfo_path` as part of `tx`, before `command` changes it,
+// and drops the snapshots beyond the retention limit. Nothing is saved when the file is
+// the same as the newest snapshot, or when HISTORY_LIMIT is 0.
+pub fn save_snapshot(game_dir: &Path, tx: &mut Transaction, gameinfo_path: &Path, command: &str, verbose: bool) -> Result<(), EnforcerError> {
+	let limit = history_limit(verbose);
+	if limit == 0 || !gameinfo_path.is_file() {
+		return Ok(());
+	}
+	let contents = read_to_string(gameinfo_path)?;
+	let snapshots = list_snapshots(game_dir, verbose)?;
+	if snapshots.first().is_some_and(|newest| newest.contents().is_ok_and(|newest| newest == contents)) {
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("gameinfo.txt is unchanged since snapshot {}", snapshots[0].id);
+		}
+		return Ok(());
+	}
+
+	let dir = history_dir(game_dir, verbose)?;
+	if !dir.is_dir() {
+		tx.create_dir(&dir)?;
+	}
+	let now = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+	let stamp = format!("{}-{}", now[0..10].replace('-', ""), now[11..19].replace(':', ""));
+	let mut id = format!("{}-{}", stamp, command);
+	let mut count = 1;
+	while dir.join(format!("{}.{}", id, SNAPSHOT_EXTENSION)).exists() {
+		count += 1;
+		id = format!("{}-{}-{}", stamp, command, count);
+	}
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Saving gameinfo.txt as snapshot {}", id);
+	}
+	tx.write(&dir.join(format!("{}.{}", id, SNAPSHOT_EXTENSION)), contents)?;
+
+	// The new snapshot counts towards the limit
+	for old in snapshots.iter().skip(limit - 1) {
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Dropping snapshot {}", old.id);
+		}
+		tx.remove(&old.path)?;
+	}
+	Ok(())
+}
+
+// A snapshot by its position in `backups list` (1 being the newest), its id, or the start of its id
+pub fn find_snapshot(game_dir: &Path, id: &str, verbose: bool) -> Result<Snapshot, EnforcerError> {
+	let snapshots = list_snapshots(game_dir, verbose)?;
+	let found = match id.parse::<usize>() {
+		Ok(position) if (1..=snapshots.len()).contains(&position) => snapshots.get(position - 1).cloned(),
+		_ => match snapshots.iter().find(|snapshot| snapshot.id == id) {
+			Some(snapshot) => Some(snapshot.clone()),
+			None => {
+				let matching: Vec<&Snapshot> = snapshots.iter().filter(|snapshot| snapshot.id.starts_with(id)).collect();
+				if matching.len() > 1 {
+					let err = format!(
+						"Snapshot \"{}\" is ambiguous, it could be: {}",
+						id,
+						matching.iter().map(|snapshot| snapshot.id.as_str()).collect::<Vec<_>>().join(", ")
+					);
+					return Err(EnforcerError::Other(err));
+				}
+				matching.first().map(|snapshot| (*snapshot).clone())
+			},
+		},
+	};
+	found.ok_or_else(|| {
+		let err = format!("There is no snapshot \"{}\"! See backups list for the available ones.", id);
+		EnforcerError::Other(err)
+	})
+}
+
+// A snapshot with the current gameinfo.txt and the snapshot's contents, to compare them
+pub fn snapshot_diff(game_dir: &Path, id: &str, verbose: bool) -> Result<(Snapshot, String, String), EnforcerError> {
+	let snapshot = find_snapshot(game_dir, id, verbose)?;
+	let current = read_to_string(gameinfo_path(game_dir, verbose)?)?;
+	let contents = snapshot.contents()?;
+	Ok((snapshot, current, contents))
+}
+
+// What restoring a snapshot did
+#[derive(Debug, Clone)]
+pub struct SnapshotRestore {
+	pub snapshot: Snapshot,
+	// The contents of gameinfo.txt before and after, none when it already was the same as the snapshot
+	pub diff: Option<(String, String)>,
+	// Addons uninstalled since the snapshot was taken are listed again, but their files are gone
+	pub missing: Vec<String>,
+	// What a dry run would have done
+	pub plan: Vec<PlannedStep>,
+}
+
+// Puts a snapshot back in place of gameinfo.txt, after saving the current one as a snapshot too
+pub fn restore_snapshot(game_dir: &Path, id: &str, dry_run: bool, verbose: bool) -> Result<SnapshotRestore, EnforcerError> {
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	// The vanilla gameinfo.txt is in place while PuG mode is enabled
+	if gameinfo_path.with_extension("txt.custom").exists() {
+		let err = "PuG mode is enabled! Disable it before restoring a snapshot.".to_string();
+		return Err(EnforcerError::Other(err));
+	}
+	let (snapshot, current, contents) = snapshot_diff(game_dir, id, verbose)?;
+	if current == contents {
+		return Ok(SnapshotRestore { snapshot, diff: None, missing: Vec::new(), plan: Vec::new() });
+	}
+	let gameinfo = Gameinfo::parse(&contents)?;
+
+	let mut tx = Transaction::new(dry_run, verbose);
+	save_snapshot(game_dir, &mut tx, &gameinfo_path, "restore", verbose)?;
+	tx.write(&gameinfo_path, &contents)?;
+	let mut manifest = Manifest::load(game_dir, verbose)?;
+	manifest.sync(&gameinfo);
+	manifest.save(game_dir, &mut tx)?;
+	let plan = tx.commit()?;
+
+	let missing = gameinfo
+		.addon_states()
+		.into_iter()
+		.map(|(addon, _)| addon)
+		.filter(|addon| !game_dir.join(addon).join("pak01_dir.vpk").is_file())
+		.collect();
+	Ok(SnapshotRestore { snapshot, diff: Some((current, contents)), missing, plan })
+}
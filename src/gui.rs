@@ -5,9 +5,15 @@
 
 use fltk::{app::version, browser::{Browser, BrowserType}, enums::{Align, Color, Event, Shortcut}, frame::Frame, group::{Flex, Pack, PackType}, menu::SysMenuBar, prelude::*, *};
 use crate::gui_theming::*;
+use crate::AddonManager;
+use crate::enforcer_error::render;
+use crate::install_addon::InstallStatus;
 use std::{path::{PathBuf, Path}, sync::{Arc, Mutex}};
 
 
+// Width of the enabled checkbox column in the installed addons list
+const CHECK_COLUMN_WIDTH: i32 = 36;
+
 pub fn main() {
 	let a = app::App::default();
 	apply_theme();
@@ -16,7 +22,7 @@ pub fn main() {
 		.with_label(env!("CARGO_PKG_NAME"))
 		.center_screen();
 
-	let mut menubar = SysMenuBar::new(294, 0, 80, 24, "");
+	let mut menubar = SysMenuBar::new(214, 0, 160, 24, "");
 	menubar.add(
 		"&Program/&About",
 		Shortcut::None,
@@ -98,10 +104,8 @@ pub fn main() {
 
 	let mut flex2 = Flex::new(0, 0, 82, 42, "").row();
     flex2.set_margin(10);
-	let mut spacer = frame::Frame::default().with_size(82, 32);
-	spacer.activate();
-	let mut spacer1 = frame::Frame::default().with_size(82, 32);
-	spacer1.activate();
+	let mut btn_up = RButton::new(0,0,82,32,"Move up");
+	let mut btn_down = RButton::new(0,0,82,32,"Move down");
 	let mut btn_refresh = RButton::new(0,0,82,32,"Refresh");
 	flex2.set_spacing(2);
     flex2.end();
@@ -111,26 +115,37 @@ pub fn main() {
 	installed_list.set_color(Color::from_rgb(22, 25, 37));
 	installed_list.set_selection_color(Color::from_rgb(185, 5, 224));
 	installed_list.set_type(BrowserType::Hold);
+	// The first column is the enabled checkbox, see add_addon_line
+	installed_list.set_column_char('\t');
+	installed_list.set_column_widths(&[CHECK_COLUMN_WIDTH]);
 
-	// Call list_addons and populate the browser
-	// Capture the output from list_addons
-	let mut output = Vec::new();
-	if let Err(e) = crate::list_addons::list_addons(true, false, false, &mut output) {
-		installed_list.set_type(BrowserType::Normal);
-		installed_list.add(&format!("Failed to list addons:"));
-		installed_list.add(&format!("{}", e));
-	} else {
-		let output_str = String::from_utf8(output).unwrap_or_default();
-		if !output_str.is_empty() {
-			for line in output_str.lines() {
-				installed_list.add(line.trim_start_matches('\t'));
-			}
-		} else {
+	// Populate the browser with the installed addons
+	match AddonManager::locate().and_then(|manager| manager.list(false)) {
+		Err(e) => {
 			installed_list.set_type(BrowserType::Normal);
-            installed_list.add(&format!("@c@iNo addons are currently installed."));
-            installed_list.add(&format!("@c@iWould you like to install one (or more)?"));
-		}
+			installed_list.add("Failed to list addons:");
+			installed_list.add(&render(&e));
+		},
+		Ok(addons) => {
+			if !addons.is_empty() {
+				for addon in addons {
+					add_addon_line(&mut installed_list, &addon.name, addon.enabled);
+				}
+			} else {
+				installed_list.set_type(BrowserType::Normal);
+				installed_list.add("@c@iNo addons are currently installed.");
+				installed_list.add("@c@iWould you like to install one (or more)?");
+			}
+		},
 	}
+	// Clicking the checkbox column enables/disables the addon on that line
+	installed_list.handle(move |b, ev| match ev {
+		Event::Released if app::event_x() < b.x() + CHECK_COLUMN_WIDTH => {
+			toggle_addon_line(b, b.value());
+			true
+		}
+		_ => false,
+	});
 	hpack.end();
 	hpack.set_type(PackType::Horizontal);
 
@@ -143,38 +158,26 @@ pub fn main() {
 		let name = name_for_selected_clone.lock().unwrap().value();
 		if let Some(_file_path) = Path::new(&addon_file).parent() {
 			if !name.is_empty() {
-				if name.contains(char::is_whitespace)
-						|| name.contains('/')
-						|| name.contains('\\')
-						|| name.contains(':')
-						|| name.contains('*')
-						|| name.contains('?')
-						|| name.contains('"')
-						|| name.contains('<')
-						|| name.contains('>')
-						|| name.contains('|')
-					{
-						dialog::alert(center().0 - 200, center().1 - 100, &format!("Invalid addon name!\n\tName cannot be empty, contain whitespace, or special characters\n\tthat are known to cause problems with file managers or filesystems."));
-					} else {
-						let installed_list_ref = Arc::clone(&installed_list_clone);
-						match crate::install_addon::install_addon(&addon_file, &name, false) {
-							Ok(1) => {
-								dialog::message(center().0 - 200, center().1 - 100, &format!("Addon \"{}\" installed successfully!", name));
-								refresh_installed_list(&installed_list_ref);
-							},
-							Ok(2) => {
-								dialog::message(center().0 - 200, center().1 - 100, &format!("Addon \"{}\" updated successfully!", name));
-								refresh_installed_list(&installed_list_ref);
-							},
-							Ok(other) => {
-								dialog::alert(center().0 - 200, center().1 - 100, &format!("Addon \"{}\" completed with status: {}", name, other));
-								refresh_installed_list(&installed_list_ref);
-							},
-							Err(e) => {
-								dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to install addon \"{}\": {}", name, e));
-							},
+				let installed_list_ref = Arc::clone(&installed_list_clone);
+				match AddonManager::locate().and_then(|manager| manager.install(&addon_file, Some(&name))) {
+					Ok(report) => {
+						let mut message = match report.status {
+							InstallStatus::Installed => format!("Addon \"{}\" installed successfully!", report.name),
+							InstallStatus::Updated => format!("Addon \"{}\" updated successfully!", report.name),
+						};
+						if report.stayed_disabled {
+							message.push_str("\nIt is disabled, and stays disabled after the update.");
 						}
-					}
+						for warning in &report.warnings {
+							message.push_str(&format!("\n\nWarning: {}", warning));
+						}
+						dialog::message(center().0 - 200, center().1 - 100, &message);
+						refresh_installed_list(&installed_list_ref);
+					},
+					Err(e) => {
+						dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to install addon \"{}\": {}", name, render(&e)));
+					},
+				}
 			} else {
 				dialog::alert(center().0 - 200, center().1 - 100, "No addon name specified!");
 			}
@@ -189,11 +192,85 @@ pub fn main() {
 		refresh_installed_list(&installed_list_ref);
     });
 
+	// Higher in the list means higher priority in the SearchPaths
+	let installed_list_clone = Arc::clone(&installed_list);
+	btn_up.set_callback(move |_| {
+		move_selected_addon(&installed_list_clone, crate::order_addons::Direction::Up);
+	});
+	let installed_list_clone = Arc::clone(&installed_list);
+	btn_down.set_callback(move |_| {
+		move_selected_addon(&installed_list_clone, crate::order_addons::Direction::Down);
+	});
+
+	// Profiles: saving the enabled addons, then one entry per profile under Activate/Compare/Export
+	let installed_list_clone = Arc::clone(&installed_list);
+	let mut menubar_clone = menubar.clone();
+	menubar.add(
+		"&Profiles/&Save current as...\t",
+		Shortcut::None,
+		menu::MenuFlag::MenuDivider,
+		move |_menu| {
+			let ctrl = controlAccentColor.to_rgb();
+			let tmp = controlColor.to_rgb();
+			app::background2(tmp.0, tmp.1, tmp.2);
+			let inp_box = dialog::input(center().0 - 200, center().1 - 100, "Save the enabled addons as profile:", "");
+			app::background2(ctrl.0, ctrl.1, ctrl.2);
+			if let Some(name) = inp_box {
+				match AddonManager::locate().and_then(|manager| manager.create_profile(&name)) {
+					Ok(saved) => {
+						if !saved.updated {
+							add_profile_menu_items(&mut menubar_clone, &name, &installed_list_clone);
+						}
+						dialog::message(center().0 - 200, center().1 - 100, &format!("Profile \"{}\" saved successfully!", name));
+					},
+					Err(e) => {
+						dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to save profile \"{}\": {}", name, render(&e)));
+					},
+				}
+			}
+		},
+	);
+	if let Ok(names) = AddonManager::locate().and_then(|manager| manager.profiles()) {
+		for name in names {
+			add_profile_menu_items(&mut menubar, &name, &installed_list);
+		}
+	}
+
+	// History: the gameinfo.txt snapshots saved by every change, index 2 being between About and Quit
+	let installed_list_clone = Arc::clone(&installed_list);
+	menubar.insert(
+		2,
+		"&Program/&History...\t",
+		Shortcut::Ctrl | 'h',
+		menu::MenuFlag::Normal,
+		move |_menu| {
+			show_history_window(&installed_list_clone);
+		},
+	);
+
 	let mut flex2 = Flex::new(0, 0, 82, 42, "").row();
     flex2.set_margin(10);
 	// let mut hpack = Pack::new(0,0,120,32,"");
-	let mut spacer = frame::Frame::default().with_size(82, 32);
-	spacer.activate();
+	let mut btn_details = RButton::new(0,0,82,32,"Details");
+	let installed_list_clone = Arc::clone(&installed_list);
+	btn_details.set_callback(move |_| {
+		let installed_list = installed_list_clone.lock().unwrap();
+		let selected = installed_list.value();
+		if selected > 0 {
+			let addon_name = addon_at_line(&installed_list, selected).unwrap_or_default();
+			drop(installed_list); // Release the lock
+			match addon_details(&addon_name) {
+				Ok(details) => {
+					dialog::message(center().0 - 200, center().1 - 100, &details);
+				},
+				Err(e) => {
+					dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to read the details of addon \"{}\": {}", addon_name, render(&*e)));
+				},
+			}
+		} else {
+			dialog::alert(center().0 - 200, center().1 - 100, "No addon selected!");
+		}
+	});
 
 	let mut btn_ren = RButton::new(0,0,82,32,"Rename");
 	let installed_list_clone = Arc::clone(&installed_list);
@@ -202,22 +279,21 @@ pub fn main() {
         let selected = installed_list.value();
 		drop(installed_list); // Release the lock
         if selected > 0 {
-            let addon_name = installed_list_clone.lock().unwrap().text(selected).unwrap();
+            let addon_name = addon_at_line(&installed_list_clone.lock().unwrap(), selected).unwrap_or_default();
             // Perform uninstall action
 			if !addon_name.is_empty() {
 				let ctrl = controlAccentColor.to_rgb();
 				let tmp = controlColor.to_rgb();
 				app::background2(tmp.0, tmp.1, tmp.2);
 				let inp_box = dialog::input(center().0 - 200, center().1 - 100, &format!("Enter new name for addon: {}", addon_name), "");
-					if !inp_box.is_none() {
-						let input = inp_box.unwrap();
-						match crate::rename_addon::rename_addon(&addon_name, &input, false) {
+					if let Some(input) = inp_box {
+						match AddonManager::locate().and_then(|manager| manager.rename(&addon_name, &input)) {
 							Ok(_) => {
 								dialog::alert(center().0 - 200, center().1 - 100, &format!("Addon \"{}\" renamed successfully!", addon_name));
 								refresh_installed_list(&installed_list_clone);
 								},
 							Err(e) => {
-								dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to rename addon \"{}\": {}", addon_name, e));
+								dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to rename addon \"{}\": {}", addon_name, render(&e)));
 							},
 						}
 					}
@@ -235,16 +311,16 @@ pub fn main() {
         let mut installed_list = installed_list_clone.lock().unwrap();
         let selected = installed_list.value();
         if selected > 0 {
-            let addon_name = installed_list.text(selected).unwrap();
+            let addon_name = addon_at_line(&installed_list, selected).unwrap_or_default();
             // Perform uninstall action
 			if !addon_name.is_empty() {
-				match crate::uninstall_addon::uninstall_addon(&addon_name, false) {
+				match AddonManager::locate().and_then(|manager| manager.uninstall(&addon_name)) {
 					Ok(_) => {
            				dialog::alert(center().0 - 200, center().1 - 100, &format!("Addon \"{}\" uninstalled successfully!", addon_name));
 						installed_list.remove(selected);
 						},
 					Err(e) => {
-                        dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to uninstall addon \"{}\": {}", addon_name, e));
+                        dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to uninstall addon \"{}\": {}", addon_name, render(&e)));
                     },
                 }
 			}
@@ -265,42 +341,52 @@ pub fn main() {
     flex3.set_margin(10);
 
 	let mut btn_pug = RButton::new(0,0,82,32,"PuG mode: Unknown");
-	match crate::pug_mode::PuG_mode_check(false) {
+	match AddonManager::locate().and_then(|manager| manager.pug_status()) {
 		Err(_e) => {
 		btn_pug.set_label("PuG mode is unavailable");
 		btn_pug.deactivate();
 		}, 
-		Ok(1) => {
+		Ok(status) if status.enabled => {
 		btn_pug.set_label("PuG mode: Enabled");
 		},
-		Ok(2) => {
+		Ok(_) => {
 		btn_pug.set_label("PuG mode: Disabled");
 		},
-		Ok(_other) => {
-		btn_pug.set_label("PuG mode: Unknown");
-		},
 	}
 	let installed_list_clone = Arc::clone(&installed_list);
 	let mut btn_pug_clone = btn_pug.clone();
 	btn_pug.set_callback(move |_| {
-		match crate::pug_mode::PuG_mode_switch(false) {
-			Ok(1) => {
-				   dialog::alert(center().0 - 200, center().1 - 100, &format!("PuG Mode is now enabled."));
-				   btn_pug_clone.set_label("PuG mode: Enabled");
-				   refresh_installed_list(&installed_list_clone);
+		// A rebuilt gameinfo.txt is shown before it is written
+		match AddonManager::locate().and_then(|manager| manager.pug_rebuild()) {
+			Ok(Some((contents, rebuilt))) => {
+				let question = "gameinfo.txt seems to be modified, but no backup is present: a vanilla one is rebuilt by\nremoving every addon from it. Enable PuG mode?";
+				if !confirm_diff("Enable PuG mode", question, &contents, &rebuilt, "gameinfo.txt (vanilla)") {
+					return;
+				}
+			},
+			Ok(None) => {},
+			Err(e) => {
+				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to change PuG Mode: {}", render(&e)));
+				return;
+			},
+		}
+		match AddonManager::locate().and_then(|manager| manager.pug_toggle()) {
+			Ok(switch) if switch.enabled => {
+				let mut message = "PuG Mode is now enabled.".to_string();
+				if switch.rebuilt.is_some() {
+					message.push_str("\n\ngameinfo.txt seemed to be modified, but no backup was present:\na vanilla one was rebuilt by removing every addon from it.");
+				}
+				dialog::alert(center().0 - 200, center().1 - 100, &message);
+				btn_pug_clone.set_label("PuG mode: Enabled");
+				refresh_installed_list(&installed_list_clone);
 				},
-			Ok(2) => {
-				dialog::alert(center().0 - 200, center().1 - 100, &format!("PuG Mode is now disabled."));
+			Ok(_) => {
+				dialog::alert(center().0 - 200, center().1 - 100, "PuG Mode is now disabled.");
 				btn_pug_clone.set_label("PuG mode: Disabled");
 				refresh_installed_list(&installed_list_clone);
-            },
-			Ok(other) => {
-				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to switch PuG Mode: {}", other));
-				btn_pug_clone.set_label("PuG mode: Unknown");
-				refresh_installed_list(&installed_list_clone);
             },
 			Err(e) => {
-				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to change PuG Mode: {}", e));
+				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to change PuG Mode: {}", render(&e)));
 			},
 		}
 	});
@@ -326,21 +412,29 @@ pub fn main() {
 
 	let installed_list_clone = Arc::clone(&installed_list);
 	btn_reset.set_callback(move |_| {
-		match dialog::choice2(center().0 - 200, center().1 - 100,
-			"Are you sure you want to reset gameinfo.txt to default? \nThis operation cannot be undone.", "&Yes", "&No", "") {
-			Some(0) => {
-				match crate::gameinfo_reset(false) {
-					Ok(_) => {
-						dialog::alert(center().0 - 200, center().1 - 100, &format!("Succesfully reset gameinfo.txt to default."));
-						refresh_installed_list(&installed_list_clone);
-						},
-					Err(e) => {
-						dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to reset gameinfo.txt: {}", e));
-					},
-				}
+		let plan = match AddonManager::locate().and_then(|manager| manager.plan_reset()) {
+			Ok(plan) => plan,
+			Err(e) => {
+				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to reset gameinfo.txt: {}", render(&e)));
+				return;
+			},
+		};
+		let (question, new_name) = if plan.rebuilt {
+			("gameinfo.txt seems to be modified, but no backup is present: a vanilla one is rebuilt by\nremoving every addon from it. Reset gameinfo.txt? This operation cannot be undone.", "gameinfo.txt (vanilla)")
+		} else {
+			("Reset gameinfo.txt to the backup? This operation cannot be undone.", "gameinfo.txt (backup)")
+		};
+		if !confirm_diff("Reset gameinfo.txt", question, &plan.current, &plan.reset, new_name) {
+			return;
+		}
+		match AddonManager::locate().and_then(|manager| manager.reset(&plan)) {
+			Ok(_) => {
+				dialog::alert(center().0 - 200, center().1 - 100, "Succesfully reset gameinfo.txt to default.");
+				refresh_installed_list(&installed_list_clone);
+				},
+			Err(e) => {
+				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to reset gameinfo.txt: {}", render(&e)));
 			},
-			Some(1) => (),
-			Some(_) | None  => (),
 		}
 	});
 
@@ -356,6 +450,227 @@ pub fn main() {
 	a.run().unwrap();
 }
 
+fn move_selected_addon(installed_list_clone: &Arc<Mutex<Browser>>, direction: crate::order_addons::Direction) {
+	let installed_list = installed_list_clone.lock().unwrap();
+	let selected = installed_list.value();
+	if selected == 0 {
+		drop(installed_list); // Release the lock
+		dialog::alert(center().0 - 200, center().1 - 100, "No addon selected to move!");
+		return;
+	}
+	let addon_name = addon_at_line(&installed_list, selected).unwrap_or_default();
+	drop(installed_list); // Release the lock
+	match AddonManager::locate().and_then(|manager| manager.move_addon(&addon_name, direction)) {
+		Ok(_) => {
+			refresh_installed_list(installed_list_clone);
+			// Keep the moved addon selected, so it can be moved again right away
+			let mut installed_list = installed_list_clone.lock().unwrap();
+			if let Some(line) = (1..=installed_list.size()).find(|&line| addon_at_line(&installed_list, line).as_deref() == Some(&addon_name)) {
+				installed_list.select(line);
+			}
+		},
+		Err(e) => {
+			dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to move addon \"{}\": {}", addon_name, render(&e)));
+		},
+	}
+}
+
+fn add_profile_menu_items(menubar: &mut SysMenuBar, name: &str, installed_list: &Arc<Mutex<Browser>>) {
+	let installed_list_clone = Arc::clone(installed_list);
+	let profile = name.to_string();
+	menubar.add(
+		&format!("&Profiles/&Activate/{}", name),
+		Shortcut::None,
+		menu::MenuFlag::Normal,
+		move |_menu| {
+			match AddonManager::locate().and_then(|manager| manager.activate_profile(&profile)) {
+				Ok(activated) => {
+					refresh_installed_list(&installed_list_clone);
+					dialog::message(center().0 - 200, center().1 - 100, &format!("Profile \"{}\" activated.\n\n{}", profile, activated.diff.describe()));
+				},
+				Err(e) => {
+					dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to activate profile \"{}\": {}", profile, render(&e)));
+				},
+			}
+		},
+	);
+	let profile = name.to_string();
+	menubar.add(
+		&format!("&Profiles/&Compare/{}", name),
+		Shortcut::None,
+		menu::MenuFlag::Normal,
+		move |_menu| {
+			match AddonManager::locate().and_then(|manager| manager.diff_profile(&profile)) {
+				Ok(diff) => {
+					dialog::message(center().0 - 200, center().1 - 100, &format!("Activating profile \"{}\" would change:\n\n{}", profile, diff.describe()));
+				},
+				Err(e) => {
+					dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to compare profile \"{}\": {}", profile, render(&e)));
+				},
+			}
+		},
+	);
+	let profile = name.to_string();
+	menubar.add(
+		&format!("&Profiles/&Export/{}", name),
+		Shortcut::None,
+		menu::MenuFlag::Normal,
+		move |_menu| {
+			if let Some(file_path) = nfc_get_file(dialog::NativeFileChooserType::BrowseSaveFile) {
+				if let Err(e) = AddonManager::locate().and_then(|manager| manager.export_profile(&profile, &file_path)) {
+					dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to export profile \"{}\": {}", profile, render(&e)));
+				}
+			}
+		},
+	);
+}
+
+// Shows the changes about to be made to gameinfo.txt as a diff, and whether they were confirmed
+fn confirm_diff(label: &str, question: &str, old: &str, new: &str, new_name: &str) -> bool {
+	let mut win = window::Window::default()
+		.with_size(560, 420)
+		.with_label(label)
+		.center_screen();
+	let mut frame = Frame::new(10, 10, 540, 40, "");
+	frame.set_label(question);
+	frame.set_align(Align::Left | Align::Inside | Align::Wrap);
+
+	let mut diff_buffer = text::TextBuffer::default();
+	diff_buffer.set_text(&crate::show_diff::unified_diff(old, new, "gameinfo.txt", new_name));
+	let mut diff_view = text::TextDisplay::new(10, 60, 540, 310, "");
+	diff_view.set_buffer(diff_buffer);
+	diff_view.set_text_font(enums::Font::Courier);
+	diff_view.set_text_size(12);
+
+	let mut btn_cancel = RButton::new(352, 378, 98, 32, "Cancel");
+	let mut btn_confirm = RButton::new(452, 378, 98, 32, "Confirm");
+	win.end();
+	win.make_modal(true);
+	win.show();
+
+	let confirmed = Arc::new(Mutex::new(false));
+	let mut win_clone = win.clone();
+	btn_cancel.set_callback(move |_| {
+		win_clone.hide();
+	});
+	let confirmed_clone = Arc::clone(&confirmed);
+	let mut win_clone = win.clone();
+	btn_confirm.set_callback(move |_| {
+		*confirmed_clone.lock().unwrap() = true;
+		win_clone.hide();
+	});
+	// Closing the window cancels too
+	while win.shown() {
+		app::wait();
+	}
+	*confirmed.lock().unwrap()
+}
+
+// Lists the gameinfo.txt snapshots, shows what restoring the selected one would change, and restores it
+fn show_history_window(installed_list: &Arc<Mutex<Browser>>) {
+	let mut win = window::Window::default()
+		.with_size(560, 520)
+		.with_label("History")
+		.center_screen();
+	let mut snapshot_list = Browser::new(10, 10, 540, 170, "");
+	snapshot_list.set_color(Color::from_rgb(22, 25, 37));
+	snapshot_list.set_selection_color(Color::from_rgb(185, 5, 224));
+	snapshot_list.set_type(BrowserType::Hold);
+	// Lines are "<id>\t<date>\t<command>", see fill_history_list
+	snapshot_list.set_column_char('\t');
+	snapshot_list.set_column_widths(&[230, 190]);
+	fill_history_list(&mut snapshot_list);
+
+	let mut diff_buffer = text::TextBuffer::default();
+	let mut diff_view = text::TextDisplay::new(10, 190, 540, 280, "");
+	diff_view.set_buffer(diff_buffer.clone());
+	diff_view.set_text_font(enums::Font::Courier);
+	diff_view.set_text_size(12);
+
+	let mut btn_restore = RButton::new(352, 478, 98, 32, "Restore");
+	let mut btn_close = RButton::new(452, 478, 98, 32, "Close");
+	win.end();
+	win.make_modal(true);
+	win.show();
+
+	let mut diff_buffer_clone = diff_buffer.clone();
+	snapshot_list.set_callback(move |list| {
+		let Some(id) = snapshot_at_line(list, list.value()) else {
+			return;
+		};
+		match AddonManager::locate().and_then(|manager| manager.snapshot_diff(&id)) {
+			Ok((_, current, contents)) if current == contents => {
+				diff_buffer_clone.set_text("gameinfo.txt is the same as this snapshot.");
+			},
+			Ok((snapshot, current, contents)) => {
+				let title = format!("{} ({})", snapshot.id, snapshot.taken_at);
+				diff_buffer_clone.set_text(&crate::show_diff::unified_diff(&current, &contents, "gameinfo.txt", &title));
+			},
+			Err(e) => {
+				diff_buffer_clone.set_text(&format!("Failed to compare snapshot {}: {}", id, render(&e)));
+			},
+		}
+	});
+
+	let installed_list = Arc::clone(installed_list);
+	let mut snapshot_list_clone = snapshot_list.clone();
+	btn_restore.set_callback(move |_| {
+		let Some(id) = snapshot_at_line(&snapshot_list_clone, snapshot_list_clone.value()) else {
+			dialog::alert(center().0 - 200, center().1 - 100, "No snapshot selected!");
+			return;
+		};
+		let question = format!("Restore gameinfo.txt from snapshot {}?\nThe current gameinfo.txt is saved as a snapshot first.", id);
+		if dialog::choice2(center().0 - 200, center().1 - 100, &question, "Cancel", "Restore", "") != Some(1) {
+			return;
+		}
+		match AddonManager::locate().and_then(|manager| manager.restore_snapshot(&id)) {
+			Ok(_) => {
+				refresh_installed_list(&installed_list);
+				fill_history_list(&mut snapshot_list_clone);
+				diff_buffer.set_text("");
+				dialog::message(center().0 - 200, center().1 - 100, &format!("gameinfo.txt restored from snapshot {}.", id));
+			},
+			Err(e) => {
+				dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to restore snapshot {}: {}", id, render(&e)));
+			},
+		}
+	});
+	btn_close.set_callback(move |_| {
+		win.hide();
+	});
+}
+
+fn fill_history_list(snapshot_list: &mut Browser) {
+	snapshot_list.clear();
+	match AddonManager::locate().and_then(|manager| manager.snapshots()) {
+		Err(e) => {
+			snapshot_list.set_type(BrowserType::Normal);
+			snapshot_list.add("@bFailed to list snapshots:");
+			snapshot_list.add(&render(&e));
+		},
+		Ok(snapshots) if snapshots.is_empty() => {
+			snapshot_list.set_type(BrowserType::Normal);
+			snapshot_list.add("@c@iNo snapshots yet.");
+			snapshot_list.add("@c@iOne is saved by every change to gameinfo.txt.");
+		},
+		Ok(snapshots) => {
+			snapshot_list.set_type(BrowserType::Hold);
+			for snapshot in snapshots {
+				snapshot_list.add(&format!("{}\t{}\tbefore {}", snapshot.id, snapshot.taken_at, snapshot.command));
+			}
+		},
+	}
+}
+
+// The snapshot id on a line of the history list
+fn snapshot_at_line(snapshot_list: &Browser, line: i32) -> Option<String> {
+	if line == 0 || snapshot_list.get_type::<BrowserType>() == BrowserType::Normal {
+		return None;
+	}
+	let text = snapshot_list.text(line)?;
+	text.split_once('\t').map(|(id, _)| id.to_string())
+}
+
 pub fn center() -> (i32, i32) {
 	(
 		(app::screen_size().0 / 2.0) as i32,
@@ -391,29 +706,66 @@ fn nfc_get_file(mode: dialog::NativeFileChooserType) -> Option<PathBuf> {
 	}
 }
 
+// Everything the addon's addoninfo.txt tells about it, one field per line
+fn addon_details(addon_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+	let addon_file = AddonManager::locate()?.addon_vpk(addon_name);
+	let info = crate::vpk_getdata::main(&addon_file.to_string_lossy().to_string(), false)?;
+	let mut details = format!("{}\n", addon_name);
+	for (label, value) in info.fields() {
+		details.push_str(&format!("\n{}: {}", label, value));
+	}
+	Ok(details)
+}
+
 fn refresh_installed_list(installed_list_clone: &Arc<Mutex<Browser>>) {
     let mut installed_list = installed_list_clone.lock().unwrap();
-    // Call list_addons and populate the browser
-    // Capture the output from list_addons
-    let mut output = Vec::new();
-    if let Err(e) = crate::list_addons::list_addons(true, false, false, &mut output) {
-        installed_list.set_type(BrowserType::Normal);
-        installed_list.add(&format!("@bFailed to list addons:"));
-        installed_list.add(&format!("{}", e));
-    } else {
-        installed_list.clear();
-		if installed_list.get_type::<BrowserType>() == BrowserType::Normal {
-			installed_list.set_type(BrowserType::Hold);
-		}
-        let output_str = String::from_utf8(output).unwrap_or_default();
-		if !output_str.is_empty() {
-			for line in output_str.lines() {
-				installed_list.add(line.trim_start_matches('\t'));
-			}
-		} else {
-			installed_list.set_type(BrowserType::Normal);
-            installed_list.add(&format!("@c@iNo addons are currently installed."));
-            installed_list.add(&format!("@c@iWould you like to install one (or more)?"));
-		}
+    // Populate the browser with the installed addons
+    match AddonManager::locate().and_then(|manager| manager.list(false)) {
+        Err(e) => {
+            installed_list.set_type(BrowserType::Normal);
+            installed_list.add("@bFailed to list addons:");
+            installed_list.add(&render(&e));
+        },
+        Ok(addons) => {
+            installed_list.clear();
+            if installed_list.get_type::<BrowserType>() == BrowserType::Normal {
+                installed_list.set_type(BrowserType::Hold);
+            }
+            if !addons.is_empty() {
+                for addon in addons {
+                    add_addon_line(&mut installed_list, &addon.name, addon.enabled);
+                }
+            } else {
+                installed_list.set_type(BrowserType::Normal);
+                installed_list.add("@c@iNo addons are currently installed.");
+                installed_list.add("@c@iWould you like to install one (or more)?");
+            }
+        },
     }
 }
+
+// Lines are "<checkbox>\t<addon name>"
+fn add_addon_line(installed_list: &mut Browser, addon: &str, enabled: bool) {
+	installed_list.add(&format!("{}\t{}", if enabled { "[x]" } else { "[  ]" }, addon));
+}
+
+// The addon name on a line of the installed list
+fn addon_at_line(installed_list: &Browser, line: i32) -> Option<String> {
+	let text = installed_list.text(line)?;
+	text.split_once('\t').map(|(_, addon)| addon.to_string())
+}
+
+fn toggle_addon_line(installed_list: &mut Browser, line: i32) {
+	let Some(addon_name) = addon_at_line(installed_list, line) else {
+		return;
+	};
+	let enabled = installed_list.text(line).is_some_and(|text| text.starts_with("[x]"));
+	match AddonManager::locate().and_then(|manager| manager.set_enabled(std::slice::from_ref(&addon_name), !enabled)) {
+		Ok(_) => {
+			installed_list.set_text(line, &format!("{}\t{}", if enabled { "[  ]" } else { "[x]" }, addon_name));
+		},
+		Err(e) => {
+			dialog::alert(center().0 - 200, center().1 - 100, &format!("Failed to {} addon \"{}\": {}", if enabled { "disable" } else { "enable" }, addon_name, render(&e)));
+		},
+	}
+}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::Gameinfo;
+use crate::vpk_getdata::{entry_path, open_vpk};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Path prefixes telling what an addon replaces or adds
+const CATEGORIES: [(&str, &[&str]); 7] = [
+	("HUD", &["resource/ui/", "scripts/hudlayout.res", "materials/vgui/", "resource/clientscheme.res"]),
+	("Survivor skin", &["models/survivors/", "materials/models/survivors/"]),
+	("Infected skin", &["models/infected/", "materials/models/infected/"]),
+	("Weapon", &[
+		"models/weapons/",
+		"models/v_models/",
+		"models/w_models/",
+		"materials/models/weapons/",
+		"materials/models/v_models/",
+		"materials/models/w_models/",
+		"scripts/weapon_",
+	]),
+	("Sound", &["sound/"]),
+	("Map", &["maps/", "missions/"]),
+	("Script", &["scripts/vscripts/"]),
+];
+
+// Directories that are broken down one level further (e.g. "scripts/vscripts")
+const SPLIT_DIRS: [&str; 2] = ["scripts", "resource"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirStats {
+	pub files: usize,
+	pub size: u64,
+}
+
+// What a VPK contains: file counts and sizes per directory, and the categories they fall into
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Inventory {
+	pub dirs: BTreeMap<String, DirStats>,
+	pub total: DirStats,
+	pub categories: Vec<&'static str>,
+}
+
+// The inventory of an inspected target
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectedAddon {
+	// The installed addon name or VPK file, as given
+	pub target: String,
+	pub vpk: PathBuf,
+	#[serde(flatten)]
+	pub inventory: Inventory,
+}
+
+pub fn inventory(vpk_file: &Path) -> Result<Inventory, EnforcerError> {
+	let (_, vpk) = open_vpk(vpk_file)?;
+	let mut inventory = Inventory::default();
+	for (key, entry) in &vpk.tree.files {
+		let path = entry_path(key).to_lowercase();
+		let size = entry.preload_length as u64 + entry.entry_length as u64;
+		let stats = inventory.dirs.entry(inventory_dir(&path)).or_default();
+		stats.files += 1;
+		stats.size += size;
+		inventory.total.files += 1;
+		inventory.total.size += size;
+		for (category, prefixes) in CATEGORIES {
+			if prefixes.iter().any(|prefix| path.starts_with(prefix)) && !inventory.categories.contains(&category) {
+				inventory.categories.push(category);
+			}
+		}
+	}
+	// Keep the order of CATEGORIES
+	inventory.categories.sort_by_key(|category| CATEGORIES.iter().position(|(c, _)| c == category));
+	Ok(inventory)
+}
+
+// The directory a file is counted in: "materials", "scripts/vscripts", "(root)"...
+fn inventory_dir(path: &str) -> String {
+	let mut parts = path.split('/');
+	let first = parts.next().unwrap_or_default();
+	let rest: Vec<&str> = parts.collect();
+	match rest.len() {
+		0 => "(root)".to_string(),
+		1 => first.to_string(),
+		_ if SPLIT_DIRS.contains(&first) => format!("{}/{}", first, rest[0]),
+		_ => first.to_string(),
+	}
+}
+
+// The inventory of each target: an installed addon name or a path to a VPK file.
+// Without targets, every installed addon is inspected. `game_dir` is only needed for addons,
+// see `targets_need_game`. A target that cannot be inspected does not stop the others.
+pub fn inspect_addons(
+	game_dir: Option<&Path>,
+	targets: &[String],
+	verbose: bool,
+) -> Result<Vec<Result<InspectedAddon, EnforcerError>>, EnforcerError> {
+	let game = read_game(game_dir, verbose)?;
+	let targets: Vec<String> = match &game {
+		Some((_, gameinfo)) if targets.is_empty() => gameinfo.addon_states().into_iter().map(|(addon, _)| addon).collect(),
+		_ => targets.to_vec(),
+	};
+	let mut inspected = Vec::new();
+	for target in targets {
+		let vpk_file = match target_vpk(&target, game.as_ref()) {
+			Ok(vpk_file) => vpk_file,
+			Err(e) => {
+				inspected.push(Err(e));
+				continue;
+			},
+		};
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Inspecting {:?}", vpk_file);
+		}
+		inspected.push(match inventory(&vpk_file) {
+			Ok(inventory) => Ok(InspectedAddon { target, vpk: vpk_file, inventory }),
+			Err(e) => Err(EnforcerError::Other(format!("{}: {}", target, e))),
+		});
+	}
+	Ok(inspected)
+}
+
+// Whether the game is needed to find `targets`: when some are not VPK files, or there are
+// none and every installed addon is meant
+pub fn targets_need_game(targets: &[String]) -> bool {
+	targets.is_empty() || targets.iter().any(|target| !Path::new(target).is_file())
+}
+
+// The game directory along with its gameinfo.txt, for looking addons up
+pub fn read_game(game_dir: Option<&Path>, verbose: bool) -> Result<Option<(&Path, Gameinfo)>, EnforcerError> {
+	match game_dir {
+		Some(game_dir) => Ok(Some((game_dir, Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?))),
+		None => Ok(None),
+	}
+}
+
+// The VPK of a target: the file itself, or the pak01_dir.vpk of an addon installed in the game
+pub fn target_vpk(target: &str, game: Option<&(&Path, Gameinfo)>) -> Result<PathBuf, EnforcerError> {
+	if Path::new(target).is_file() {
+		return Ok(PathBuf::from(target));
+	}
+	let Some((game_dir, gameinfo)) = game else {
+		let err = format!("{} is not a VPK file!", target);
+		return Err(EnforcerError::Other(err));
+	};
+	if !gameinfo.has_addon(target) && !gameinfo.is_disabled(target) {
+		return Err(gameinfo.missing_addon(target));
+	}
+	Ok(game_dir.join(target).join("pak01_dir.vpk"))
+}
+
+pub fn human_size(bytes: u64) -> String {
+	const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+	if bytes < 1024 {
+		return format!("{} B", bytes);
+	}
+	let mut size = bytes as f64 / 1024.0;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	format!("{:.1} {}", size, UNITS[unit])
+}
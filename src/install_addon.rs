@@ -1,44 +1,264 @@
 // SPDX-License-Identifier: LGPL-3.0-only
 use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::{Gameinfo, is_game_dir};
+use crate::vanilla_gameinfo::check_vanilla;
+use crate::transaction::{PlannedStep, Transaction};
+use crate::unpack_archive::{UnpackedArchive, is_archive, is_unsupported_archive, unpack_archive};
+use crate::pack_addon::{is_loose_addon, write_vpk};
+use crate::verify_vpk::verify_vpk;
+use serde::Serialize;
+use tempfile::TempDir;
 
-pub fn autoinstall_addon(
-	addon_file: &String,
+// Whether an addon was new to gameinfo.txt or replaced an installed one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InstallStatus {
+	Installed,
+	Updated,
+}
+
+// What installing one addon did
+#[derive(Debug, Clone)]
+pub struct InstallReport {
+	pub name: String,
+	pub status: InstallStatus,
+	// A disabled addon is updated in place and stays disabled
+	pub stayed_disabled: bool,
+	// Things worth telling the user, such as a modified gameinfo.txt being backed up
+	// or a corrupt addon installed with `force`
+	pub warnings: Vec<String>,
+	// The steps a dry run of `install` would have performed.
+	// Always empty within an `InstallSummary`, which has the plan of the whole batch.
+	pub plan: Vec<PlannedStep>,
+}
+
+// Derives the addon name from the addonTitle found in the VPK's addoninfo.txt
+pub fn addon_name_from_vpk(
+	addon_file: &str,
+	verbose: bool,
+) -> Result<String, EnforcerError> {
+	let info = vpk_getdata::main(&addon_file.to_string(), verbose)?;
+	let Some(title) = info.title.filter(|title| !title.trim().is_empty()) else {
+		let err = "Unable to define the addon name. Please specify it manually.".to_string();
+		return Err(EnforcerError::Other(err));
+	};
+	Ok(sanitize_filename::sanitize(title.replace(" ", "_").replace("'", "").as_str()))
+}
+
+// Installs or updates one addon file as `name`.
+// A corrupt addon is refused unless `force` is set.
+pub fn install(
+	game_dir: &Path,
+	addon_file: &Path,
+	name: &str,
+	force: bool,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<InstallReport, EnforcerError> {
+	validate_addon_name(name)?;
+	let mut batch = InstallBatch::begin(game_dir, force, dry_run, verbose)?;
+	let mut report = batch.stage(&addon_file.to_string_lossy(), name, &origin_of(addon_file))?;
+	let mut warnings = std::mem::take(&mut batch.warnings);
+	warnings.append(&mut report.warnings);
+	report.warnings = warnings;
+	report.plan = batch.finish()?;
+	Ok(report)
+}
+
+// An addon file to install along with what it came from (or why it cannot be installed),
+// labelled for the summary
+type AddonSource = (String, Result<(PathBuf, String), String>);
+// What was installed, or the error message
+pub type InstallOutcome = Result<InstallReport, EnforcerError>;
+
+// What `install_addons` did
+#[derive(Debug, Default)]
+pub struct InstallSummary {
+	// About the whole batch, such as a modified gameinfo.txt being backed up.
+	// The warnings about one addon are in its report.
+	pub warnings: Vec<String>,
+	// Each addon file with its label (its file name, or its path in an archive)
+	pub results: Vec<(String, InstallOutcome)>,
+	// The archive entries that are not addons and were left out: the archive and the entry
+	pub skipped: Vec<(String, String)>,
+	// The steps a dry run would have performed
+	pub plan: Vec<PlannedStep>,
+}
+
+impl InstallSummary {
+	pub fn failed(&self) -> usize {
+		self.results.iter().filter(|(_, outcome)| outcome.is_err()).count()
+	}
+
+	// Whether some addons were refused as corrupt, see `--force`
+	pub fn has_corrupt(&self) -> bool {
+		self.results.iter().any(|(_, outcome)| matches!(outcome, Err(EnforcerError::VpkCorrupt { .. })))
+	}
+}
+
+// Installs every VPK found in `inputs` (files, directories, glob patterns and archives).
+// Addons are installed one by one, a failing addon is rolled back on its own and reported
+// in the summary, while gameinfo.txt is written only once at the end.
+// Only errors stopping the whole batch are returned as such.
+// Corrupt addons are refused unless `force` is set.
+pub fn install_addons(
+	game_dir: &Path,
+	inputs: &[String],
+	name: Option<&str>,
+	force: bool,
+	dry_run: bool,
 	verbose: bool,
-) -> Result<i32, Box<dyn std::error::Error>> {
-	let datapack = vpk_getdata::main(&addon_file, verbose)?;
-	if datapack.title.is_empty() {
+) -> Result<InstallSummary, EnforcerError> {
+	// Unpacked archives and packed folders must outlive the batch,
+	// their VPKs are copied from a temporary directory
+	let mut archives = Vec::new();
+	let mut packed = Vec::new();
+	let sources = collect_addon_files(inputs, &mut archives, &mut packed, verbose)?;
+	if sources.is_empty() {
+		let err = format!("No addon.vpk files found in: {}", inputs.join(", "));
+		return Err(EnforcerError::Other(err));
+	}
+	if name.is_some() && sources.len() > 1 {
 		let err = format!(
-			"Unable to define the addon name. Please specify it manually."
+			"Argument -n <name> can only be used when installing a single addon! ({} files given)",
+			sources.len()
 		);
-		eprintln!("{} {}", "Error:".red(), err);
-		println!(
-			"Type {} / {} for more information",
-			"-h".blue(),
-			"--help".blue()
-		);
-		return Err(Box::new(QuietErr(Some(err))));
+		return Err(EnforcerError::Usage(err));
+	}
+
+	let mut batch = InstallBatch::begin(game_dir, force, dry_run, verbose)?;
+	let mut summary = InstallSummary { warnings: std::mem::take(&mut batch.warnings), ..InstallSummary::default() };
+	for (label, source) in sources {
+		let outcome = source.map_err(EnforcerError::Other).and_then(|(file, origin)| {
+			let file = file.to_string_lossy().to_string();
+			if var_os("DEBUG").is_some() || verbose {
+				log::debug!("Installing {:?}", file);
+			}
+			let name = match name {
+				Some(name) => name.to_string(),
+				None => addon_name_from_vpk(&file, verbose)?,
+			};
+			validate_addon_name(&name)?;
+			batch.stage(&file, &name, &origin)
+		});
+		summary.results.push((label, outcome));
 	}
-		let i = install_addon(&addon_file, &sanitize_filename::sanitize(&datapack.title.replace(" ", "_").replace("'", "").as_str()), verbose)?;
-		Ok(i)
+	summary.plan = batch.finish()?;
+	for archive in &archives {
+		for skipped in &archive.skipped {
+			summary.skipped.push((archive.name.clone(), skipped.clone()));
+		}
+	}
+	Ok(summary)
 }
 
-pub fn install_addon(
-	addon_file: &str,
-	name: &str,
+// Expands directories (their *.vpk files), glob patterns, archives and loose addon folders
+// into a list of addon files, each with a label for the summary.
+// Archives and folders that cannot be unpacked or packed are kept as failures.
+fn collect_addon_files(
+	inputs: &[String],
+	archives: &mut Vec<UnpackedArchive>,
+	packed: &mut Vec<TempDir>,
 	verbose: bool,
-) -> Result<i32, Box<dyn std::error::Error>> {
-	let gameinfo_orig_md5 = format!("586b3b0b39bc44ddfb07792b1932c479");
-
-	// Require both arguments on installation
-	if (name.is_empty() && !addon_file.is_empty()) || (!name.is_empty() && addon_file.is_empty()) {
-		let err = format!("Both addon name and addon file path must be provided!");
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
-	}
-	// Validate addon name
-	if
-	/*name.is_empty() || */
-	name.contains(char::is_whitespace)
+) -> Result<Vec<AddonSource>, EnforcerError> {
+	let mut files = Vec::new();
+	for input in inputs {
+		let path = PathBuf::from(input);
+		if path.is_dir() && !is_loose_addon(&path) {
+			let mut found: Vec<PathBuf> = std::fs::read_dir(&path)?
+				.filter_map(|entry| entry.ok().map(|e| e.path()))
+				.filter(|p| p.is_file() && is_vpk(p))
+				.collect();
+			found.sort();
+			files.extend(found);
+		} else if !path.exists() && input.contains(['*', '?', '[']) {
+			let pattern = glob::glob(input).map_err(|e| {
+				let err = format!("Invalid file pattern \"{}\": {}", input, e);
+				EnforcerError::Other(err)
+			})?;
+			files.extend(pattern.filter_map(Result::ok).filter(|p| p.is_file() || is_loose_addon(p)));
+		} else {
+			// Loose addon folders are packed below,
+			// non-existing files are reported by the installation itself
+			files.push(path);
+		}
+	}
+	// Chunks of a multi-chunk VPK are installed along with their _dir.vpk
+	let mut unique: Vec<PathBuf> = Vec::new();
+	for file in files {
+		let file = vpk_getdata::dir_vpk_of_chunk(&file).unwrap_or(file);
+		if !unique.contains(&file) {
+			unique.push(file);
+		}
+	}
+
+	let mut sources = Vec::new();
+	for file in unique {
+		let label = file.file_name().map_or(file.to_string_lossy(), |f| f.to_string_lossy()).to_string();
+		if file.is_dir() {
+			match pack_to_temp(&file, verbose) {
+				Ok((dir, vpk)) => {
+					sources.push((format!("{}/", label), Ok((vpk, origin_of(&file)))));
+					packed.push(dir);
+				},
+				Err(e) => sources.push((label, Err(format!("Unable to pack the folder: {}", e)))),
+			}
+		} else if is_unsupported_archive(&file) {
+			let err = "Unsupported archive format, only .zip and .tar.gz can be installed directly.".to_string();
+			sources.push((label, Err(err)));
+		} else if is_archive(&file) && file.is_file() {
+			match unpack_archive(&file, verbose) {
+				Ok(archive) if archive.vpks.is_empty() => {
+					sources.push((label, Err("The archive does not contain any .vpk file.".to_string())));
+				},
+				Ok(archive) => {
+					for vpk in archive.vpks.iter().filter(|v| vpk_getdata::dir_vpk_of_chunk(v).is_none()) {
+						let entry = vpk.strip_prefix(archive.dir.path()).unwrap_or(vpk);
+						let origin = format!("{}: {}", origin_of(&file), entry.to_string_lossy());
+						sources.push((archive.label(vpk), Ok((vpk.clone(), origin))));
+					}
+					archives.push(archive);
+				},
+				Err(e) => sources.push((label, Err(format!("Unable to unpack the archive: {}", e)))),
+			}
+		} else {
+			let origin = origin_of(&file);
+			sources.push((label, Ok((file, origin))));
+		}
+	}
+	Ok(sources)
+}
+
+// Packs a loose addon folder into a VPK inside a new temporary directory
+fn pack_to_temp(dir: &Path, verbose: bool) -> Result<(TempDir, PathBuf), EnforcerError> {
+	let temp = tempfile::Builder::new().prefix("l4ae-").tempdir()?;
+	let vpk = temp.path().join("pak01_dir.vpk");
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Packing {:?} into {:?}", dir, vpk);
+	}
+	write_vpk(dir, &vpk, false, verbose)?;
+	Ok((temp, vpk))
+}
+
+// How the manifest records where an addon was installed from
+fn origin_of(path: &Path) -> String {
+	std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().to_string()
+}
+
+fn is_vpk(path: &Path) -> bool {
+	path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("vpk"))
+}
+
+// Names must be usable as a directory and in gameinfo.txt, and leave the game's own paths alone
+pub fn validate_addon_name(name: &str) -> Result<(), EnforcerError> {
+	if is_game_dir(name) {
+		return Err(EnforcerError::CoreComponentProtected(name.to_string()));
+	}
+	if name.is_empty()
+		|| name == "."
+		|| name == ".."
+		|| name.contains(char::is_whitespace)
 		|| name.contains('/')
 		|| name.contains('\\')
 		|| name.contains(':')
@@ -49,133 +269,239 @@ pub fn install_addon(
 		|| name.contains('>')
 		|| name.contains('|')
 	{
-		let err = format!(
-			"Invalid addon name! \n\tName cannot be empty, contain whitespace, or special characters \n\tthat are known to cause problems with file managers or filesystems."
-		);
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
+		return Err(EnforcerError::InvalidName(name.to_string()));
 	}
+	Ok(())
+}
 
-	// Validate addon file
-	let addon_path = PathBuf::from(&addon_file);
-	if var_os("DEBUG").is_some() {
-		println!("{} {} {:?}", "[D]".blue(), "Addon path:".bold(), addon_path);
-	}
-	if !addon_path.is_file() {
-		let err = format!("Invalid addon file path!");
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
-	}
+// One or more addon installations sharing a single gameinfo.txt rewrite
+struct InstallBatch {
+	tx: Transaction,
+	gameinfo_path: PathBuf,
+	gameinfo: Gameinfo,
+	gameinfo_changed: bool,
+	manifest: Manifest,
+	game_dir: PathBuf,
+	staged: Vec<String>,
+	// Collected by `begin`, for the front-end to show
+	warnings: Vec<String>,
+	// Install addons that fail verification
+	force: bool,
+	verbose: bool,
+}
 
-	// Locate the gameinfo.txt file
-	let gameinfo_path = gameinfo_path(verbose)?;
-	// Calculate the MD5 of the gameinfo.txt file
-	let gameinfo_md5 = calculate_md5(&gameinfo_path).expect("Failed to calculate MD5");
+impl InstallBatch {
+	fn begin(game_dir: &Path, force: bool, dry_run: bool, verbose: bool) -> Result<InstallBatch, EnforcerError> {
+		// Every change below is journaled and reverted if a later step fails
+		let mut tx = Transaction::new(dry_run, verbose);
+		let mut warnings = Vec::new();
 
-	let gameinfo_backup_path = gameinfo_backup_path(verbose)?;
-	if var_os("DEBUG").is_some() || verbose {
-		println!(
-			"{} {} {:?}",
-			"[D]".blue(),
-			"gameinfo_backup_path:".bold(),
-			gameinfo_backup_path
-		);
+		// Locate the gameinfo.txt file
+		let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+		// Calculate the MD5 of the gameinfo.txt file
+		let gameinfo_md5 = calculate_md5(&gameinfo_path)?;
+		let vanilla = check_vanilla(&gameinfo_path)?;
+
+		let gameinfo_backup_path = gameinfo_backup_path(game_dir, verbose)?;
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("gameinfo_backup_path: {:?}", gameinfo_backup_path);
+		}
+		if !gameinfo_backup_path.exists() {
+			if var_os("DEBUG").is_some() || verbose {
+				log::debug!("gameinfo.txt is {}", vanilla.describe());
+			}
+			if !vanilla.is_vanilla() {
+				warnings.push(format!(
+					"gameinfo.txt file seems to be modified!\nThe backup may be useless. Though proceeding anyway...\n
+If you haven't already modified the gameinfo.txt, this is probably\n\ta {}'s bug you can report to the dev!\n\t\tYour gameinfo.txt MD5 hash is: {}",
+					env!("CARGO_PKG_NAME"),
+					gameinfo_md5
+				));
+			}
+			// Create a backup of the gameinfo.txt file
+			tx.copy(&gameinfo_path, &gameinfo_backup_path)?;
+		} else {
+			if var_os("DEBUG").is_some() || verbose {
+				log::debug!(
+					"Found a gameinfo.txt backup ({:?})",
+					gameinfo_backup_path.file_name().unwrap().to_string_lossy(),
+				);
+			}
+		}
+
+		// Read and parse the gameinfo.txt file
+		let gameinfo = Gameinfo::read(&gameinfo_path)?;
+		let manifest = Manifest::load(game_dir, verbose)?;
+
+		Ok(InstallBatch {
+			tx,
+			gameinfo_path,
+			gameinfo,
+			gameinfo_changed: false,
+			manifest,
+			game_dir: game_dir.to_path_buf(),
+			staged: Vec::new(),
+			warnings,
+			force,
+			verbose,
+		})
 	}
-	if !gameinfo_backup_path.exists() {
-		if gameinfo_md5 != gameinfo_orig_md5 {
-			println!(
-				"{} gameinfo.txt file seems to be modified!",
-				"Warning:".yellow()
-			);
-			println!("The backup may be useless. Though proceeding anyway...");
-			println!("");
-			println!("If you haven't already modified the gameinfo.txt, this is probably");
-			println!(
-				"\ta {}'s bug you can report to the dev!",
-				env!("CARGO_PKG_NAME")
+
+	// Copies one addon in place and adds its entry to the in-memory gameinfo.txt and manifest.
+	// On failure, only this addon's changes are reverted.
+	fn stage(&mut self, addon_file: &str, name: &str, source: &str) -> Result<InstallReport, EnforcerError> {
+		if self.staged.iter().any(|staged| staged == name) {
+			let err = format!(
+				"Another addon in this batch is already being installed as {}! Install it separately with -n <name>.",
+				name
 			);
-			println!("\t\tYour gameinfo.txt MD5 hash is: {}", gameinfo_md5.bold());
+			return Err(EnforcerError::Other(err));
 		}
-		// Create a backup of the gameinfo.txt file
-		copy(&gameinfo_path, &gameinfo_backup_path)?;
-	} else {
+
+		let savepoint = self.tx.savepoint();
+		let gameinfo_before = self.gameinfo.doc.clone();
+		let manifest_before = self.manifest.clone();
+		match self.stage_steps(addon_file, name, source) {
+			Ok(report) => {
+				self.staged.push(name.to_string());
+				Ok(report)
+			},
+			Err(e) => {
+				self.tx.rollback_to(savepoint);
+				self.gameinfo.doc = gameinfo_before;
+				self.manifest = manifest_before;
+				Err(e)
+			},
+		}
+	}
+
+	fn stage_steps(&mut self, addon_file: &str, name: &str, source: &str) -> Result<InstallReport, EnforcerError> {
+		let verbose = self.verbose;
+
+		// Validate addon file
+		let addon_path = PathBuf::from(&addon_file);
 		if var_os("DEBUG").is_some() || verbose {
-			println!(
-				"{} Found a gameinfo.txt backup ({:?})",
-				"[D]".blue(),
-				gameinfo_backup_path.file_name().unwrap().to_string_lossy()
+			log::debug!("Addon path: {:?}", addon_path);
+		}
+		if !addon_path.is_file() {
+			let err = format!("Invalid addon file path: {}", addon_path.display());
+			return Err(EnforcerError::Other(err));
+		}
+
+		// A `<name>_dir.vpk` may keep its files in numbered `<name>_NNN.vpk` chunks next to it
+		let chunks = vpk_getdata::vpk_chunks(&addon_path)?;
+		let missing: Vec<String> = vpk_getdata::archive_indices(&addon_path)?
+			.into_iter()
+			.filter(|index| !chunks.iter().any(|(chunk, _)| chunk == index))
+			.map(|index| format!("{:03}", index))
+			.collect();
+		if !missing.is_empty() {
+			let err = format!(
+				"The addon is split into chunks, but some of them are missing next to {}! \n\tMissing chunk number(s): {}",
+				addon_path.file_name().unwrap_or_default().to_string_lossy(),
+				missing.join(", ")
 			);
+			return Err(EnforcerError::Other(err));
+		}
+		if !chunks.is_empty() && (var_os("DEBUG").is_some() || verbose) {
+			log::debug!("Found {} chunk file(s) next to {:?}", chunks.len(), addon_path);
 		}
-	}
 
-	// Read the gameinfo.txt file
-	let contents = read_to_string(&gameinfo_path)?;
+		// A half-downloaded or damaged addon is refused, unless forced
+		let mut warnings = Vec::new();
+		match verify_vpk(&addon_path, verbose)?.into_result() {
+			Ok(_) => {},
+			Err(e) if self.force => warnings.push(format!("{} \n\tInstalling it anyway because of --force.", e)),
+			Err(e) => return Err(e),
+		}
 
-	// Split the file into lines
-	let mut lines: Vec<&str> = contents.lines().collect();
+		// Create the new addon directory
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Creating addon directory: {}", name);
+		}
+		let addon_dir = self.game_dir.join(name);
+		let mut addon_dir_existed = false;
+		if addon_dir.exists() {
+			addon_dir_existed = true;
+			if var_os("DEBUG").is_some() || verbose {
+				log::debug!("Warning: An addon directory with the same name already exists! Assuming this is an update.");
+			}
+		} else {
+			self.tx.create_dir(&addon_dir)?;
+		}
 
-	// Create the new addon directory
-	if var_os("DEBUG").is_some() || verbose {
-		println!("{} Creating addon directory: {}", "[D]".blue(), name);
-	}
-	let l4d2_dir = l4d2_path()?;
-	let addon_dir = l4d2_dir.join(format!("{}", name));
-	let mut addon_dir_existed = false;
-	if addon_dir.exists() {
-		addon_dir_existed = true;
+		// Copy the addon file to the new addon directory
 		if var_os("DEBUG").is_some() || verbose {
-			println!(
-				"{} {} An addon directory with the same name already exists! Assuming this is an update.",
-				"[D]".blue(),
-				"Warning:".yellow()
-			);
+			log::debug!("Copying addon file to: {:?}", addon_dir);
+		}
+		let destination = addon_dir.join("pak01_dir.vpk");
+		if destination.exists() && (var_os("DEBUG").is_some() || verbose) {
+			log::debug!("Replacing {:?}", destination);
+		}
+		self.tx.copy(&addon_path, &destination)?;
+		for (index, chunk) in &chunks {
+			self.tx.copy(chunk, &addon_dir.join(format!("pak01_{:03}.vpk", index)))?;
+		}
+		// Chunks left over from a previous, bigger version are no longer referenced by the tree
+		if addon_dir_existed {
+			for (index, stale) in vpk_getdata::vpk_chunks(&destination)? {
+				if !chunks.iter().any(|(chunk, _)| *chunk == index) {
+					if var_os("DEBUG").is_some() || verbose {
+						log::debug!("Removing stale chunk {:?}", stale);
+					}
+					self.tx.remove(&stale)?;
+				}
+			}
 		}
-	} else {
-		create_dir_all(&addon_dir)?;
-	}
 
-	// Copy the addon file to the new addon directory
-	if var_os("DEBUG").is_some() || verbose {
-		println!("{} Copying addon file to: {:?}", "[D]".blue(), addon_dir);
+		// The copy is identical, hashing the original spares reading it back
+		self.manifest.record_install(name, Some(source.to_string()), &addon_path, verbose)?;
+
+		// A disabled addon is updated in place and stays disabled
+		let listed = self.gameinfo.has_addon(name) || self.gameinfo.is_disabled(name);
+		let up = listed && addon_dir_existed;
+		if !listed {
+			// Insert the new entry above "Game update"
+			self.gameinfo.add_addon(name)?;
+			self.gameinfo_changed = true;
+		}
+		Ok(InstallReport {
+			name: name.to_string(),
+			status: if up { InstallStatus::Updated } else { InstallStatus::Installed },
+			stayed_disabled: self.gameinfo.is_disabled(name),
+			warnings,
+			plan: Vec::new(),
+		})
 	}
-	let mut destination = addon_dir.join(addon_path.file_name().unwrap());
-	destination.set_file_name("pak01_dir");
-	destination.set_extension("vpk");
-	if destination.exists() {
-		remove_file(&destination)?;
-		if var_os("DEBUG").is_some() || verbose {
-			println!("{} Deleted {:?}", "[D]".blue(), destination);
+
+	// Writes gameinfo.txt (if anything was added) and the manifest, and makes all staged changes permanent.
+	// Returns the plan of a dry run.
+	fn finish(mut self) -> Result<Vec<PlannedStep>, EnforcerError> {
+		if self.gameinfo_changed {
+			// Write the updated contents back to the file
+			crate::gameinfo_history::save_snapshot(&self.game_dir, &mut self.tx, &self.gameinfo_path, "install", self.verbose)?;
+			self.tx.write(&self.gameinfo_path, self.gameinfo.doc.to_string())?;
+		}
+		if !self.staged.is_empty() {
+			self.manifest.sync(&self.gameinfo);
+			self.manifest.save(&self.game_dir, &mut self.tx)?;
 		}
+		Ok(self.tx.commit()?)
 	}
-	copy(&addon_path, &destination)?;
+}
 
-	// The line to insert
-	let new_line = format!("\t\t\tGame\t\t\t\t{}", name);
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	let mut up = false;
-	if lines
-		.iter()
-		.any(|line| line.contains("Game") && line.contains(&name))
-		&& addon_dir_existed
-	{
-		println!("Updated {} successfully.", name.italic());
-		up = true;
-	} else {
-		// Find the line with "Game update"
-		let index = lines
-			.iter()
-			.position(|line| line.contains("Game") && line.contains("update"))
-			.unwrap_or(0);
-
-		// Insert the new line above it
-		lines.insert(index, &new_line);
-
-		// Join the lines back into a single string
-		let new_contents = lines.join("\n");
-
-		// Write the updated contents back to the file
-		write(&gameinfo_path, new_contents)?;
-		println!("Installed {} successfully.", name.italic());
-	}
-	if up { Ok(2) } else { Ok(1) }
+	#[test]
+	fn addon_names_leave_the_game_alone() {
+		assert!(validate_addon_name("ion_vocalizer").is_ok());
+		for name in ["", ".", "..", "my addon", "a\"b", "../left4dead2", "C:"] {
+			assert!(matches!(validate_addon_name(name), Err(EnforcerError::InvalidName(_))), "{:?}", name);
+		}
+		for name in ["left4dead2", "Left4Dead2_LV", "platform", "update", "left4dead2_dlc3"] {
+			assert!(matches!(validate_addon_name(name), Err(EnforcerError::CoreComponentProtected(_))), "{:?}", name);
+		}
+	}
 }
@@ -0,0 +1,675 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+
+// A lossless reader/writer for Valve's KeyValues (KV1) text format.
+//
+// Every byte of the input ends up somewhere in the tree: whitespace and `//` comments are kept
+// as `Trivia` items next to the pairs they surround, and tokens remember whether they were quoted.
+// Printing a parsed document (via `Display`) therefore reproduces the original file byte for byte,
+// and edits only touch the lines they are about.
+
+use std::{borrow::Cow, error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvError {
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+}
+
+impl fmt::Display for KvError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+	}
+}
+impl Error for KvError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvToken {
+	pub text: String,
+	pub quoted: bool,
+}
+
+impl KvToken {
+	pub fn unquoted(text: &str) -> KvToken {
+		KvToken { text: text.to_string(), quoted: false }
+	}
+
+	// A token reading back as `text`: unquoted when it can be, else quoted with `"` and `\` escaped
+	pub fn new(text: &str) -> KvToken {
+		let needs_quotes = text.is_empty()
+			|| text.starts_with("//")
+			|| text.contains(|c: char| c.is_ascii_whitespace() || matches!(c, '"' | '{' | '}'));
+		if !needs_quotes {
+			return KvToken::unquoted(text);
+		}
+		KvToken { text: text.replace('\\', "\\\\").replace('"', "\\\""), quoted: true }
+	}
+
+	// The text with the escape sequences of a quoted token (\" \\ \n \t) resolved.
+	// `text` keeps them as written, so that the file prints back unchanged.
+	pub fn unescaped(&self) -> Cow<'_, str> {
+		if !self.quoted || !self.text.contains('\\') {
+			return Cow::Borrowed(&self.text);
+		}
+		let mut unescaped = String::with_capacity(self.text.len());
+		let mut chars = self.text.chars();
+		while let Some(c) = chars.next() {
+			if c != '\\' {
+				unescaped.push(c);
+				continue;
+			}
+			match chars.next() {
+				Some('n') => unescaped.push('\n'),
+				Some('t') => unescaped.push('\t'),
+				Some(escaped @ ('"' | '\\')) => unescaped.push(escaped),
+				Some(other) => {
+					unescaped.push('\\');
+					unescaped.push(other);
+				},
+				None => unescaped.push('\\'),
+			}
+		}
+		Cow::Owned(unescaped)
+	}
+}
+
+impl fmt::Display for KvToken {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.quoted {
+			write!(f, "\"{}\"", self.text)
+		} else {
+			write!(f, "{}", self.text)
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KvValue {
+	Str(KvToken),
+	Block(KvBlock),
+}
+
+impl KvValue {
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			KvValue::Str(token) => Some(&token.text),
+			KvValue::Block(_) => None,
+		}
+	}
+	pub fn as_block(&self) -> Option<&KvBlock> {
+		match self {
+			KvValue::Block(block) => Some(block),
+			KvValue::Str(_) => None,
+		}
+	}
+	pub fn as_block_mut(&mut self) -> Option<&mut KvBlock> {
+		match self {
+			KvValue::Block(block) => Some(block),
+			KvValue::Str(_) => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvPair {
+	pub key: KvToken,
+	// Whitespace (and comments) between the key and its value
+	pub gap: String,
+	pub value: KvValue,
+	// Platform conditional following a value, e.g. `[$WIN32]`, together with the whitespace before it
+	pub condition: Option<(String, String)>,
+}
+
+impl KvPair {
+	pub fn new(key: KvToken, gap: &str, value: KvValue) -> KvPair {
+		KvPair { key, gap: gap.to_string(), value, condition: None }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KvItem {
+	Trivia(String),
+	Pair(KvPair),
+}
+
+// The contents of a `{ ... }` block (or of the whole document), in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KvBlock {
+	pub items: Vec<KvItem>,
+}
+
+impl KvBlock {
+	pub fn pairs(&self) -> impl Iterator<Item = &KvPair> {
+		self.items.iter().filter_map(|item| match item {
+			KvItem::Pair(pair) => Some(pair),
+			KvItem::Trivia(_) => None,
+		})
+	}
+
+	pub fn pairs_mut(&mut self) -> impl Iterator<Item = &mut KvPair> {
+		self.items.iter_mut().filter_map(|item| match item {
+			KvItem::Pair(pair) => Some(pair),
+			KvItem::Trivia(_) => None,
+		})
+	}
+
+	// Keys are matched case-insensitively, as the engine does
+	pub fn get_block(&self, key: &str) -> Option<&KvBlock> {
+		self.pairs()
+			.filter(|pair| pair.key.text.eq_ignore_ascii_case(key))
+			.find_map(|pair| pair.value.as_block())
+	}
+
+	pub fn get_block_mut(&mut self, key: &str) -> Option<&mut KvBlock> {
+		self.pairs_mut()
+			.filter(|pair| pair.key.text.eq_ignore_ascii_case(key))
+			.find_map(|pair| pair.value.as_block_mut())
+	}
+
+	pub fn find_block(&self, path: &[&str]) -> Option<&KvBlock> {
+		path.iter().try_fold(self, |block, key| block.get_block(key))
+	}
+
+	pub fn find_block_mut(&mut self, path: &[&str]) -> Option<&mut KvBlock> {
+		path.iter().try_fold(self, |block, key| block.get_block_mut(key))
+	}
+
+	pub fn pair_at(&self, index: usize) -> Option<&KvPair> {
+		match self.items.get(index) {
+			Some(KvItem::Pair(pair)) => Some(pair),
+			_ => None,
+		}
+	}
+
+	pub fn pair_at_mut(&mut self, index: usize) -> Option<&mut KvPair> {
+		match self.items.get_mut(index) {
+			Some(KvItem::Pair(pair)) => Some(pair),
+			_ => None,
+		}
+	}
+
+	// Line ending used by the block, so that inserted lines match the rest of the file
+	fn newline(&self) -> &'static str {
+		let crlf = self.items.iter().any(|item| matches!(item, KvItem::Trivia(t) if t.contains("\r\n")));
+		if crlf { "\r\n" } else { "\n" }
+	}
+
+	// Indentation of the pair at `index`, taken from the trivia in front of it
+	fn indent_of(&self, index: usize) -> String {
+		match index.checked_sub(1).and_then(|i| self.items.get(i)) {
+			Some(KvItem::Trivia(t)) => match t.rfind('\n') {
+				Some(nl) => t[nl + 1..].to_string(),
+				None => String::new(),
+			},
+			_ => String::new(),
+		}
+	}
+
+	// Inserts `pair` on its own line right above the pair found at item `index`,
+	// using the same indentation.
+	pub fn insert_line_before(&mut self, index: usize, pair: KvPair) {
+		let newline = self.newline();
+		let indent = self.indent_of(index);
+		self.items.insert(index, KvItem::Trivia(format!("{}{}", newline, indent)));
+		self.items.insert(index, KvItem::Pair(pair));
+	}
+
+	// Removes the pair at item `index` together with the line it was written on
+	// (its indentation and any comment trailing it on the same line).
+	pub fn remove_line(&mut self, index: usize) -> Option<KvPair> {
+		let pair = match self.items.get(index) {
+			Some(KvItem::Pair(_)) => match self.items.remove(index) {
+				KvItem::Pair(pair) => pair,
+				KvItem::Trivia(_) => unreachable!(),
+			},
+			_ => return None,
+		};
+
+		// Drop the rest of the removed line: everything up to (not including) the next line break
+		if let Some(KvItem::Trivia(after)) = self.items.get_mut(index) {
+			if let Some(nl) = after.find('\n') {
+				let cut = if nl > 0 && after.as_bytes()[nl - 1] == b'\r' { nl - 1 } else { nl };
+				after.replace_range(..cut, "");
+			}
+		}
+		// ...and the line break plus indentation that led to it
+		let mut merged = false;
+		if index > 0 {
+			if let Some(KvItem::Trivia(before)) = self.items.get_mut(index - 1) {
+				if let Some(nl) = before.rfind('\n') {
+					let cut = if nl > 0 && before.as_bytes()[nl - 1] == b'\r' { nl - 1 } else { nl };
+					before.truncate(cut);
+				}
+				merged = true;
+			}
+		}
+		if merged {
+			if let Some(KvItem::Trivia(after)) = self.items.get(index).cloned() {
+				self.items.remove(index);
+				if let Some(KvItem::Trivia(before)) = self.items.get_mut(index - 1) {
+					before.push_str(&after);
+				}
+			}
+		}
+		Some(pair)
+	}
+
+	// Turns the pair at item `index` into a `//` comment on the same line, so that the engine
+	// ignores it while the file still remembers it. Only pairs with a string value fit on one line.
+	pub fn comment_out(&mut self, index: usize) -> bool {
+		let newline = self.newline();
+		let indent = self.indent_of(index);
+		let mut text = match self.items.get(index) {
+			Some(KvItem::Pair(pair)) if pair.value.as_str().is_some() => commented_line(pair, newline, &indent),
+			_ => return false,
+		};
+		// Comments are trivia, merge them with the trivia around the pair
+		if let Some(KvItem::Trivia(after)) = self.items.get(index + 1) {
+			text.push_str(after);
+			self.items.remove(index + 1);
+		}
+		if let Some(KvItem::Trivia(before)) = index.checked_sub(1).and_then(|i| self.items.get_mut(i)) {
+			before.push_str(&text);
+			self.items.remove(index);
+		} else {
+			self.items[index] = KvItem::Trivia(text);
+		}
+		true
+	}
+}
+
+// `//` followed by the pair, on a single line. A gap between the key and the value that spans
+// lines or holds comments is replaced by its leading whitespace, and its comments are kept
+// on lines of their own below.
+fn commented_line(pair: &KvPair, newline: &str, indent: &str) -> String {
+	if !pair.gap.contains('\n') && !pair.gap.contains("//") {
+		return format!("//{}", pair);
+	}
+	let spacing: String = pair.gap.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+	let gap = if spacing.is_empty() { "\t".to_string() } else { spacing };
+	let mut text = format!("//{}", KvPair { gap, ..pair.clone() });
+	for comment in pair.gap.lines().filter_map(|line| line.find("//").map(|at| line[at..].trim_end())) {
+		text.push_str(newline);
+		text.push_str(indent);
+		text.push_str(comment);
+	}
+	text
+}
+
+impl fmt::Display for KvBlock {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for item in &self.items {
+			match item {
+				KvItem::Trivia(trivia) => write!(f, "{}", trivia)?,
+				KvItem::Pair(pair) => write!(f, "{}", pair)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for KvPair {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}{}", self.key, self.gap)?;
+		match &self.value {
+			KvValue::Str(token) => write!(f, "{}", token)?,
+			KvValue::Block(block) => write!(f, "{{{}}}", block)?,
+		}
+		if let Some((gap, condition)) = &self.condition {
+			write!(f, "{}{}", gap, condition)?;
+		}
+		Ok(())
+	}
+}
+
+// A whole KeyValues file. The root holds the top-level pairs (usually a single "GameInfo" or
+// "AddonInfo" block) and the trivia around them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KvDocument {
+	pub root: KvBlock,
+}
+
+impl KvDocument {
+	pub fn parse(input: &str) -> Result<KvDocument, KvError> {
+		let mut parser = Parser { src: input, pos: 0 };
+		let root = parser.block(false)?;
+		Ok(KvDocument { root })
+	}
+}
+
+impl fmt::Display for KvDocument {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.root)
+	}
+}
+
+struct Parser<'a> {
+	src: &'a str,
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn error(&self, message: &str) -> KvError {
+		let before = &self.src[..self.pos];
+		let line = before.matches('\n').count() + 1;
+		let column = before.rfind('\n').map_or(before.chars().count(), |nl| before[nl + 1..].chars().count()) + 1;
+		KvError { line, column, message: message.to_string() }
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.src.as_bytes().get(self.pos).copied()
+	}
+
+	// Whitespace, `//` comments and a byte order mark
+	fn trivia(&mut self) -> &'a str {
+		let start = self.pos;
+		loop {
+			let rest = &self.src[self.pos..];
+			if rest.starts_with("//") {
+				self.pos += rest.find('\n').unwrap_or(rest.len());
+			} else if rest.starts_with('\u{feff}') {
+				self.pos += '\u{feff}'.len_utf8();
+			} else if self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+				self.pos += 1;
+			} else {
+				break;
+			}
+		}
+		&self.src[start..self.pos]
+	}
+
+	fn token(&mut self) -> Result<KvToken, KvError> {
+		if self.peek() == Some(b'"') {
+			let rest = &self.src[self.pos + 1..];
+			let len = closing_quote(rest).ok_or_else(|| self.error("Unterminated quoted string"))?;
+			let text = rest[..len].to_string();
+			self.pos += len + 2;
+			Ok(KvToken { text, quoted: true })
+		} else {
+			let start = self.pos;
+			while let Some(c) = self.peek() {
+				if c.is_ascii_whitespace() || c == b'"' || c == b'{' || c == b'}' {
+					break;
+				}
+				self.pos += 1;
+			}
+			Ok(KvToken { text: self.src[start..self.pos].to_string(), quoted: false })
+		}
+	}
+
+	// Optional `[$CONDITION]` on the same line as a value
+	fn condition(&mut self) -> Result<Option<(String, String)>, KvError> {
+		let start = self.pos;
+		while matches!(self.peek(), Some(b' ') | Some(b'\t')) {
+			self.pos += 1;
+		}
+		if self.peek() != Some(b'[') {
+			self.pos = start;
+			return Ok(None);
+		}
+		let gap = self.src[start..self.pos].to_string();
+		let rest = &self.src[self.pos..];
+		let len = rest.find(']').ok_or_else(|| self.error("Unterminated conditional"))?;
+		let condition = rest[..=len].to_string();
+		self.pos += len + 1;
+		Ok(Some((gap, condition)))
+	}
+
+	fn block(&mut self, braced: bool) -> Result<KvBlock, KvError> {
+		let mut block = KvBlock::default();
+		loop {
+			let trivia = self.trivia();
+			if !trivia.is_empty() {
+				block.items.push(KvItem::Trivia(trivia.to_string()));
+			}
+			match self.peek() {
+				None if braced => return Err(self.error("Unexpected end of file, missing '}'")),
+				None => return Ok(block),
+				Some(b'}') if braced => {
+					self.pos += 1;
+					return Ok(block);
+				},
+				Some(b'}') => return Err(self.error("Unexpected '}'")),
+				Some(b'{') => return Err(self.error("Unexpected '{' without a key")),
+				Some(_) => {},
+			}
+
+			let key = self.token()?;
+			let gap = self.trivia().to_string();
+			let pair = match self.peek() {
+				None => return Err(self.error(&format!("Missing value for key \"{}\"", key.text))),
+				Some(b'}') => return Err(self.error(&format!("Missing value for key \"{}\"", key.text))),
+				Some(b'{') => {
+					self.pos += 1;
+					let value = KvValue::Block(self.block(true)?);
+					KvPair { key, gap, value, condition: None }
+				},
+				Some(_) => {
+					let value = KvValue::Str(self.token()?);
+					let condition = self.condition()?;
+					KvPair { key, gap, value, condition }
+				},
+			};
+			block.items.push(KvItem::Pair(pair));
+		}
+	}
+}
+
+// Offset of the quote ending a quoted token, `\"` and `\\` being escaped characters within it
+fn closing_quote(rest: &str) -> Option<usize> {
+	let bytes = rest.as_bytes();
+	let mut i = 0;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'"' => return Some(i),
+			b'\\' if matches!(bytes.get(i + 1), Some(b'"') | Some(b'\\')) => i += 2,
+			_ => i += 1,
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The vanilla gameinfo.txt of Left 4 Dead 2, with LF line endings
+	const VANILLA_GAMEINFO: &str = r#""GameInfo"
+{
+	game	"Left 4 Dead 2"	// Window title
+	type	multiplayer_only
+	nomodels 1
+	nohimodel 1
+	nocrosshair 0
+	hidden_maps
+	{
+		"test_speakers"		1
+		"test_hardware"		1
+	}
+	nodegraph 0
+	SupportsXbox360 1
+	SupportsDX8	0 [$WIN32]
+	GameData	"left4dead2.fgd"
+
+	FileSystem
+	{
+		SteamAppId				550		// This will mount all the GCFs we need (240=CS:S, 220=HL2).
+		ToolsAppId				211		// Tools will load this (ie: source SDK caches) to get things like materials\debug, materials\editor, etc.
+		
+		//
+		// The code that loads this file automatically does a few things here:
+		//
+		// 1. For each "Game" search path, it adds a "GameBin" path, in <dir>\bin
+		// 2. For each "Game" search path, it adds another "Game" path in front of it with _<langage> at the end.
+		//    For example: c:\hl2\cstrike on a french machine would get a c:\hl2\cstrike_french path added to it.
+		// 3. For the first "Game" search path, it adds a search path called "MOD".
+		// 4. For the first "Game" search path, it adds a search path called "DEFAULT_WRITE_PATH".
+		//
+
+		//
+		// Search paths are relative to the base directory, which is where hl2.exe is found.
+		//
+		// |gameinfo_path| points at the directory where gameinfo.txt is.
+		// We always want to mount that directory relative to gameinfo.txt, so
+		// people can mount stuff in c:\mymod, and the main game resources are in
+		// someplace like c:\program files\valve\steam\steamapps\half-life 2.
+		//
+		SearchPaths
+		{
+			Game				update
+			Game				left4dead2_dlc3
+			Game				left4dead2_dlc2
+			Game				left4dead2_dlc1
+			Game				|gameinfo_path|.
+			Game				hl2
+		}
+	}
+}
+"#;
+
+	const SEARCH_PATHS: [&str; 3] = ["GameInfo", "FileSystem", "SearchPaths"];
+
+	fn crlf(text: &str) -> String {
+		text.replace('\n', "\r\n")
+	}
+
+	fn search_path_values(doc: &KvDocument) -> Vec<String> {
+		let block = doc.root.find_block(&SEARCH_PATHS).unwrap();
+		block.pairs().filter_map(|pair| pair.value.as_str()).map(str::to_string).collect()
+	}
+
+	// Item index of the pair whose value is `value`
+	fn index_of(block: &KvBlock, value: &str) -> usize {
+		block.items.iter().position(|item| matches!(item, KvItem::Pair(pair) if pair.value.as_str() == Some(value))).unwrap()
+	}
+
+	#[test]
+	fn round_trips_vanilla_gameinfo() {
+		for contents in [VANILLA_GAMEINFO.to_string(), crlf(VANILLA_GAMEINFO)] {
+			let doc = KvDocument::parse(&contents).unwrap();
+			assert_eq!(doc.to_string(), contents);
+			assert_eq!(search_path_values(&doc), ["update", "left4dead2_dlc3", "left4dead2_dlc2", "left4dead2_dlc1", "|gameinfo_path|.", "hl2"]);
+		}
+	}
+
+	#[test]
+	fn round_trips_byte_order_mark() {
+		let contents = format!("\u{feff}{}", crlf(VANILLA_GAMEINFO));
+		assert_eq!(KvDocument::parse(&contents).unwrap().to_string(), contents);
+	}
+
+	#[test]
+	fn keeps_platform_conditions() {
+		let doc = KvDocument::parse(VANILLA_GAMEINFO).unwrap();
+		let gameinfo = doc.root.get_block("GameInfo").unwrap();
+		let dx8 = gameinfo.pairs().find(|pair| pair.key.text == "SupportsDX8").unwrap();
+		assert_eq!(dx8.value.as_str(), Some("0"));
+		assert_eq!(dx8.condition, Some((" ".to_string(), "[$WIN32]".to_string())));
+	}
+
+	#[test]
+	fn remove_line_drops_the_whole_line() {
+		for newline in ["\n", "\r\n"] {
+			let contents = VANILLA_GAMEINFO.replace('\n', newline);
+			let mut doc = KvDocument::parse(&contents).unwrap();
+			let block = doc.root.find_block_mut(&SEARCH_PATHS).unwrap();
+			let removed = block.remove_line(index_of(block, "left4dead2_dlc2")).unwrap();
+			assert_eq!(removed.value.as_str(), Some("left4dead2_dlc2"));
+			let expected = contents.replace(&format!("{}\t\t\tGame\t\t\t\tleft4dead2_dlc2", newline), "");
+			assert_eq!(doc.to_string(), expected);
+			assert_eq!(KvDocument::parse(&expected).unwrap(), doc.clone());
+		}
+	}
+
+	#[test]
+	fn remove_line_takes_a_trailing_comment_along() {
+		let mut doc = KvDocument::parse("Paths\n{\n\tGame\tfoo\t// mine\n\tGame\tbar\n}\n").unwrap();
+		let block = doc.root.get_block_mut("Paths").unwrap();
+		block.remove_line(index_of(block, "foo"));
+		assert_eq!(doc.to_string(), "Paths\n{\n\tGame\tbar\n}\n");
+	}
+
+	#[test]
+	fn remove_line_ignores_trivia() {
+		let mut doc = KvDocument::parse("Paths\n{\n\tGame\tfoo\n}\n").unwrap();
+		let block = doc.root.get_block_mut("Paths").unwrap();
+		assert!(block.remove_line(0).is_none());
+		assert_eq!(doc.to_string(), "Paths\n{\n\tGame\tfoo\n}\n");
+	}
+
+	#[test]
+	fn comment_out_keeps_the_line() {
+		for newline in ["\n", "\r\n"] {
+			let contents = VANILLA_GAMEINFO.replace('\n', newline);
+			let mut doc = KvDocument::parse(&contents).unwrap();
+			let block = doc.root.find_block_mut(&SEARCH_PATHS).unwrap();
+			assert!(block.comment_out(index_of(block, "left4dead2_dlc1")));
+			let expected = contents.replace("\t\t\tGame\t\t\t\tleft4dead2_dlc1", "\t\t\t//Game\t\t\t\tleft4dead2_dlc1");
+			assert_eq!(doc.to_string(), expected);
+			assert!(!search_path_values(&KvDocument::parse(&expected).unwrap()).contains(&"left4dead2_dlc1".to_string()));
+		}
+	}
+
+	#[test]
+	fn comment_out_puts_a_split_pair_on_one_line() {
+		let mut doc = KvDocument::parse("Paths\r\n{\r\n\tGame\r\n\t\tfoo\r\n\tGame\tbar\r\n}\r\n").unwrap();
+		let block = doc.root.get_block_mut("Paths").unwrap();
+		assert!(block.comment_out(index_of(block, "foo")));
+		assert_eq!(doc.to_string(), "Paths\r\n{\r\n\t//Game\tfoo\r\n\tGame\tbar\r\n}\r\n");
+	}
+
+	#[test]
+	fn comment_out_keeps_comments_of_the_gap() {
+		let mut doc = KvDocument::parse("Paths\n{\n\tGame // the HUD\n\t\tfoo\n}\n").unwrap();
+		let block = doc.root.get_block_mut("Paths").unwrap();
+		assert!(block.comment_out(index_of(block, "foo")));
+		assert_eq!(doc.to_string(), "Paths\n{\n\t//Game foo\n\t// the HUD\n}\n");
+		let reparsed = KvDocument::parse(&doc.to_string()).unwrap();
+		assert_eq!(reparsed.root.get_block("Paths").unwrap().pairs().count(), 0);
+	}
+
+	#[test]
+	fn comment_out_refuses_blocks() {
+		let mut doc = KvDocument::parse(VANILLA_GAMEINFO).unwrap();
+		let gameinfo = doc.root.get_block_mut("GameInfo").unwrap();
+		let file_system = gameinfo.items.iter().position(|item| matches!(item, KvItem::Pair(pair) if pair.key.text == "FileSystem")).unwrap();
+		assert!(!gameinfo.comment_out(file_system));
+		assert_eq!(doc.to_string(), VANILLA_GAMEINFO);
+	}
+
+	#[test]
+	fn parses_escaped_quotes() {
+		let contents = "\"AddonInfo\"\n{\n\taddonDescription\t\"The \\\"best\\\" HUD \\\\ ever\"\n\taddonPath\t\"c:\\\\addons\\\\\"\n}\n";
+		let doc = KvDocument::parse(contents).unwrap();
+		assert_eq!(doc.to_string(), contents);
+		let info = doc.root.get_block("AddonInfo").unwrap();
+		let values: Vec<&KvToken> = info.pairs().filter_map(|pair| match &pair.value {
+			KvValue::Str(token) => Some(token),
+			KvValue::Block(_) => None,
+		}).collect();
+		assert_eq!(values[0].text, "The \\\"best\\\" HUD \\\\ ever");
+		assert_eq!(values[0].unescaped(), "The \"best\" HUD \\ ever");
+		assert_eq!(values[1].unescaped(), "c:\\addons\\");
+	}
+
+	#[test]
+	fn new_tokens_read_back() {
+		for text in ["ion_hud", "my addon", "a\"b", "{", "//x", "", "c:\\addons"] {
+			let token = KvToken::new(text);
+			let doc = KvDocument::parse(&format!("key {}\n", token)).unwrap();
+			let KvValue::Str(parsed) = &doc.root.pairs().next().unwrap().value else {
+				panic!("expected a string value for {:?}", text);
+			};
+			assert_eq!(parsed, &token);
+			assert_eq!(parsed.unescaped(), text);
+		}
+		assert!(!KvToken::new("ion_hud").quoted);
+	}
+
+	#[test]
+	fn reports_unterminated_strings() {
+		let err = KvDocument::parse("\"AddonInfo\"\n{\n\taddonTitle \"Broken \\\"\n}\n").unwrap_err();
+		assert_eq!((err.line, err.column), (3, 13));
+		assert_eq!(err.message, "Unterminated quoted string");
+	}
+}
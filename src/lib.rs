@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+
+// The addon manager itself, without its command line and GUI front-ends.
+// Other tools can drive it through `AddonManager`, see addon_manager.rs.
+
+#![allow(non_snake_case)]
+// Shared with the front-ends in main.rs
+pub mod core_imports;
+use crate::core_imports::*;
+
+pub mod keyvalues;
+pub mod gameinfo;
+pub mod vanilla_gameinfo;
+pub mod show_diff;
+pub mod transaction;
+pub mod install_addon;
+pub mod unpack_archive;
+pub mod pack_addon;
+pub mod inspect_addon;
+pub mod find_conflicts;
+pub mod order_addons;
+pub mod toggle_addon;
+pub mod manage_profiles;
+pub mod gameinfo_history;
+pub mod addon_manifest;
+pub mod repair_manifest;
+pub mod check_health;
+pub mod rename_addon;
+pub mod list_addons;
+pub mod structured_output;
+pub mod uninstall_addon;
+pub mod pug_mode;
+pub mod vpk_getdata;
+pub mod verify_vpk;
+pub mod extract_addon;
+pub mod enforcer_error;
+pub mod addon_manager;
+
+pub use addon_manager::AddonManager;
+pub use enforcer_error::EnforcerError;
+
+// What resetting gameinfo.txt would write, see `plan_reset`
+#[derive(Debug, Clone)]
+pub struct ResetPlan {
+	// The contents of gameinfo.txt now, and once reset
+	pub current: String,
+	pub reset: String,
+	// A modified gameinfo.txt without a backup is turned into a vanilla one
+	// by removing every addon from it, rather than by restoring the backup
+	pub rebuilt: bool,
+}
+
+// Works out what `gameinfo_reset` would write, without changing anything, so it can be shown first
+pub fn plan_reset(game_dir: &Path, verbose: bool) -> Result<ResetPlan, EnforcerError> {
+	// Locate the gameinfo.txt file
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+
+	let vanilla = vanilla_gameinfo::check_vanilla(&gameinfo_path)?;
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("gameinfo.txt is {}", vanilla.describe());
+	}
+
+	let gameinfo_backup_path = gameinfo_backup_path(game_dir, verbose)?;
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("gameinfo_backup_path: {:?}", gameinfo_backup_path);
+	}
+	if vanilla.is_vanilla() {
+		let err = "gameinfo.txt is already at its default state!".to_string();
+		return Err(EnforcerError::Other(err));
+	}
+	let current = read_to_string(&gameinfo_path)?;
+	if gameinfo_backup_path.exists() {
+		let reset = read_to_string(&gameinfo_backup_path)?;
+		Ok(ResetPlan { current, reset, rebuilt: false })
+	} else {
+		let reset = vanilla_gameinfo::rebuild_vanilla(&current)?;
+		Ok(ResetPlan { current, reset, rebuilt: true })
+	}
+}
+
+// Writes the vanilla gameinfo.txt of `plan` and returns the steps a dry run planned.
+// The plan is refused when gameinfo.txt was changed since it was made.
+pub fn gameinfo_reset(
+	game_dir: &Path,
+	plan: &ResetPlan,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<Vec<transaction::PlannedStep>, EnforcerError> {
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	if read_to_string(&gameinfo_path)? != plan.current {
+		let err = "gameinfo.txt was changed since the reset was planned, nothing was reset!".to_string();
+		return Err(EnforcerError::Other(err));
+	}
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!(
+			"Writing the {} vanilla gameinfo.txt to {:?}",
+			if plan.rebuilt { "rebuilt" } else { "backed up" },
+			&gameinfo_path.file_name().unwrap().to_string_lossy(),
+		);
+	}
+	let mut tx = transaction::Transaction::new(dry_run, verbose);
+	gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "reset", verbose)?;
+	tx.write(&gameinfo_path, &plan.reset)?;
+	// The vanilla gameinfo.txt lists no addons, so the manifest forgets them too
+	let mut manifest = addon_manifest::Manifest::load(game_dir, verbose)?;
+	manifest.addons.clear();
+	manifest.save(game_dir, &mut tx)?;
+	Ok(tx.commit()?)
+}
+
+pub fn calculate_md5(filepath: &Path) -> Result<String, std::io::Error> {
+	let mut file = BufReader::new(File::open(filepath)?); // Buffered for performance
+	let mut hasher = Md5::new();
+	let mut buffer = [0; 4096]; // Read in chunks for efficiency
+	loop {
+		let count = file.read(&mut buffer)?;
+		if count == 0 {
+			break;
+		}
+		hasher.update(&buffer[..count]);
+	}
+	let result = hasher.finalize();
+	Ok(format!("{:x}", result)) // Format as hexadecimal string
+}
+
+// fn clean_path(path: &str) -> Cow<str> {
+//     if path.contains("/../") {
+//         let path_buf = PathBuf::from(format!("/{path}"));
+//         let Ok(absolute_path) = path_buf.parse_dot_from("/") else {
+//             return path.into();
+//         };
+//         let path = absolute_path.to_str().unwrap().trim_start_matches('/');
+//         String::from(path).into()
+//     } else {
+//         path.into()
+//     }
+// }
+
+// #[test]
+// fn test_clean_path() {
+//     assert_eq!("foo/bar", clean_path("foo/bar"));
+//     assert_eq!("foo/bar", clean_path("foo/asd/../bar"));
+//     assert_eq!("../bar", clean_path("../bar"));
+// }
+
+// Where the game is: L4D2_DIR, or Steam's library
+pub fn find_game_dir() -> Result<PathBuf, EnforcerError> {
+	let err1 = "Failed to find Steam installation location".to_string();
+	let err2 = "Failed to locate Left 4 Dead 2 directory".to_string();
+	
+	if let Some(path) = var_os("L4D2_DIR") {
+		let path: PathBuf = path.into();
+		if path.is_dir() {
+			Ok(path)
+		} else {
+			Err(EnforcerError::GameNotFound(format!("L4D2_DIR ({}) is not a directory", path.display())))
+		}
+	} else {
+		let (app, library) = SteamDir::locate()
+			.map_err(|_| EnforcerError::GameNotFound(err1))?
+			.find_app(550)
+			.map_err(|_| EnforcerError::GameNotFound(err2.clone()))?
+			.ok_or(EnforcerError::GameNotFound(err2))?;
+		Ok(library.resolve_app_dir(&app))
+	}
+}
+
+pub fn gameinfo_path(game_dir: &Path, verbose: bool) -> Result<PathBuf, EnforcerError> {
+	let gameinfo_path = game_dir.join("left4dead2/gameinfo.txt");
+
+	if !gameinfo_path.exists() {
+		return Err(EnforcerError::GameinfoMissing(gameinfo_path));
+	}
+
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Gameinfo.txt path: {:?}", gameinfo_path);
+	}
+	Ok(gameinfo_path)
+}
+
+pub fn gameinfo_backup_path(game_dir: &Path, verbose: bool) -> Result<PathBuf, EnforcerError> {
+	//let backup_path = PathBuf::new();
+	if let Some(path) = var_os("BACKUP_PATH") {
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("env BACKUP_PATH={:?}", path.to_string_lossy());
+		}
+		let path: PathBuf = path.into();
+		if path.is_file() {
+			Ok(path)
+		} else {
+			let err = format!("BACKUP_PATH ({}) is not a file", path.to_string_lossy());
+			Err(EnforcerError::Other(err))
+		}
+	} else if let Some(name) = var_os("BACKUP_NAME") {
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("env BACKUP_NAME={:?}", name.to_string_lossy());
+		}
+		let name = name.to_string_lossy().to_string();
+		let place = format!("left4dead2/{}", name);
+		let place_invalid = "left4dead2/".to_string();
+		if place_invalid == place {
+			let err = format!("{} <- the backup place[from name!] does not contain any file name!", place);
+			Err(EnforcerError::Other(err))
+		} else {
+			let backup_name = game_dir.join(place);
+			if backup_name.exists() {
+				Ok(backup_name)
+			} else {
+				let err = format!("{} — the backup file does not exist!", backup_name.to_string_lossy());
+				Err(EnforcerError::Other(err))
+			}
+		}
+	} else {
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("No custom backup path is provided. Using default 'gameinfo.txt.orig'");
+		}
+		let backup_def_path = game_dir.join("left4dead2/gameinfo.txt.orig");
+		Ok(backup_def_path)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const GAMEINFO: &str = "\"GameInfo\"\n{\n\tgame\t\"Left 4 Dead 2\"\n\tFileSystem\n\t{\n\t\tSteamAppId\t550\n\t\tSearchPaths\n\t\t{\n\t\t\tGame\tion_hud\n\t\t\tGame\tupdate\n\t\t\tGame\tleft4dead2_dlc3\n\t\t\tGame\tleft4dead2_dlc2\n\t\t\tGame\tleft4dead2_dlc1\n\t\t\tGame\t|gameinfo_path|.\n\t\t\tGame\thl2\n\t\t}\n\t}\n}\n";
+
+	#[test]
+	fn reset_is_planned_before_it_is_written() {
+		let game = tempfile::tempdir().unwrap();
+		let gameinfo_path = game.path().join("left4dead2/gameinfo.txt");
+		std::fs::create_dir_all(gameinfo_path.parent().unwrap()).unwrap();
+		std::fs::write(&gameinfo_path, GAMEINFO).unwrap();
+
+		// Without a backup, the addon is removed from the current gameinfo.txt
+		let plan = plan_reset(game.path(), false).unwrap();
+		assert!(plan.rebuilt);
+		assert_eq!(plan.current, GAMEINFO);
+		assert!(!plan.reset.contains("ion_hud"));
+		assert!(!gameinfo_path.with_extension("txt.orig").exists());
+
+		let steps = gameinfo_reset(game.path(), &plan, true, false).unwrap();
+		assert!(steps.iter().any(|step| step.diff.as_ref().is_some_and(|(_, _, new)| *new == plan.reset)));
+		assert_eq!(read_to_string(&gameinfo_path).unwrap(), GAMEINFO);
+
+		// A plan made for other contents is refused
+		std::fs::write(&gameinfo_path, GAMEINFO.replace("ion_hud", "tank_skin")).unwrap();
+		assert!(gameinfo_reset(game.path(), &plan, false, false).is_err());
+		std::fs::write(&gameinfo_path, GAMEINFO).unwrap();
+
+		assert!(gameinfo_reset(game.path(), &plan, false, false).unwrap().is_empty());
+		assert_eq!(read_to_string(&gameinfo_path).unwrap(), plan.reset);
+	}
+}
@@ -1,71 +1,69 @@
-use std::{env::join_paths, path};
-
 // SPDX-License-Identifier: LGPL-3.0-only
 use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::gameinfo::Gameinfo;
+use crate::vpk_getdata::AddonInfo;
+use serde::Serialize;
 
-pub fn list_addons(
-	quiet: bool,
-	verbose: bool,
-	details: bool,
-	buf_writer: &mut impl Write,
-) -> Result<(), Box<dyn std::error::Error>> {
-	// Locate the gameinfo.txt file
-	let gameinfo_path = gameinfo_path(verbose)?;
-
-	// Read the gameinfo.txt file
-	let contents = read_to_string(&gameinfo_path)?;
-
-	// Split the file into lines
-	let lines: Vec<&str> = contents.lines().collect();
+// An addon listed in gameinfo.txt, enabled or not
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledAddon {
+	pub name: String,
+	// The addon directory, holding pak01_dir.vpk
+	pub path: PathBuf,
+	pub enabled: bool,
+	// Position in the SearchPaths, 1 being the highest priority. Disabled addons keep their place.
+	pub load_order: usize,
+	// Whether pak01_dir.vpk is there at all
+	pub vpk_present: bool,
+	// The addoninfo.txt fields, only read when details are asked for
+	pub info: Option<AddonInfo>,
+	// Why the addoninfo.txt could not be read
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub info_error: Option<String>,
+	// From the addon manifest, when details are asked for
+	pub installed_at: Option<String>,
+	pub updated_at: Option<String>,
+	pub source: Option<String>,
+}
 
-	let excl_addons = vec![
-		"update",
-		"left4dead2_dlc3",
-		"left4dead2_dlc2",
-		"left4dead2_dlc1",
-		"hl2",
-		"|gameinfo_path|.",
-	];
-
-	// List the installed custom addons
-	let mut SearchPaths = false;
-	if lines.iter().any(|line| line.contains("SearchPaths")) {
-		if !quiet {
-			println!("{}", "Installed addons:".bold());
-		}
-		let mut l4d2_dir: PathBuf = path::PathBuf::new();
-		if details {
-			l4d2_dir = l4d2_path()?;
-		}
-		for line in lines.iter() {
-			if line.contains("SearchPaths") {
-				SearchPaths = true;
-			} else if SearchPaths && line.contains("}") {
-				SearchPaths = false;
+// Every addon of gameinfo.txt in load order. With `details`, each addon's addoninfo.txt
+// and manifest entry are read too.
+pub fn installed_addons(game_dir: &Path, details: bool, verbose: bool) -> Result<Vec<InstalledAddon>, EnforcerError> {
+	let gameinfo = Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?;
+	// A damaged manifest only costs the installation details, `doctor` and `repair` report it
+	let manifest_path = Manifest::path(game_dir);
+	let manifest = if details && manifest_path.exists() { Manifest::read(&manifest_path).unwrap_or_default() } else { Manifest::default() };
+	let mut addons = Vec::new();
+	for (position, (name, enabled)) in gameinfo.addon_states().into_iter().enumerate() {
+		let path = game_dir.join(&name);
+		let vpk = path.join("pak01_dir.vpk");
+		let vpk_present = vpk.is_file();
+		let mut addon = InstalledAddon {
+			name,
+			path,
+			enabled,
+			load_order: position + 1,
+			vpk_present,
+			info: None,
+			info_error: None,
+			installed_at: None,
+			updated_at: None,
+			source: None,
+		};
+		if details && vpk_present {
+			// A broken addoninfo.txt should not hide the rest of the list
+			match vpk_getdata::main(&vpk.to_string_lossy().to_string(), verbose) {
+				Ok(info) => addon.info = Some(info),
+				Err(e) => addon.info_error = Some(e.to_string()),
 			}
-			if SearchPaths
-				&& line.contains("Game")
-				&& !excl_addons.iter().any(|&s| line.contains(s))
-			{
-				let addon = line
-					.trim_start_matches('\t')
-					.trim_start_matches("Game")
-					.replace("\t\t\t\t", "\t");
-				if details {
-					let addon_file = l4d2_dir.join(&addon.trim_start_matches('\t')).join("pak01_dir.vpk");
-					if verbose {println!("{} path: {}", &addon.green(), addon_file.to_string_lossy().to_string().purple());}
-					if addon_file.exists() {
-						let datapack = vpk_getdata::main(&addon_file.to_string_lossy().to_string(), verbose)?;
-						writeln!(buf_writer, "{} (title: {}, version: {}, description: {})", addon, datapack.title, datapack.version, datapack.description).unwrap();
-					} else {
-						writeln!(buf_writer, "{}", addon).unwrap();
-					}
-				} else {
-				writeln!(buf_writer, "{}", addon).unwrap();
-				}
+			if let Some(entry) = manifest.entry(&addon.name) {
+				addon.installed_at = entry.installed_at.clone();
+				addon.updated_at = entry.updated_at.clone();
+				addon.source = entry.source.clone();
 			}
 		}
-		return Ok(());
+		addons.push(addon);
 	}
-	Ok(())
-}
\ No newline at end of file
+	Ok(addons)
+}
@@ -2,60 +2,48 @@
 
 #![allow(non_snake_case)]
 #![windows_subsystem = "windows"]
-mod core_imports;
-use crate::{core_args::SubCommands, core_imports::*};
+use crate::core_args::SubCommands;
+pub use l4addonenforcer::*;
+use l4addonenforcer::core_imports::*;
+use colored::Colorize;
 mod core_args;
+mod core_output;
 
 mod gui;
 mod gui_theming;
-mod install_addon;
-mod rename_addon;
-mod list_addons;
-mod uninstall_addon;
-mod pug_mode;
-// mod gameinfo_reset;
-mod vpk_getdata;
 
-
-
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
 	let args = core_args::Args::parse();
+	core_output::init_logging();
+	if let Err(err) = run(&args) {
+		exit(core_output::report(&*err));
+	}
+}
+
+// Runs the subcommand, errors being reported by `main`
+fn run(args: &core_args::Args) -> Result<(), Box<dyn std::error::Error>> {
 	match &args.command {
 		Some(SubCommands::Install(install_args)) | Some(SubCommands::I(install_args)) => {
 			// Install or update logic
-			match (&install_args.file, &install_args.name) {
-				(Some(addon_file), None) => {
-					let _ = install_addon::autoinstall_addon(&addon_file, args.verbose);
+			let files: Vec<String> = install_args.file.iter().chain(&install_args.files).cloned().collect();
+			match (files.is_empty(), &install_args.name) {
+				(false, name) => {
+					let summary = install_addon::install_addons(&find_game_dir()?, &files, name.as_deref(), install_args.force, args.dry_run, args.verbose)?;
+					core_output::print_install_summary(summary)?;
 				},
-				(Some(addon_file), Some(name)) => {
-					let _ = install_addon::install_addon(&addon_file, &name, args.verbose);
-				},
-				(None, Some(..)) => {
-					let err = format!(
+				(true, Some(..)) => {
+					let err = String::from(
 						"Argument -n <name> has been passed but no -f <file> have been passed!
 											\nNo addon.vpk file provided for installation."
 					);
-					eprintln!("{} {}", "Error:".red(), err);
-					println!(
-						"Type {} / {} for more information",
-						"-h".blue(),
-						"--help".blue()
-					);
-					return Err(Box::new(QuietErr(Some(err))));
+					return Err(Box::new(EnforcerError::Usage(err)));
 				},
-				(None, None) => {
-					let err = format!(
+				(true, None) => {
+					let err = String::from(
 						"Arguments -n <name> and -f <file> have not been passed!
 											\nNo addon.vpk file provided for installation."
 					);
-					eprintln!("{} {}", "Error:".red(), err);
-					println!(
-						"Type {} / {} for more information",
-						"-h".blue(),
-						"--help".blue()
-					);
-					return Err(Box::new(QuietErr(Some(err))));
+					return Err(Box::new(EnforcerError::Usage(err)));
 				},
 			}
 		}
@@ -63,106 +51,286 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 			// Uninstall logic
 			match &uninstall_args.name {
 				Some(name) => {
-					let _ = uninstall_addon::uninstall_addon(&name, args.verbose);
+					let uninstalled = uninstall_addon::uninstall(&find_game_dir()?, name, args.dry_run, args.verbose)?;
+					if uninstalled.removed_dir.is_some() {
+						println!("Uninstalled {} successfully.", uninstalled.name.italic());
+					}
+					core_output::print_plan(&uninstalled.plan);
 				},
 				None => {
-					let err = format!(
+					let err = String::from(
 						"Argument -n <name> has not been passed!
 											\nNo addon name provided for uninstalling."
 					);
-					eprintln!("{} {}", "Error:".red(), err);
-					println!(
-						"Type {} / {} for more information",
-						"-h".blue(),
-						"--help".blue()
-					);
-					return Err(Box::new(QuietErr(Some(err))));
+					return Err(Box::new(EnforcerError::Usage(err)));
 				},
 			}
 		}
 		Some(SubCommands::List(list_args)) | Some(SubCommands::L(list_args)) => {
 			// List addons
-			let _ = list_addons::list_addons(list_args.quiet, args.verbose, list_args.details, &mut std::io::stdout()); // Unused result becase of QuietErr usage?
+			// Scripts get everything there is to know
+			let details = list_args.details || list_args.format != structured_output::OutputFormat::Text;
+			let addons = list_addons::installed_addons(&find_game_dir()?, details, args.verbose)?;
+			core_output::print_addons(&addons, list_args.quiet, args.verbose, list_args.details, list_args.format, &mut std::io::stdout())?;
 		}
 		Some(SubCommands::Rename(rename_args)) | Some(SubCommands::R(rename_args)) => {
 			// Rename logic
 			match (&rename_args.current, &rename_args.new) {
 				(Some(current), Some(new)) => {
-					let _ = rename_addon::rename_addon(&current, &new, args.verbose);
+					let renamed = rename_addon::rename(&find_game_dir()?, current, new, args.dry_run, args.verbose)?;
+					println!("Renamed {} to {} successfully.", renamed.from.italic(), renamed.to.italic());
+					core_output::print_plan(&renamed.plan);
 				},
 				(Some(..), None) => {
-					let err = format!(
+					let err = String::from(
 						"Argument --current (-c) <current_name> has been passed but no --new (-n) <new_name> have been passed!
 											\nNo new addon name provided for renaming."
 					);
-					eprintln!("{} {}", "Error:".red(), err);
-					println!(
-						"Type {} / {} for more information",
-						"-h".blue(),
-						"--help".blue()
-					);
-					return Err(Box::new(QuietErr(Some(err))));
+					return Err(Box::new(EnforcerError::Usage(err)));
 				},
 				(None, Some(..)) => {
-					let err = format!(
+					let err = String::from(
 						"Argument --current (-c) <current_name> has not been passed!
 											\nNo currently installed addon provided for renaming."
 					);
-					eprintln!("{} {}", "Error:".red(), err);
-					println!(
-						"Type {} / {} for more information",
-						"-h".blue(),
-						"--help".blue()
-					);
-					return Err(Box::new(QuietErr(Some(err))));
+					return Err(Box::new(EnforcerError::Usage(err)));
 				},
 				(None, None) => {
-					let err = format!(
+					let err = String::from(
 						"Arguments --current (-c) <current_name> and --new (-n) <new_name> have not been passed!
 											\nNo currently installed addon provided for renaming."
 					);
-					eprintln!("{} {}", "Error:".red(), err);
-					println!(
-						"Type {} / {} for more information",
-						"-h".blue(),
-						"--help".blue()
-					);
-					return Err(Box::new(QuietErr(Some(err))));
+					return Err(Box::new(EnforcerError::Usage(err)));
 				},
 			}
 		}
 		Some(SubCommands::Reset(reset_args)) | Some(SubCommands::Rs(reset_args)) => {
-			// Reset logic
-			if reset_args.confirm {
-				let _ = gameinfo_reset(args.verbose); // Unused result becase of QuietErr usage?
+			// Reset logic: the changes are shown first, and only written with --CONFIRM.
+			// A dry run changes nothing and needs no confirmation, its plan shows the changes.
+			let game_dir = find_game_dir()?;
+			let plan = plan_reset(&game_dir, args.verbose)?;
+			if reset_args.confirm || args.dry_run {
+				if !args.dry_run {
+					core_output::print_reset_plan(&plan);
+				}
+				core_output::print_plan(&gameinfo_reset(&game_dir, &plan, args.dry_run, args.verbose)?);
+				println!("Succesfully reset gameinfo.txt to default.");
 			}
 			else {
-				let err = format!(
+				core_output::print_reset_plan(&plan);
+				let err = String::from(
 					"Argument --CONFIRM has not been passed!
-										\nNo confirmation provided for reset."
+										\nNo confirmation provided for reset, pass it to write the changes above."
 				);
-				eprintln!("{} {}", "Error:".red(), err);
-				return Err(Box::new(QuietErr(Some(err))));
+				return Err(Box::new(EnforcerError::Usage(err)));
 			}
 			
 		},
 		Some(SubCommands::PuG(pug_args)) | Some(SubCommands::P(pug_args)) => {
 			if pug_args.check {
-				let _ = pug_mode::PuG_mode_check(args.verbose);
+				let status = pug_mode::pug_status(&find_game_dir()?, args.verbose)?;
+				core_output::print_pug_status(&status, pug_args.format)?;
 			} else if pug_args.switch {
-				let _ = pug_mode::PuG_mode_switch(args.verbose);
+				let game_dir = find_game_dir()?;
+				// A rebuilt gameinfo.txt is shown before it is written, the plan of a dry run shows it already
+				if !args.dry_run && let Some((contents, rebuilt)) = pug_mode::pug_rebuild(&game_dir, args.verbose)? {
+					core_output::print_rebuilt(&contents, &rebuilt);
+				}
+				let switch = pug_mode::pug_switch(&game_dir, args.dry_run, args.verbose)?;
+				core_output::print_pug_switch(&switch);
+				core_output::print_plan(&switch.plan);
 			} else {
-				let err = format!(
+				let err = String::from(
 					"No PuG mode action specified!
 										\nUse --check (-c) to check PuG mode status or --switch (-s) to toggle it."
 				);
-				eprintln!("{} {}", "Error:".red(), err);
-				println!(
-					"Type {} / {} for more information",
-					"-h".blue(),
-					"--help".blue()
-				);
-				return Err(Box::new(QuietErr(Some(err))));
+				return Err(Box::new(EnforcerError::Usage(err)));
+			}
+		}
+		Some(SubCommands::Pack(pack_args)) => {
+			match &pack_args.dir {
+				Some(dir) => {
+					let packed = pack_addon::pack_addon(dir, pack_args.output.as_deref(), pack_args.v2, args.dry_run, args.verbose)?;
+					if !args.dry_run {
+						println!("Packed {} files from {} into {}", packed.files, dir, packed.vpk.display().to_string().italic());
+					}
+					core_output::print_plan(&packed.plan);
+				},
+				None => {
+					let err = String::from(
+						"No addon directory provided for packing!"
+					);
+					return Err(Box::new(EnforcerError::Usage(err)));
+				},
+			}
+		}
+		Some(SubCommands::Inspect(inspect_args)) => {
+			let game_dir = game_dir_for(&inspect_args.targets)?;
+			let inspected = inspect_addon::inspect_addons(game_dir.as_deref(), &inspect_args.targets, args.verbose)?;
+			core_output::print_inspected(inspected, inspect_args.format)?;
+		}
+		Some(SubCommands::Verify(verify_args)) => {
+			let game_dir = game_dir_for(&verify_args.targets)?;
+			let verifications = verify_vpk::verify_addons(game_dir.as_deref(), &verify_args.targets, args.verbose)?;
+			core_output::print_verifications(verifications, verify_args.format)?;
+		}
+		Some(SubCommands::Extract(extract_args)) => {
+			match &extract_args.target {
+				Some(target) => {
+					let game_dir = game_dir_for(std::slice::from_ref(target))?;
+					let extraction = extract_addon::extract_addon(
+						game_dir.as_deref(),
+						target,
+						&extract_args.patterns,
+						extract_args.output.as_deref(),
+						extract_args.list,
+						args.dry_run,
+						args.verbose,
+					)?;
+					core_output::print_extraction(&extraction);
+				},
+				None => {
+					let err = "No addon name or VPK file provided to extract from!".to_string();
+					return Err(Box::new(EnforcerError::Usage(err)));
+				},
+			}
+		}
+		Some(SubCommands::Conflicts(conflicts_args)) => {
+			let report = find_conflicts::find_conflicts(&find_game_dir()?, args.verbose)?;
+			core_output::print_conflicts(&report, conflicts_args.format)?;
+		}
+		Some(SubCommands::Order(order_args)) => {
+			let directions = [
+				(order_args.up, order_addons::Direction::Up),
+				(order_args.down, order_addons::Direction::Down),
+				(order_args.top, order_addons::Direction::Top),
+				(order_args.bottom, order_addons::Direction::Bottom),
+			];
+			let directions: Vec<order_addons::Direction> =
+				directions.into_iter().filter(|(set, _)| *set).map(|(_, direction)| direction).collect();
+			let game_dir = find_game_dir()?;
+			match (&order_args.name, directions.as_slice(), &order_args.file) {
+				(None, [], None) => core_output::print_load_order(&order_addons::load_order(&game_dir, args.verbose)?),
+				(None, [], Some(file)) => {
+					let applied = order_addons::apply_order_file(&game_dir, file, args.dry_run, args.verbose)?;
+					core_output::print_order_applied(&applied, file);
+					core_output::print_plan(&applied.plan);
+				},
+				(Some(name), [direction], None) => {
+					let moved = order_addons::move_addon(&game_dir, name, *direction, args.dry_run, args.verbose)?;
+					if moved.from == moved.to {
+						println!("{} is already at position {} of {}.", name.italic(), moved.from + 1, moved.count);
+					} else {
+						println!("Moved {} to position {} of {}.", name.italic(), moved.to + 1, moved.count);
+					}
+					core_output::print_plan(&moved.plan);
+				},
+				(Some(..), [], None) => {
+					let err = String::from(
+						"No direction specified for moving the addon!
+											\nUse --up (-u), --down (-d), --top (-t) or --bottom (-b)."
+					);
+					return Err(Box::new(EnforcerError::Usage(err)));
+				},
+				_ => {
+					let err = String::from(
+						"Either move one addon in a single direction, or apply an order with --file (-f)!"
+					);
+					return Err(Box::new(EnforcerError::Usage(err)));
+				},
+			}
+		}
+		Some(SubCommands::Enable(toggle_args)) | Some(SubCommands::Disable(toggle_args)) => {
+			let enable = matches!(&args.command, Some(SubCommands::Enable(..)));
+			let toggled = toggle_addon::set_addons_enabled(&find_game_dir()?, &toggle_args.names, enable, args.dry_run, args.verbose)?;
+			for name in &toggled.already {
+				println!("{} is already {}.", name.italic(), if enable { "enabled" } else { "disabled" });
+			}
+			for name in &toggled.changed {
+				println!("{} {} successfully.", if enable { "Enabled" } else { "Disabled" }, name.italic());
+			}
+			core_output::print_plan(&toggled.plan);
+		}
+		Some(SubCommands::Profile(profile_args)) => {
+			use core_args::ProfileAction;
+			let game_dir = find_game_dir()?;
+			match &profile_args.action {
+				ProfileAction::List => core_output::print_profiles(&manage_profiles::profiles(&game_dir, args.verbose)?),
+				ProfileAction::Show { name } => core_output::print_profile(name, &manage_profiles::read_profile(&game_dir, name, args.verbose)?),
+				ProfileAction::Create { name, file } => {
+					let saved = manage_profiles::create_profile(&game_dir, name, file.as_deref(), args.dry_run, args.verbose)?;
+					println!(
+						"{} profile {} with {} addons.",
+						if saved.updated { "Updated" } else { "Saved" },
+						name.italic(),
+						saved.addons.len()
+					);
+					core_output::print_plan(&saved.plan);
+				},
+				ProfileAction::Delete { name } => {
+					let plan = manage_profiles::delete_profile(&game_dir, name, args.dry_run, args.verbose)?;
+					println!("Deleted profile {}.", name.italic());
+					core_output::print_plan(&plan);
+				},
+				ProfileAction::Diff { name, other } => {
+					let diff = manage_profiles::diff_profile(&game_dir, name, other.as_deref(), args.verbose)?;
+					core_output::print_profile_diff(&diff, name, other.as_deref());
+				},
+				// Without an output file, the profile is printed
+				ProfileAction::Export { name, output: Some(output) } => {
+					let plan = manage_profiles::export_profile(&game_dir, name, Path::new(output), args.dry_run, args.verbose)?;
+					println!("Exported profile {} to {}.", name.italic(), output);
+					core_output::print_plan(&plan);
+				},
+				ProfileAction::Export { name, output: None } => {
+					for addon in manage_profiles::read_profile(&game_dir, name, args.verbose)? {
+						println!("{}", addon);
+					}
+				},
+				ProfileAction::Activate { name } => {
+					let activated = manage_profiles::activate_profile(&game_dir, name, args.dry_run, args.verbose)?;
+					core_output::print_profile_activated(&activated.diff, name);
+					core_output::print_plan(&activated.plan);
+				},
+			}
+		}
+		Some(SubCommands::Repair(..)) => {
+			let report = repair_manifest::repair_manifest(&find_game_dir()?, args.dry_run, args.verbose)?;
+			core_output::print_repair(&report);
+			core_output::print_plan(&report.plan);
+		}
+		Some(SubCommands::Doctor(doctor_args)) => {
+			// Problems left unfixed make the exit status fail, for scripts
+			// A missing game is one of the problems reported
+			let game_dir = find_game_dir().ok();
+			let report = check_health::check_health(game_dir.as_deref(), doctor_args.fix, args.dry_run, args.verbose)?;
+			core_output::print_health(&report, doctor_args.format)?;
+			// JSON holds the plan itself
+			if doctor_args.format == structured_output::OutputFormat::Text {
+				core_output::print_plan(&report.plan);
+			}
+			let left = report.problems_left();
+			if left > 0 {
+				let err = format!("{} problem(s) left unfixed.", left);
+				return Err(Box::new(EnforcerError::Other(err)));
+			}
+		}
+		Some(SubCommands::Backups(backups_args)) => {
+			use core_args::BackupsAction;
+			let game_dir = find_game_dir()?;
+			match &backups_args.action {
+				BackupsAction::List { format } => {
+					core_output::print_snapshots(&gameinfo_history::list_snapshots(&game_dir, args.verbose)?, *format)?;
+				},
+				BackupsAction::Diff { id } => {
+					let (snapshot, current, contents) = gameinfo_history::snapshot_diff(&game_dir, id, args.verbose)?;
+					core_output::print_snapshot_diff(&snapshot, &current, &contents);
+				},
+				BackupsAction::Restore { id } => {
+					let restore = gameinfo_history::restore_snapshot(&game_dir, id, args.dry_run, args.verbose)?;
+					core_output::print_snapshot_restore(&restore);
+					core_output::print_plan(&restore.plan);
+				},
 			}
 		}
 		None => {
@@ -177,240 +345,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 			}
 		},
 	}
-
-
-	Ok(())
-}
-
-fn gameinfo_reset(verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
-	let gameinfo_orig_md5 = format!("586b3b0b39bc44ddfb07792b1932c479");
-	// Locate the gameinfo.txt file
-	let gameinfo_path = gameinfo_path(verbose)?;
-
-	// Calculate the MD5 of the gameinfo.txt file
-	let gameinfo_md5 = calculate_md5(&gameinfo_path).expect("Failed to calculate MD5");
-
-	let gameinfo_backup_path = gameinfo_backup_path(verbose)?;
-	if var_os("DEBUG").is_some() || verbose {
-		println!(
-			"{} {} {:?}",
-			"[D]".blue(),
-			"gameinfo_backup_path:".bold(),
-			gameinfo_backup_path
-		);
-	}
-	if !gameinfo_backup_path.exists() {
-		if gameinfo_md5 != gameinfo_orig_md5 {
-			println!(
-				"{} gameinfo.txt file seems to be modified, but no backup is present!",
-				"Warning:".yellow()
-			);
-			println!("");
-			println!("If you haven't already modified the gameinfo.txt, this is probably");
-			println!(
-				"\ta {}'s bug you can report to the dev!",
-				env!("CARGO_PKG_NAME")
-			);
-			println!("\t\tYour gameinfo.txt MD5 hash is: {}", gameinfo_md5.bold());
-		} else {
-			let err = format!("gameinfo.txt is already at its default state!");
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
-		}
-	} else {
-		if gameinfo_md5 != gameinfo_orig_md5 {
-			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Copying gameinfo.txt backup ({:?}) to {:?}",
-					"[D]".blue(),
-					&gameinfo_backup_path.file_name().unwrap().to_string_lossy(),
-					&gameinfo_path.file_name().unwrap().to_string_lossy()
-				);
-			}
-			copy(&gameinfo_backup_path, &gameinfo_path)?;
-			println!("Succesfully reset gameinfo.txt to default.");
-		} else {
-			let err = format!("gameinfo.txt is already at its default state!");
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
-		}
-	}
 	Ok(())
 }
 
-#[derive(Debug)]
-pub struct QuietErr(Option<String>);
-impl fmt::Display for QuietErr {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		if let Some(ref msg) = self.0 {
-			write!(f, "{}", msg)
-		} else {
-			write!(f, "")
-		}
-	}
-}
-impl Error for QuietErr {}
-
-fn calculate_md5(filepath: &Path) -> Result<String, std::io::Error> {
-	let mut file = BufReader::new(File::open(filepath)?); // Buffered for performance
-	let mut hasher = Md5::new();
-	let mut buffer = [0; 4096]; // Read in chunks for efficiency
-	loop {
-		let count = file.read(&mut buffer)?;
-		if count == 0 {
-			break;
-		}
-		hasher.update(&buffer[..count]);
-	}
-	let result = hasher.finalize();
-	Ok(format!("{:x}", result)) // Format as hexadecimal string
-}
-
-// fn clean_path(path: &str) -> Cow<str> {
-//     if path.contains("/../") {
-//         let path_buf = PathBuf::from(format!("/{path}"));
-//         let Ok(absolute_path) = path_buf.parse_dot_from("/") else {
-//             return path.into();
-//         };
-//         let path = absolute_path.to_str().unwrap().trim_start_matches('/');
-//         String::from(path).into()
-//     } else {
-//         path.into()
-//     }
-// }
-
-// #[test]
-// fn test_clean_path() {
-//     assert_eq!("foo/bar", clean_path("foo/bar"));
-//     assert_eq!("foo/bar", clean_path("foo/asd/../bar"));
-//     assert_eq!("../bar", clean_path("../bar"));
-// }
-
-fn l4d2_path() -> Result<PathBuf, Box<dyn Error>> {
-	let err = "Failed to find L4D2 install location".to_string();
-	let err1 = "Failed to find Steam installation location".to_string();
-	let err2 = "Failed to locate Left 4 Dead 2 directory".to_string();
-	
-	if let Some(path) = var_os("L4D2_DIR") {
-		let path: PathBuf = path.into();
-		if path.is_dir() {
-			Ok(path)
-		} else {
-			eprintln!("{} {}: {}", "Error:".red(), err, err1);
-			let e = format!("\n{}: \n{}", err, err1);
-			return Err(Box::new(QuietErr(Some(e))));
-		}
-	} else {
-		let (app, library) = SteamDir::locate()
-			.map_err(|_| {
-				let e = format!("{}: {}", err, err1);
-				eprintln!("{} {}", "Error:".red(), e);
-				Box::new(QuietErr(Some(e))) as Box<dyn Error>
-			})?
-			.find_app(550)
-			.map_err(|_| {
-				let e = format!("{}: {}", err, err2);
-				eprintln!("{} {}", "Error:".red(), e);
-				Box::new(QuietErr(Some(e))) as Box<dyn Error>
-			})?
-			.ok_or_else(|| {
-				eprintln!("{} {}", "Error:".red(), err);
-				Box::new(QuietErr(Some(err))) as Box<dyn Error>
-			})?;
-		Ok(library.resolve_app_dir(&app))
-	}
-}
-
-fn gameinfo_path(verbose: bool) -> Result<PathBuf, Box<dyn std::error::Error>> {
-	// Locate the Left 4 Dead 2 directory
-	if var_os("DEBUG").is_some() || verbose {
-		println!("{} Locating L4D2 directory...", "[D]".blue());
-	}
-	let l4d2_dir = l4d2_path()?;
-	let gameinfo_path = l4d2_dir.join("left4dead2/gameinfo.txt");
-
-	if !gameinfo_path.exists() {
-		let err = format!("Unable to locate gameinfo.txt file. Is the game installation broken?");
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
-	}
-
-	if var_os("DEBUG").is_some() || verbose {
-		println!(
-			"{} {} {:?}",
-			"[D]".blue(),
-			"Gameinfo.txt path:".bold(),
-			gameinfo_path
-		);
-	}
-	Ok(gameinfo_path)
-}
-
-fn gameinfo_backup_path(verbose: bool) -> Result<PathBuf, Box<dyn std::error::Error>> {
-	//let backup_path = PathBuf::new();
-	let l4d2_dir = l4d2_path()?;
-	if let Some(path) = var_os("BACKUP_PATH") {
-		if var_os("DEBUG").is_some() || verbose {
-			println!(
-				"{} {}={:?}",
-				"[D]".blue(),
-				"env BACKUP_PATH".bold(),
-				path.to_string_lossy()
-			);
-		}
-		let path: PathBuf = path.into();
-		if path.is_file() {
-			Ok(path)
-		} else {
-			let err = "is not a file".to_string();
-			eprintln!("{} {} {}", "Error:".red(), path.to_string_lossy(), err);
-			let e = format!("\n{} \n{}", path.to_string_lossy(), err);
-			return Err(Box::new(QuietErr(Some(e))));
-		}
-	} else if let Some(name) = var_os("BACKUP_NAME") {
-		if var_os("DEBUG").is_some() || verbose {
-			println!(
-				"{} {}={:?}",
-				"[D]".blue(),
-				"env BACKUP_NAME".bold(),
-				name.to_string_lossy()
-			);
-		}
-		let name = name.to_string_lossy().to_string();
-		let place = format!("left4dead2/{}", name);
-		let place_invalid = format!("left4dead2/");
-		if place_invalid == place {
-			let err = "the backup place[from name!] does not contain any file name!".to_string();
-			eprintln!("{} {}  <- {}", "Error:".red(), place, err);
-			let e = format!("\n{} \n^-- {} --^", place, err);
-			return Err(Box::new(QuietErr(Some(e))));
-		} else {
-			let backup_name = l4d2_dir.join(place);
-			if backup_name.exists() {
-				Ok(backup_name)
-			} else {
-				let err = "the backup file does not exist!".to_string();
-				eprintln!(
-					"{} {} — {}",
-					"Error:".red(),
-					backup_name.to_string_lossy(),
-					err
-				);
-				let e = format!("\n{} \n^-- {} --^", backup_name.to_string_lossy(), err);
-				Err(Box::new(QuietErr(Some(e))))
-			}
-		}
-	} else {
-		if var_os("DEBUG").is_some() || verbose {
-			println!(
-				"{} {}",
-				"[D]".blue(),
-				"No custom backup path is provided. Using default 'gameinfo.txt.orig'".bold(),
-			);
-		}
-		let backup_def_path = l4d2_dir.join("left4dead2/gameinfo.txt.orig");
-		Ok(backup_def_path)
-	}
+// The game directory, unless every target is a VPK file (which can be read without the game)
+fn game_dir_for(targets: &[String]) -> Result<Option<PathBuf>, EnforcerError> {
+	if inspect_addon::targets_need_game(targets) { Ok(Some(find_game_dir()?)) } else { Ok(None) }
 }
 
 const HELP: Help = Help(sections!(
@@ -432,6 +372,11 @@ const HELP: Help = Help(sections!(
 				["Enable verbose output"]
 				Long ["showing more details about the operations being performed."]
 			}
+			"--dry-run" => {
+				["Show what a command would change, without changing it"]
+				Long ["Every step (mkdir, copy, write, rename, remove_dir_all) is listed instead of \n"
+					  "performed, followed by a diff of gameinfo.txt and the other text files changed."]
+			}
 			"-V, --version" => {
 				["Print version information"]
 			}
@@ -446,41 +391,130 @@ const HELP: Help = Help(sections!(
 			}
 			"list, l" => {
 				["List installed addons"]
-				Long ["Lists all currently installed addons."]
+				Long ["Lists all currently installed addons, disabled ones included."]
 			}
 			"uninstall, u" => {
 				["Uninstall an addon"]
 				Long ["Uninstalls a specified addon by name."]
 			}
+			"enable / disable" => {
+				["Enable or disable addons"]
+				Long ["Disabling comments out the addon's Game line in gameinfo.txt, so the game \n"
+					  "stops loading it while its files stay installed. Enabling restores the line."]
+			}
+			"profile" => {
+				["Manage addon profiles"]
+				Long ["A profile is a named, ordered set of enabled addons (e.g. \"versus-clean\"). \n"
+					  "Activating one enables its addons in its order and disables every other \n"
+					  "addon, in a single write of gameinfo.txt."]
+			}
 			"rename, r" => {
 				["Rename an addon"]
 				Long ["Renames an installed addon. Requires both the current and the new name."]
 			}
 			"pug, p" => {
 				["Manage PuG mode"]
-				Long ["Enables, disables, or checks the status of PuG mode."]
+				Long ["Enables, disables, or checks the status of PuG mode. Enabling it without a \n"
+					  "backup rebuilds a vanilla gameinfo.txt, like " m:"reset" " does."]
 			}
 			"reset, rs" => {
 				["Reset gameinfo.txt"]
-				Long ["Resets the gameinfo.txt file to its original state using a backup."]
+				Long ["Resets the gameinfo.txt file to its original state using a backup. Without a \n"
+					  "backup, a vanilla gameinfo.txt is rebuilt by removing every addon from the \n"
+					  "current one. The changes are shown first, and only written with " C:"--CONFIRM" "."]
+			}
+			"inspect" => {
+				["Show what addons contain"]
+				Long ["Shows file counts and sizes per directory of installed addons (all of them \n"
+					  "by default) or VPK files, and what they change: HUD, Survivor skin, \n"
+					  "Infected skin, Weapon, Sound, Map or Script."]
+			}
+			"verify" => {
+				["Check addons for corruption"]
+				Long ["Reads every file of installed addons (all of them by default) or VPK files and \n"
+					  "checks it against its CRC32, and for version 2 VPKs the MD5 checksums of the \n"
+					  "chunks, the directory tree and the file. " m:"install" " runs the same checks."]
+			}
+			"extract" => {
+				["List or extract the files of an addon"]
+				Long ["Lists or extracts files of an installed addon or a VPK file into a directory, \n"
+					  "all of them or the ones matching paths or glob patterns (e.g. " m:"materials/vgui/**" "). \n"
+					  "Files kept in chunks (" m:"pak01_NNN.vpk" ") are read from there."]
+			}
+			"conflicts" => {
+				["Show files overridden by more than one addon"]
+				Long ["Lists every file provided by more than one installed addon, and which \n"
+					  "addon wins under the current SearchPaths order of gameinfo.txt."]
+			}
+			"order" => {
+				["Show or change the load order of addons"]
+				Long ["Without arguments, prints the load order (highest priority first). \n"
+					  "Moves an addon up, down, to the top or to the bottom, or applies a whole \n"
+					  "order from a file, by reordering the Game lines of gameinfo.txt."]
+			}
+			"repair" => {
+				["Reconcile gameinfo.txt with the addon manifest"]
+				Long ["Every command keeps a manifest of the installed addons (" m:"addon_manifest.json" " \n"
+					  "next to gameinfo.txt). Repair puts back the gameinfo.txt entries of addons \n"
+					  "whose files are still there (e.g. after Steam verified the game files), \n"
+					  "forgets addons whose files are gone and records addons it did not know."]
+			}
+			"doctor" => {
+				["Check the installation for problems"]
+				Long ["Checks that the game and gameinfo.txt are found, that every addon has a readable \n"
+					  "pak01_dir.vpk, that no addon directory is left unreferenced, that the backup is \n"
+					  "the vanilla gameinfo.txt and that the PuG mode state is consistent. The report \n"
+					  "starts with what is worth attaching to a bug report."]
+			}
+			"backups" => {
+				["List, compare and restore saved versions of gameinfo.txt"]
+				Long ["Every command changing gameinfo.txt first saves a snapshot of it in \n"
+					  m:"gameinfo_history/" " next to gameinfo.txt. The newest " m:"HISTORY_LIMIT" " (20 by \n"
+					  "default) snapshots are kept."]
+			}
+			"pack" => {
+				["Pack an addon folder into a VPK"]
+				Long ["Packs a loose addon folder (addoninfo.txt, materials/, models/, scripts/...) \n"
+					  "into a single VPK file, without the need for Valve's vpk.exe."]
 			}
 		}
 	}
 	"INSTALL SUBCOMMAND ARGS" {
 		table Auto {
-			"-f, --file <FILE_PATH>" => {
-				["Path to the VPK addon file"]
+			"-f, --file <FILE_PATH>..." => {
+				["Path(s) to the VPK addon file(s)"]
+				Long ["Accepts several files, directories (all *.vpk inside), glob patterns \n"
+					  "and .zip / .tar.gz archives (every *.vpk inside, other files are skipped). \n"
+					  "For addons split into chunks, pass the *_dir.vpk file (chunks must be next to it). \n"
+					  "A loose addon folder (holding addoninfo.txt, materials/, models/...) is packed \n"
+					  "into a VPK on the fly and installed. \n"
+					  "gameinfo.txt is rewritten once, and a summary of every addon is printed."]
 			}
 			"-n, --name <NAME>" => {
 				["Specify the addon name manually"]
-				Long ["If not provided, the addon name will be extracted from the VPK."]
+				Long ["If not provided, the addon name will be extracted from the VPK. \n"
+					  "Only allowed when installing a single addon."]
+			}
+			"--force" => {
+				["Install addons even if they are corrupt"]
+				Long ["Every addon is verified (see " m:"verify" ") before being installed, and a \n"
+					  "corrupt one (e.g. a half-downloaded file) is refused unless this is set."]
 			}
 		}
 	}
 	"LIST SUBCOMMAND ARGS" {
 		table Auto {
 			"-d, --details" => {
-				["List details for each addon (title, version, description"]
+				["List details for each addon (from its addoninfo.txt)"]
+				Long ["Title, version, tagline, author, URLs, content kinds, description \n"
+					  "and any other field the addon's addoninfo.txt sets, then when and from \n"
+					  "where the addon was installed."]
+			}
+			"--format <text|json|tsv>" => {
+				["Print the list for scripts"]
+				Long ["json prints an array of addons with their name, path, enabled state, load \n"
+					  "order, addoninfo.txt fields and installation details. tsv prints one line per \n"
+					  "addon after a header line. Both always include the details."]
 			}
 		}
 	}
@@ -491,6 +525,42 @@ const HELP: Help = Help(sections!(
 			}
 		}
 	}
+	"ENABLE / DISABLE SUBCOMMAND ARGS" {
+		table Auto {
+			"<NAME>..." => {
+				["Names of the addons to enable or disable"]
+			}
+		}
+	}
+	"PROFILE SUBCOMMAND ARGS" {
+		table Auto {
+			"list" => {
+				["List saved profiles, marking the active one"]
+			}
+			"show <NAME>" => {
+				["Print the addons of a profile"]
+			}
+			"create <NAME> [-f <FILE_PATH>]" => {
+				["Save the currently enabled addons as a profile"]
+				Long ["With " c:"-f" ", the profile is made from a file listing one addon per line, \n"
+					  "highest priority first (the format " m:"order -f" " takes). \n"
+					  "An existing profile of the same name is overwritten."]
+			}
+			"delete <NAME>" => {
+				["Delete a profile"]
+			}
+			"diff <NAME> [<OTHER>]" => {
+				["Show what activating a profile would change"]
+				Long ["Or, with " m:"<OTHER>" ", what changes going from profile " m:"<OTHER>" " to " m:"<NAME>" "."]
+			}
+			"export <NAME> [-o <FILE_PATH>]" => {
+				["Write a profile to a file (or print it)"]
+			}
+			"activate <NAME>" => {
+				["Enable the profile's addons in its order, disable the others"]
+			}
+		}
+	}
 	"RENAME SUBCOMMAND ARGS" {
 		table Auto {
 			"-c, --current <CURRENT_NAME>" => {
@@ -509,18 +579,165 @@ const HELP: Help = Help(sections!(
 			"-s, --switch" => {
 				["Toggle PuG mode (enable/disable)"]
 			}
+			"--format <text|json|tsv>" => {
+				["Print the status of " m:"--check" " for scripts"]
+			}
 		}
 	}
 	"RESET SUBCOMMAND ARGS" {
 		table Auto {
-			"--CONFIRM" => { ["Confirm the reset operation"] }
+			"--CONFIRM" => { ["Confirm the reset operation, without it the changes are only shown"] }
+		}
+	}
+	"INSPECT SUBCOMMAND ARGS" {
+		table Auto {
+			"<NAME_OR_FILE>..." => {
+				["Installed addon names or VPK files to inspect (all installed addons if none)"]
+			}
+			"--format <text|json|tsv>" => {
+				["Print the inventories for scripts"]
+				Long ["tsv prints one line per directory, the totals being the " m:"(total)" " directory."]
+			}
+		}
+	}
+	"VERIFY SUBCOMMAND ARGS" {
+		table Auto {
+			"<NAME_OR_FILE>..." => {
+				["Installed addon names or VPK files to verify (all installed addons if none)"]
+			}
+			"--format <text|json|tsv>" => {
+				["Print the results for scripts"]
+				Long ["json prints an array of targets with their verification, or the error that \n"
+					  "failed them. tsv prints one line per target after a header line."]
+			}
+		}
+	}
+	"EXTRACT SUBCOMMAND ARGS" {
+		table Auto {
+			"<NAME_OR_FILE>" => {
+				["Installed addon name or VPK file to read"]
+			}
+			"<PATTERN>..." => {
+				["Files to list or extract (all of them if none)"]
+				Long ["A path (" m:"addoninfo.jpg" "), a directory (" m:"materials/vgui" ") or a glob pattern, \n"
+					  "where " m:"*" " stays within a directory and " m:"**" " goes through any number \n"
+					  "of them (" m:"**/*.vtf" "). Quote patterns so the shell leaves them alone. \n"
+					  "Paths are matched case-insensitively, like the game does."]
+			}
+			"-o, --output <DIRECTORY>" => {
+				["Where to extract the files"]
+				Long ["Defaults to a directory named after the addon (or the VPK file) in the \n"
+					  "current one. Files keep their path inside the VPK, and existing ones are replaced."]
+			}
+			"-l, --list" => {
+				["Only list the files, with their sizes"]
+			}
+		}
+	}
+	"CONFLICTS SUBCOMMAND ARGS" {
+		table Auto {
+			"--format <text|json|tsv>" => {
+				["Print the conflicts for scripts"]
+				Long ["json prints the conflicting files with the addons providing them, highest \n"
+					  "priority first. tsv prints one line per file: the addon that wins, then the \n"
+					  "ones it overrides."]
+			}
+		}
+	}
+	"ORDER SUBCOMMAND ARGS" {
+		table Auto {
+			"<NAME>" => {
+				["The addon to move"]
+			}
+			"-u, --up" => {
+				["Move the addon one position up (higher priority)"]
+			}
+			"-d, --down" => {
+				["Move the addon one position down (lower priority)"]
+			}
+			"-t, --top" => {
+				["Move the addon to the top (highest priority)"]
+			}
+			"-b, --bottom" => {
+				["Move the addon to the bottom (lowest priority)"]
+			}
+			"-f, --file <FILE_PATH>" => {
+				["Apply the load order listed in a file"]
+				Long ["One addon name per line, highest priority first. Blank lines and lines \n"
+					  "starting with " m:"//" " or " m:"#" " are ignored. Installed addons missing \n"
+					  "from the file are kept below the listed ones."]
+			}
+		}
+	}
+	"DOCTOR SUBCOMMAND ARGS" {
+		table Auto {
+			"-f, --fix" => {
+				["Fix the problems found, where possible"]
+				Long ["Broken or missing addons lose their gameinfo.txt entry, unreferenced addon \n"
+					  "directories are listed as disabled addons, a missing backup is made from a \n"
+					  "vanilla gameinfo.txt and the addon manifest is brought up to date."]
+			}
+			"--format <text|json|tsv>" => {
+				["Print the report for scripts"]
+				Long ["json prints the whole report. tsv prints one line per problem with its fix, \n"
+					  "then one per fix of the addon manifest, which has no problem."]
+			}
+		}
+	}
+	"BACKUPS SUBCOMMAND ARGS" {
+		table Auto {
+			"list [--format <text|json|tsv>]" => {
+				["List the snapshots, newest first"]
+				Long ["With " c:"--format" ", for scripts: json prints an array of snapshots, tsv one \n"
+					  "line per snapshot after a header line."]
+			}
+			"diff <ID>" => {
+				["Show what restoring a snapshot would change in gameinfo.txt"]
+				Long [m:"<ID>" " is the snapshot's position in " m:"backups list" " (1 being the newest), \n"
+					  "its id, or the start of its id."]
+			}
+			"restore <ID>" => {
+				["Put a snapshot back in place of gameinfo.txt"]
+				Long ["The current gameinfo.txt is saved as a snapshot first, so a restore can be undone."]
+			}
+		}
+	}
+	"PACK SUBCOMMAND ARGS" {
+		table Auto {
+			"<DIRECTORY>" => {
+				["The addon folder to pack"]
+			}
+			"-o, --output <FILE_PATH>" => {
+				["Where to write the VPK"]
+				Long ["Defaults to " m:"<DIRECTORY>.vpk" " next to the addon folder."]
+			}
+			"--v2" => {
+				["Write a VPK version 2 file"]
+				Long ["Left 4 Dead 2 uses version 1, which is the default."]
+			}
 		}
 	}
 	[]
 	"EXAMPLE" {
 		["\t" g:{env!("CARGO_PKG_NAME")} " " c:"i" " " c:"/home/user/Downloads/ion_vocalizer.vpk"]
 		[g:{env!("CARGO_PKG_NAME")} " " c:"i" " " C:"-f" " " c:"/home/user/Downloads/ion_vocalizer.vpk" " " C:"-n" " " c:"vocalizer"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"i" " " c:"/home/user/Downloads/addons/" " " c:"\"/home/user/Downloads/*.vpk\""]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"i" " " c:"/home/user/Downloads/ion_vocalizer.zip"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"inspect" " " c:"vocalizer"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"verify" " " c:"/home/user/Downloads/ion_vocalizer.vpk"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"extract" " " c:"ion_hud" " " c:"\"materials/vgui/**\"" " " C:"-o" " " c:"/home/user/ion_hud/"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"conflicts"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"order" " " c:"vocalizer" " " C:"--top"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"order" " " C:"-f" " " c:"/home/user/load_order.txt"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"pack" " " c:"/home/user/addons/my_addon/" " " C:"-o" " " c:"/home/user/my_addon.vpk"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"disable" " " c:"vocalizer" " " c:"ion_hud"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"profile" " " c:"create" " " c:"versus-clean"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"profile" " " c:"activate" " " c:"campaign-full"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"repair"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"doctor" " " C:"--fix"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"backups" " " c:"restore" " " c:"2"]
 		[g:{env!("CARGO_PKG_NAME")} " " c:"u" " " C:"-n" " " c:"vocalizer"]
+		[g:{env!("CARGO_PKG_NAME")} " " c:"u" " " C:"-n" " " c:"vocalizer" " " C:"--dry-run"]
 		[g:{env!("CARGO_PKG_NAME")} " " c:"r" " " C:"-c" " " c:"vocalizer" " " C:"-n" " " c:"ion_vocalizer"]
 	}
 	"ENVIRONMENT VARIABLES" {
@@ -538,11 +755,32 @@ const HELP: Help = Help(sections!(
 			"If this is not set, the default name will be used: " m:"gameinfo.txt.orig"
 		]
 		[]
+		[c:"HISTORY_LIMIT\n\t"
+			"How many gameinfo.txt snapshots to keep (default: 20, 0 disables them)"
+		]
+		[]
 		[c:"DEBUG\n\t"
 			"Enables verbose output\n\t"
 			"(Equivalent to the \"-v\" option)"
 		]
 	}
+	"EXIT CODES" {
+		table Auto {
+			"0" => { ["Success"] }
+			"1" => { ["Any other failure, or " m:"doctor" " left problems unfixed"] }
+			"2" => { ["Missing or conflicting arguments"] }
+			"3" => { ["The game could not be found"] }
+			"4" => { ["gameinfo.txt could not be found"] }
+			"5" => { ["gameinfo.txt is modified and has no backup, while a custom copy exists"] }
+			"6" => { ["No such addon"] }
+			"7" => { ["The addon is disabled"] }
+			"8" => { ["An addon with that name is already installed"] }
+			"9" => { ["Invalid addon name"] }
+			"10" => { ["Core game components cannot be installed, renamed, uninstalled, enabled or disabled"] }
+			"11" => { ["The VPK file could not be read"] }
+			"12" => { ["The VPK file is corrupt"] }
+		}
+	}
 ));
 
 fn print_short_help(use_colors: bool) {
@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::Gameinfo;
+use crate::order_addons::read_order_file;
+use crate::transaction::{PlannedStep, Transaction};
+
+// Profiles are kept next to gameinfo.txt, one file per profile, in the same format
+// as the files taken by `order -f`: the enabled addons, one per line, highest priority first.
+const PROFILES_DIR: &str = "addon_profiles";
+const PROFILE_EXTENSION: &str = "txt";
+
+// What activating a profile changes, compared to the current addons (or another profile)
+#[derive(Debug, Clone, Default)]
+pub struct ProfileDiff {
+	pub enabled: Vec<String>,
+	pub disabled: Vec<String>,
+	// The new load order, only when it differs for the addons both sides enable
+	pub order: Option<Vec<String>>,
+}
+
+impl ProfileDiff {
+	pub fn new(from: &[String], to: &[String]) -> ProfileDiff {
+		let kept_from: Vec<&String> = from.iter().filter(|addon| to.contains(addon)).collect();
+		let kept_to: Vec<&String> = to.iter().filter(|addon| from.contains(addon)).collect();
+		ProfileDiff {
+			enabled: to.iter().filter(|addon| !from.contains(addon)).cloned().collect(),
+			disabled: from.iter().filter(|addon| !to.contains(addon)).cloned().collect(),
+			order: if kept_from != kept_to { Some(to.to_vec()) } else { None },
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.enabled.is_empty() && self.disabled.is_empty() && self.order.is_none()
+	}
+
+	// Plain text, so that the GUI can show it too
+	pub fn describe(&self) -> String {
+		if self.is_empty() {
+			return "No differences.".to_string();
+		}
+		let mut lines = Vec::new();
+		for addon in &self.enabled {
+			lines.push(format!("+ {}", addon));
+		}
+		for addon in &self.disabled {
+			lines.push(format!("- {}", addon));
+		}
+		if let Some(order) = &self.order {
+			lines.push("Load order:".to_string());
+			let width = order.len().to_string().len();
+			for (position, addon) in order.iter().enumerate() {
+				lines.push(format!("\t{:>width$}. {}", position + 1, addon));
+			}
+		}
+		lines.join("\n")
+	}
+}
+
+fn profiles_dir(game_dir: &Path, verbose: bool) -> Result<PathBuf, EnforcerError> {
+	Ok(gameinfo_path(game_dir, verbose)?.with_file_name(PROFILES_DIR))
+}
+
+fn profile_path(game_dir: &Path, name: &str, verbose: bool) -> Result<PathBuf, EnforcerError> {
+	// Profile names end up in file names and GUI menu entries
+	let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+		&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+	if !valid {
+		let err = format!(
+			"Invalid profile name \"{}\"! \n\tOnly letters, digits, '-', '_' and '.' are allowed, starting with a letter or digit.",
+			name
+		);
+		return Err(EnforcerError::Other(err));
+	}
+	Ok(profiles_dir(game_dir, verbose)?.join(format!("{}.{}", name, PROFILE_EXTENSION)))
+}
+
+fn existing_profile_path(game_dir: &Path, name: &str, verbose: bool) -> Result<PathBuf, EnforcerError> {
+	let path = profile_path(game_dir, name, verbose)?;
+	if !path.is_file() {
+		let err = format!("There is no profile named {}!", name);
+		return Err(EnforcerError::Other(err));
+	}
+	Ok(path)
+}
+
+// Names of all saved profiles, sorted
+pub fn list_profiles(game_dir: &Path, verbose: bool) -> Result<Vec<String>, EnforcerError> {
+	let dir = profiles_dir(game_dir, verbose)?;
+	if !dir.is_dir() {
+		return Ok(Vec::new());
+	}
+	let mut names: Vec<String> = std::fs::read_dir(&dir)?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == PROFILE_EXTENSION))
+		.filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+		.collect();
+	names.sort();
+	Ok(names)
+}
+
+// The enabled addons of a profile, highest priority first
+pub fn read_profile(game_dir: &Path, name: &str, verbose: bool) -> Result<Vec<String>, EnforcerError> {
+	read_order_file(&existing_profile_path(game_dir, name, verbose)?)
+}
+
+// A saved profile, as listed by `profile list`
+#[derive(Debug, Clone)]
+pub struct Profile {
+	pub name: String,
+	pub addons: Vec<String>,
+	// Whether the profile's addons are the ones enabled, in its order
+	pub active: bool,
+}
+
+// Every readable profile, sorted by name
+pub fn profiles(game_dir: &Path, verbose: bool) -> Result<Vec<Profile>, EnforcerError> {
+	let names = list_profiles(game_dir, verbose)?;
+	if names.is_empty() {
+		return Ok(Vec::new());
+	}
+	let current = Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?.addons();
+	Ok(names
+		.into_iter()
+		.filter_map(|name| {
+			let addons = read_profile(game_dir, &name, verbose).ok()?;
+			Some(Profile { active: addons == current, name, addons })
+		})
+		.collect())
+}
+
+// What saving a profile did
+#[derive(Debug, Clone)]
+pub struct ProfileSaved {
+	// Whether a profile of that name was replaced
+	pub updated: bool,
+	pub addons: Vec<String>,
+	// The steps a dry run would have performed
+	pub plan: Vec<PlannedStep>,
+}
+
+// What activating a profile did
+#[derive(Debug, Clone)]
+pub struct ProfileActivated {
+	// Empty when the profile was already active
+	pub diff: ProfileDiff,
+	// The steps a dry run would have performed
+	pub plan: Vec<PlannedStep>,
+}
+
+// Saves a profile: the currently enabled addons, or the list in `from_file`
+pub fn create_profile(
+	game_dir: &Path,
+	name: &str,
+	from_file: Option<&str>,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<ProfileSaved, EnforcerError> {
+	let path = profile_path(game_dir, name, verbose)?;
+	let addons = match from_file {
+		Some(file) => read_order_file(Path::new(file))?,
+		None => Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?.addons(),
+	};
+	let updated = path.exists();
+	let plan = write_profile(&path, name, &addons, dry_run, verbose)?;
+	Ok(ProfileSaved { updated, addons, plan })
+}
+
+fn write_profile(path: &Path, name: &str, addons: &[String], dry_run: bool, verbose: bool) -> Result<Vec<PlannedStep>, EnforcerError> {
+	let mut contents = format!("// {} profile: {}\n", env!("CARGO_PKG_NAME"), name);
+	for addon in addons {
+		contents.push_str(addon);
+		contents.push('\n');
+	}
+	let mut tx = Transaction::new(dry_run, verbose);
+	if let Some(dir) = path.parent().filter(|dir| !dir.is_dir()) {
+		tx.create_dir(dir)?;
+	}
+	tx.write(path, contents)?;
+	Ok(tx.commit()?)
+}
+
+// Returns the plan of a dry run
+pub fn delete_profile(game_dir: &Path, name: &str, dry_run: bool, verbose: bool) -> Result<Vec<PlannedStep>, EnforcerError> {
+	let path = existing_profile_path(game_dir, name, verbose)?;
+	let mut tx = Transaction::new(dry_run, verbose);
+	tx.remove(&path)?;
+	Ok(tx.commit()?)
+}
+
+// Writes the profile to `output`, in the format `profile create --file` reads.
+// Returns the plan of a dry run.
+pub fn export_profile(
+	game_dir: &Path,
+	name: &str,
+	output: &Path,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<Vec<PlannedStep>, EnforcerError> {
+	let addons = read_profile(game_dir, name, verbose)?;
+	write_profile(output, name, &addons, dry_run, verbose)
+}
+
+// Compares a profile with the current addons, or with `other` when given
+pub fn diff_profile(game_dir: &Path, name: &str, other: Option<&str>, verbose: bool) -> Result<ProfileDiff, EnforcerError> {
+	let to = read_profile(game_dir, name, verbose)?;
+	let from = match other {
+		Some(other) => read_profile(game_dir, other, verbose)?,
+		None => Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?.addons(),
+	};
+	Ok(ProfileDiff::new(&from, &to))
+}
+
+// Enables the profile's addons in its order and disables all others, in a single write of gameinfo.txt
+pub fn activate_profile(game_dir: &Path, name: &str, dry_run: bool, verbose: bool) -> Result<ProfileActivated, EnforcerError> {
+	let addons = read_profile(game_dir, name, verbose)?;
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	// The vanilla gameinfo.txt is in place while PuG mode is enabled
+	if gameinfo_path.with_extension("txt.custom").exists() {
+		let err = "PuG mode is enabled! Disable it before activating a profile.".to_string();
+		return Err(EnforcerError::Other(err));
+	}
+	let mut gameinfo = Gameinfo::read(&gameinfo_path)?;
+	let installed: Vec<String> = gameinfo.addon_states().into_iter().map(|(addon, _)| addon).collect();
+	let missing: Vec<&String> = addons.iter().filter(|addon| !installed.contains(addon)).collect();
+	if !missing.is_empty() {
+		let err = format!(
+			"Profile {} enables addons that are not installed: {}",
+			name,
+			missing.iter().map(|addon| addon.as_str()).collect::<Vec<_>>().join(", ")
+		);
+		return Err(EnforcerError::Other(err));
+	}
+
+	let diff = ProfileDiff::new(&gameinfo.addons(), &addons);
+	// Already active
+	if diff.is_empty() {
+		return Ok(ProfileActivated { diff, plan: Vec::new() });
+	}
+	gameinfo.set_enabled_addons(&addons)?;
+	let mut manifest = Manifest::load(game_dir, verbose)?;
+	manifest.sync(&gameinfo);
+	let mut tx = Transaction::new(dry_run, verbose);
+	crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "profile", verbose)?;
+	tx.write(&gameinfo_path, gameinfo.doc.to_string())?;
+	manifest.save(game_dir, &mut tx)?;
+	let plan = tx.commit()?;
+	Ok(ProfileActivated { diff, plan })
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::Gameinfo;
+use crate::transaction::{PlannedStep, Transaction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+	Up,
+	Down,
+	Top,
+	Bottom,
+}
+
+// Where an addon was moved in the load order, positions being 0-based
+#[derive(Debug, Clone)]
+pub struct AddonMove {
+	pub from: usize,
+	// The same as `from` when the addon already was at the top or bottom
+	pub to: usize,
+	// How many addons the load order has
+	pub count: usize,
+	// What a dry run would have done
+	pub plan: Vec<PlannedStep>,
+}
+
+// Moves an addon within the load order. Addons higher in the order take priority.
+pub fn move_addon(game_dir: &Path, name: &str, direction: Direction, dry_run: bool, verbose: bool) -> Result<AddonMove, EnforcerError> {
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	let mut gameinfo = Gameinfo::read(&gameinfo_path)?;
+	let mut order = gameinfo.addons();
+	let Some(position) = order.iter().position(|addon| addon == name) else {
+		return Err(gameinfo.missing_addon(name));
+	};
+
+	let new_position = match direction {
+		Direction::Up => position.saturating_sub(1),
+		Direction::Down => (position + 1).min(order.len() - 1),
+		Direction::Top => 0,
+		Direction::Bottom => order.len() - 1,
+	};
+	let mut moved = AddonMove { from: position, to: new_position, count: order.len(), plan: Vec::new() };
+	if new_position == position {
+		return Ok(moved);
+	}
+	let addon = order.remove(position);
+	order.insert(new_position, addon);
+
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Moving {} from position {} to {}", name, position + 1, new_position + 1);
+	}
+	moved.plan = write_order(game_dir, &gameinfo_path, &mut gameinfo, &order, dry_run, verbose)?;
+	Ok(moved)
+}
+
+// What applying an order file did
+#[derive(Debug, Clone)]
+pub struct OrderApplied {
+	// The new load order, highest priority first
+	pub order: Vec<String>,
+	// Installed addons the file does not list, kept at the bottom
+	pub unlisted: Vec<String>,
+	// False when the load order already was the one listed
+	pub changed: bool,
+	// What a dry run would have done
+	pub plan: Vec<PlannedStep>,
+}
+
+// Applies the load order listed in a text file (see `read_order_file`).
+// Installed addons missing from the file keep their relative order, below the listed ones.
+pub fn apply_order_file(game_dir: &Path, file: &str, dry_run: bool, verbose: bool) -> Result<OrderApplied, EnforcerError> {
+	let mut order = read_order_file(Path::new(file))?;
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	let mut gameinfo = Gameinfo::read(&gameinfo_path)?;
+	let installed = gameinfo.addons();
+	if let Some(addon) = order.iter().find(|addon| !installed.contains(addon)) {
+		return Err(gameinfo.missing_addon(addon));
+	}
+
+	let unlisted: Vec<String> = installed.iter().filter(|addon| !order.contains(addon)).cloned().collect();
+	order.extend(unlisted.iter().cloned());
+	let changed = order != installed;
+	let plan = if changed {
+		write_order(game_dir, &gameinfo_path, &mut gameinfo, &order, dry_run, verbose)?
+	} else {
+		Vec::new()
+	};
+	Ok(OrderApplied { order, unlisted, changed, plan })
+}
+
+// Reads a list of addon names: one per line, highest priority first.
+// Blank lines and lines starting with `//` or `#` are ignored.
+pub fn read_order_file(file: &Path) -> Result<Vec<String>, EnforcerError> {
+	let contents = read_to_string(file).map_err(|e| {
+		let err = format!("Failed to read {}: {}", file.display(), e);
+		EnforcerError::Other(err)
+	})?;
+	let mut order: Vec<String> = Vec::new();
+	for line in contents.lines().map(str::trim) {
+		if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+			continue;
+		}
+		if order.iter().any(|addon| addon == line) {
+			let err = format!("{} is listed more than once in {}", line, file.display());
+			return Err(EnforcerError::Other(err));
+		}
+		order.push(line.to_string());
+	}
+	Ok(order)
+}
+
+// The current load order of the enabled addons, highest priority first
+pub fn load_order(game_dir: &Path, verbose: bool) -> Result<Vec<String>, EnforcerError> {
+	Ok(Gameinfo::read(&gameinfo_path(game_dir, verbose)?)?.addons())
+}
+
+fn write_order(
+	game_dir: &Path,
+	gameinfo_path: &Path,
+	gameinfo: &mut Gameinfo,
+	order: &[String],
+	dry_run: bool,
+	verbose: bool,
+) -> Result<Vec<PlannedStep>, EnforcerError> {
+	gameinfo.reorder_addons(order)?;
+	let mut manifest = Manifest::load(game_dir, verbose)?;
+	manifest.sync(gameinfo);
+	let mut tx = Transaction::new(dry_run, verbose);
+	crate::gameinfo_history::save_snapshot(game_dir, &mut tx, gameinfo_path, "order", verbose)?;
+	tx.write(gameinfo_path, gameinfo.doc.to_string())?;
+	manifest.save(game_dir, &mut tx)?;
+	Ok(tx.commit()?)
+}
@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::transaction::{PlannedStep, Transaction};
+use crate::vpk_getdata::{EMBEDDED_ARCHIVE, HEADER_SIZE_V1, HEADER_SIZE_V2};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use sourcepak::common::file::VPKFileWriter;
+use sourcepak::common::format::{VPK_ENTRY_TERMINATOR, VPKDirectoryEntry, VPKTree};
+use sourcepak::pak::v1::format::{VPK_SIGNATURE_V1, VPK_VERSION_V1, VPKHeaderV1};
+use sourcepak::pak::v2::format::{VPK_SIGNATURE_V2, VPK_VERSION_V2};
+use std::io::{Seek, SeekFrom};
+
+// Tree, archive MD5 section and whole file checksums
+const OTHER_MD5_SECTION_SIZE: u32 = 48;
+
+// Top-level folders of a loose (unpacked) addon, next to its addoninfo.txt
+const CONTENT_DIRS: [&str; 8] =
+	["materials", "models", "scripts", "sound", "maps", "particles", "resource", "missions"];
+
+// A directory holding an addon's loose files rather than *.vpk files
+pub fn is_loose_addon(dir: &Path) -> bool {
+	dir.is_dir()
+		&& (dir.join("addoninfo.txt").is_file()
+			|| CONTENT_DIRS.iter().any(|content| dir.join(content).is_dir()))
+}
+
+// What packing an addon did
+#[derive(Debug, Clone)]
+pub struct Packed {
+	pub vpk: PathBuf,
+	pub files: usize,
+	// The steps a dry run would have performed
+	pub plan: Vec<PlannedStep>,
+}
+
+// Packs a loose addon folder into `output` (by default `<dir>.vpk` next to the folder).
+// The VPK is built in a temporary directory, then written like any other change.
+pub fn pack_addon(
+	dir: &str,
+	output: Option<&str>,
+	v2: bool,
+	dry_run: bool,
+	verbose: bool,
+) -> Result<Packed, EnforcerError> {
+	let src_dir = Path::new(dir);
+	let output = match output {
+		Some(output) => PathBuf::from(output),
+		None => PathBuf::from(format!("{}.vpk", dir.trim_end_matches(['/', '\\']))),
+	};
+	let temp = tempfile::Builder::new().prefix("l4ae-").tempdir()?;
+	let scratch = temp.path().join("pack.vpk");
+	let count = build_vpk(src_dir, &output, &scratch, v2, verbose)?;
+	let mut tx = Transaction::new(dry_run, verbose);
+	tx.write(&output, std::fs::read(&scratch)?)?;
+	let plan = tx.commit()?;
+	Ok(Packed { vpk: output, files: count, plan })
+}
+
+// Writes every file under `src_dir` into a single-file VPK (v1, or v2 when `v2` is set),
+// with all file data embedded after the directory tree. Returns the number of packed files.
+pub fn write_vpk(
+	src_dir: &Path,
+	output: &Path,
+	v2: bool,
+	verbose: bool,
+) -> Result<usize, EnforcerError> {
+	build_vpk(src_dir, output, output, v2, verbose)
+}
+
+// Like `write_vpk`, but writes the VPK meant for `output` to `scratch`
+fn build_vpk(
+	src_dir: &Path,
+	output: &Path,
+	scratch: &Path,
+	v2: bool,
+	verbose: bool,
+) -> Result<usize, EnforcerError> {
+	if !src_dir.is_dir() {
+		let err = format!("{} is not a directory!", src_dir.display());
+		return Err(EnforcerError::Other(err));
+	}
+	let mut files = Vec::new();
+	// The output may be inside the packed directory when overwriting an older build
+	collect_loose_files(src_dir, output.canonicalize().ok().as_deref(), &mut files)?;
+	files.sort();
+	if files.is_empty() {
+		let err = format!("There are no files to pack in {}", src_dir.display());
+		return Err(EnforcerError::Other(err));
+	}
+
+	// Directory entries need the CRC, size and position of every file up front
+	let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+	let mut tree: VPKTree<VPKDirectoryEntry> = VPKTree::new();
+	let mut offset: u64 = 0;
+	for file in &files {
+		let key = vpk_key(src_dir, file)?;
+		let mut digest = crc.digest();
+		let mut reader = BufReader::new(File::open(file)?);
+		let mut buffer = [0; 65536];
+		let mut length: u64 = 0;
+		loop {
+			let count = reader.read(&mut buffer)?;
+			if count == 0 {
+				break;
+			}
+			digest.update(&buffer[..count]);
+			length += count as u64;
+		}
+		// Checked against the total size below
+		let (entry_offset, entry_length) = (offset as u32, length as u32);
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Packing {:?} ({} bytes)", key, length);
+		}
+		let entry = VPKDirectoryEntry {
+			crc: digest.finalize(),
+			preload_length: 0,
+			archive_index: EMBEDDED_ARCHIVE,
+			entry_offset,
+			entry_length,
+			terminator: VPK_ENTRY_TERMINATOR,
+		};
+		if tree.files.insert(key.clone(), entry).is_some() {
+			let err = format!("More than one file is packed as {} (file names only differing in case?)", key.trim());
+			return Err(EnforcerError::Other(err));
+		}
+		offset += length;
+	}
+	if u32::try_from(offset).is_err() {
+		let err = "The addon is too big to be packed into a single VPK file (4 GiB max).".to_string();
+		return Err(EnforcerError::Other(err));
+	}
+
+	let result = write_vpk_file(scratch, &tree, &files, offset, v2);
+	if result.is_err() {
+		let _ = remove_file(scratch);
+	}
+	result?;
+	Ok(files.len())
+}
+
+fn write_vpk_file(
+	output: &Path,
+	tree: &VPKTree<VPKDirectoryEntry>,
+	files: &[PathBuf],
+	data_size: u64,
+	v2: bool,
+) -> Result<(), EnforcerError> {
+	let header_size = if v2 { HEADER_SIZE_V2 } else { HEADER_SIZE_V1 };
+	let mut out = File::create(output)?;
+
+	// The tree goes first, so its size is known when the header is written
+	out.seek(SeekFrom::Start(header_size))?;
+	tree.write(&mut out).map_err(EnforcerError::Other)?;
+	// sourcepak does not terminate the list of extensions
+	out.write_u8(0)?;
+	let tree_size = out.stream_position()? - header_size;
+	for file in files {
+		std::io::copy(&mut File::open(file)?, &mut out)?;
+	}
+
+	out.seek(SeekFrom::Start(0))?;
+	if v2 {
+		out.write_u32(VPK_SIGNATURE_V2)?;
+		out.write_u32(VPK_VERSION_V2)?;
+		out.write_u32(tree_size as u32)?;
+		out.write_u32(data_size as u32)?;
+		out.write_u32(0)?; // No archive MD5 entries, everything is embedded
+		out.write_u32(OTHER_MD5_SECTION_SIZE)?;
+		out.write_u32(0)?; // Unsigned
+	} else {
+		let header = VPKHeaderV1 { signature: VPK_SIGNATURE_V1, version: VPK_VERSION_V1, tree_size: tree_size as u32 };
+		header.write(&mut out).map_err(EnforcerError::Other)?;
+	}
+	out.flush()?;
+
+	if v2 {
+		let tree_md5 = md5_of_range(output, header_size, tree_size)?;
+		let archive_md5 = Md5::digest([]).to_vec();
+		let mut whole_file = Md5::new();
+		let mut reader = BufReader::new(File::open(output)?);
+		std::io::copy(&mut reader, &mut Md5Writer(&mut whole_file))?;
+		whole_file.update(&tree_md5);
+		whole_file.update(&archive_md5);
+
+		out.seek(SeekFrom::End(0))?;
+		out.write_all(&tree_md5)?;
+		out.write_all(&archive_md5)?;
+		out.write_all(&whole_file.finalize())?;
+	}
+	out.sync_all()?;
+	Ok(())
+}
+
+pub fn md5_of_range(path: &Path, start: u64, length: u64) -> Result<Vec<u8>, std::io::Error> {
+	let mut file = File::open(path)?;
+	file.seek(SeekFrom::Start(start))?;
+	let mut hasher = Md5::new();
+	std::io::copy(&mut file.take(length), &mut Md5Writer(&mut hasher))?;
+	Ok(hasher.finalize().to_vec())
+}
+
+// Lets std::io::copy stream into an MD5 hasher
+struct Md5Writer<'a>(&'a mut Md5);
+impl Write for Md5Writer<'_> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.update(buf);
+		Ok(buf.len())
+	}
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+// Every file below `dir`, leaving out hidden files/directories (.git etc.) and `skip`
+fn collect_loose_files(dir: &Path, skip: Option<&Path>, files: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+	for entry in std::fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')) {
+			continue;
+		}
+		if path.is_dir() {
+			collect_loose_files(&path, skip, files)?;
+		} else if path.is_file() && path.canonicalize().ok().as_deref() != skip {
+			files.push(path);
+		}
+	}
+	Ok(())
+}
+
+// The directory tree key of a file, as built by sourcepak: "{path}/{file_name}.{extension}".
+// Files at the root and files without an extension use a single space instead.
+// The game looks files up in VPKs by their lowercase path.
+fn vpk_key(src_dir: &Path, file: &Path) -> Result<String, EnforcerError> {
+	let relative = file.strip_prefix(src_dir).map_err(|e| EnforcerError::Other(e.to_string()))?;
+	let mut parts = Vec::new();
+	for component in relative.components() {
+		let Some(part) = component.as_os_str().to_str() else {
+			let err = format!("{} has a file name that is not valid UTF-8", file.display());
+			return Err(EnforcerError::Other(err));
+		};
+		parts.push(part.to_lowercase());
+	}
+	let file_name = parts.pop().unwrap_or_default();
+	let path = if parts.is_empty() { " ".to_string() } else { parts.join("/") };
+	let (stem, extension) = match file_name.rsplit_once('.') {
+		Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => (stem, extension),
+		_ => (file_name.as_str(), " "),
+	};
+	Ok(format!("{}/{}.{}", path, stem, extension))
+}
@@ -1,72 +1,113 @@
 // SPDX-License-Identifier: LGPL-3.0-only
 use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::transaction::{PlannedStep, Transaction};
+use crate::vanilla_gameinfo::check_vanilla;
+use serde::Serialize;
 
-pub fn PuG_mode_switch(verbose: bool) -> Result<i32, Box<dyn std::error::Error>> {
-	let gameinfo_orig_md5 = format!("586b3b0b39bc44ddfb07792b1932c479");
+// What switching PuG mode did
+#[derive(Debug, Clone)]
+pub struct PugSwitch {
+	// The new state
+	pub enabled: bool,
+	// A modified gameinfo.txt without a backup is turned into a vanilla one by removing
+	// every addon from it: the contents before and after
+	pub rebuilt: Option<(String, String)>,
+	// What a dry run would have done
+	pub plan: Vec<PlannedStep>,
+}
+
+// The vanilla gameinfo.txt `pug_switch` would rebuild, as the contents before and after, so it
+// can be shown first. None when the switch restores a backup or the custom gameinfo.txt instead.
+pub fn pug_rebuild(game_dir: &Path, verbose: bool) -> Result<Option<(String, String)>, EnforcerError> {
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	let gameinfo_custom = gameinfo_path.with_extension("txt.custom");
+	if gameinfo_backup_path(game_dir, verbose)?.exists() || gameinfo_custom.exists() || check_vanilla(&gameinfo_path)?.is_vanilla() {
+		return Ok(None);
+	}
+	let contents = read_to_string(&gameinfo_path)?;
+	let rebuilt = crate::vanilla_gameinfo::rebuild_vanilla(&contents)?;
+	Ok(Some((contents, rebuilt)))
+}
+
+// Enables PuG mode when it is disabled and the other way round
+pub fn pug_switch(game_dir: &Path, dry_run: bool, verbose: bool) -> Result<PugSwitch, EnforcerError> {
 	// Locate the gameinfo.txt
-	let gameinfo_path = gameinfo_path(verbose)?;
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
 	// Calculate the MD5 of the gameinfo.txt file
-	let gameinfo_md5 = calculate_md5(&gameinfo_path).expect("Failed to calculate MD5");
+	let gameinfo_md5 = calculate_md5(&gameinfo_path)?;
+	// Line endings, whitespace and unknown game builds do not count as modifications
+	let vanilla = check_vanilla(&gameinfo_path)?;
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("gameinfo.txt is {}", vanilla.describe());
+	}
+
+	let gameinfo_backup_path = gameinfo_backup_path(game_dir, verbose)?;
 
-	let gameinfo_backup_path = gameinfo_backup_path(verbose)?;
+	// Every change below is journaled and reverted if a later step fails
+	let mut tx = Transaction::new(dry_run, verbose);
 
 	// path for user's modified gameinfo to be held in
 	let gameinfo_custom = gameinfo_path.with_extension("txt.custom");
 	if var_os("DEBUG").is_some() || verbose {
-		println!(
-			"{} {} {:?}",
-			"[D]".blue(),
-			"gameinfo_backup_path:".bold(),
-			gameinfo_backup_path
-		);
+		log::debug!("gameinfo_backup_path: {:?}", gameinfo_backup_path);
 	}
 	if !gameinfo_backup_path.exists() {
-		if gameinfo_md5 != gameinfo_orig_md5 {
-			let err = format!(
-				"gameinfo.txt file seems to be modified, but no backup is present!
-				\n If you haven't already modified the gameinfo.txt, this is probably
-				\n\ta {}'s bug you can report to the dev!
-				\n\t\tYour gameinfo.txt MD5 hash is: {}",
-				env!("CARGO_PKG_NAME"),
-				gameinfo_md5
-			);
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
+		if !vanilla.is_vanilla() {
+			if gameinfo_custom.exists() {
+				return Err(EnforcerError::GameinfoModifiedNoBackup { md5: gameinfo_md5 });
+			}
+			let contents = read_to_string(&gameinfo_path)?;
+			let rebuilt = crate::vanilla_gameinfo::rebuild_vanilla(&contents)?;
+			if var_os("DEBUG").is_some() || verbose {
+				log::debug!(
+					"Copying current custom gameinfo.txt ({:?}) to the custom backup {:?}",
+					&gameinfo_path.file_name().unwrap().to_string_lossy(),
+					&gameinfo_custom.file_name().unwrap().to_string_lossy(),
+				);
+			}
+			crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "pug", verbose)?;
+			tx.copy(&gameinfo_path, &gameinfo_custom)?;
+			tx.write(&gameinfo_path, &rebuilt)?;
+			record_pug_mode(game_dir, &mut tx, true, verbose)?;
+			let plan = tx.commit()?;
+			Ok(PugSwitch { enabled: true, rebuilt: Some((contents, rebuilt)), plan })
 		} else {
 			// Create a backup of the gameinfo.txt file. Because why not?
-			copy(&gameinfo_path, &gameinfo_backup_path)?;
+			tx.copy(&gameinfo_path, &gameinfo_backup_path)?;
 			if !gameinfo_custom.exists() {
-				let err = format!(
-					"gameinfo.txt is already at its default state, but no custom gameinfo.txt is to be found!
+				// Keep the freshly made backup even though there is nothing to switch to
+				tx.commit()?;
+				let err = "gameinfo.txt is already at its default state, but no custom gameinfo.txt is to be found!
 					\n (PuG Mode is disabled, and gameinfo in unmodified vanilla state)"
-				);
-				eprintln!("{} {}", "Error:".red(), err);
-				return Err(Box::new(QuietErr(Some(err))));
+					.to_string();
+				Err(EnforcerError::Other(err))
 			} else {
 				// Copy custom to gameinfo.txt
 				if var_os("DEBUG").is_some() || verbose {
-					println!(
-						"{} Copying custom gameinfo.txt ({:?}) to {:?}",
-						"[D]".blue(),
+					log::debug!(
+						"Copying custom gameinfo.txt ({:?}) to {:?}",
 						&gameinfo_custom.file_name().unwrap().to_string_lossy(),
-						&gameinfo_path.file_name().unwrap().to_string_lossy()
+						&gameinfo_path.file_name().unwrap().to_string_lossy(),
 					);
 				}
-				copy(&gameinfo_custom, &gameinfo_path)?;
+				crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "pug", verbose)?;
+				tx.copy(&gameinfo_custom, &gameinfo_path)?;
 				if var_os("DEBUG").is_some() || verbose {
-					println!(
-						"{} Deleting unneeded custom gameinfo.txt copy ({:?})",
-						"[D]".blue(),
-						&gameinfo_custom.file_name().unwrap().to_string_lossy()
+					log::debug!(
+						"Deleting unneeded custom gameinfo.txt copy ({:?})",
+						&gameinfo_custom.file_name().unwrap().to_string_lossy(),
 					);
 				}
-				remove_file(&gameinfo_custom)?;
-				println!("PuG Mode is now disabled.");
-				return Ok(2);
+				tx.remove(&gameinfo_custom)?;
+				record_pug_mode(game_dir, &mut tx, false, verbose)?;
+				let plan = tx.commit()?;
+				Ok(PugSwitch { enabled: false, rebuilt: None, plan })
 			}
 		}
 	} else {
-		if gameinfo_md5 != gameinfo_orig_md5 {
+		if !vanilla.is_vanilla() {
 			// if gameinfo_custom.exists() {
 			//     let err = format!(
 			//         "gameinfo.txt file seems to be modified, and a custom gameinfo.txt is present!
@@ -81,65 +122,81 @@ pub fn PuG_mode_switch(verbose: bool) -> Result<i32, Box<dyn std::error::Error>>
 			//         "Error:".red(),
 			//         err
 			//     );
-			//     return Err(Box::new(QuietErr(Some(err))));
+			//     return Err(QuietErr(Some(err)));
 			// }
 			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Copying current custom gameinfo.txt ({:?}) to the custom backup {:?}",
-					"[D]".blue(),
+				log::debug!(
+					"Copying current custom gameinfo.txt ({:?}) to the custom backup {:?}",
 					&gameinfo_path.file_name().unwrap().to_string_lossy(),
-					&gameinfo_custom.file_name().unwrap().to_string_lossy()
+					&gameinfo_custom.file_name().unwrap().to_string_lossy(),
 				);
 			}
-			copy(&gameinfo_path, &gameinfo_custom)?;
+			crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "pug", verbose)?;
+			tx.copy(&gameinfo_path, &gameinfo_custom)?;
 			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Copying gameinfo.txt backup ({:?}) to {:?}",
-					"[D]".blue(),
+				log::debug!(
+					"Copying gameinfo.txt backup ({:?}) to {:?}",
 					&gameinfo_backup_path.file_name().unwrap().to_string_lossy(),
-					&gameinfo_path.file_name().unwrap().to_string_lossy()
+					&gameinfo_path.file_name().unwrap().to_string_lossy(),
 				);
 			}
-			copy(&gameinfo_backup_path, &gameinfo_path)?;
-			println!("PuG Mode is now enabled.");
-			return Ok(1);
+			tx.copy(&gameinfo_backup_path, &gameinfo_path)?;
+			record_pug_mode(game_dir, &mut tx, true, verbose)?;
+			let plan = tx.commit()?;
+			Ok(PugSwitch { enabled: true, rebuilt: None, plan })
 		} else {
 			// Copy custom to gameinfo.txt
 			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Copying custom gameinfo.txt ({:?}) to {:?}",
-					"[D]".blue(),
+				log::debug!(
+					"Copying custom gameinfo.txt ({:?}) to {:?}",
 					&gameinfo_custom.file_name().unwrap().to_string_lossy(),
-					&gameinfo_path.file_name().unwrap().to_string_lossy()
+					&gameinfo_path.file_name().unwrap().to_string_lossy(),
 				);
 			}
-			copy(&gameinfo_custom, &gameinfo_path)?;
+			crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "pug", verbose)?;
+			tx.copy(&gameinfo_custom, &gameinfo_path)?;
 			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Deleting unneeded custom gameinfo.txt copy ({:?})",
-					"[D]".blue(),
-					&gameinfo_custom.file_name().unwrap().to_string_lossy()
+				log::debug!(
+					"Deleting unneeded custom gameinfo.txt copy ({:?})",
+					&gameinfo_custom.file_name().unwrap().to_string_lossy(),
 				);
 			}
-			remove_file(&gameinfo_custom)?;
-			println!("PuG Mode is now disabled.");
-			return Ok(2);
+			tx.remove(&gameinfo_custom)?;
+			record_pug_mode(game_dir, &mut tx, false, verbose)?;
+			let plan = tx.commit()?;
+			Ok(PugSwitch { enabled: false, rebuilt: None, plan })
 		}
 	}
 }
 
-pub fn PuG_mode_check(verbose: bool) -> Result<i32, Box<dyn std::error::Error>> {
+// Whether PuG mode is enabled, and what the addon manifest says about it
+#[derive(Debug, Clone, Serialize)]
+pub struct PugStatus {
+	pub enabled: bool,
+	// As recorded by the last switch, unknown before the first one.
+	// When it differs from `enabled`, the custom copy was removed or restored by hand.
+	pub recorded: Option<bool>,
+	pub gameinfo_path: PathBuf,
+	// Where the user's gameinfo.txt is kept while PuG mode is enabled
+	pub custom_path: Option<PathBuf>,
+}
+
+pub fn pug_status(game_dir: &Path, verbose: bool) -> Result<PugStatus, EnforcerError> {
 	// Locate the gameinfo.txt file
-	let gameinfo_path = gameinfo_path(verbose)?;
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
 
 	// path for user's modified gameinfo to be held in
 	let gameinfo_custom = gameinfo_path.with_extension("txt.custom");
 
-	if !gameinfo_custom.exists() {
-		println!("PuG Mode is currently disabled.");
-		Ok(2)
-	} else {
-		println!("PuG Mode is currently enabled.");
-		Ok(1)
-	}
+	let enabled = gameinfo_custom.exists();
+	let manifest_path = Manifest::path(game_dir);
+	let recorded = if manifest_path.exists() { Manifest::read(&manifest_path).ok().and_then(|manifest| manifest.pug_mode) } else { None };
+	Ok(PugStatus { enabled, recorded, gameinfo_path, custom_path: enabled.then_some(gameinfo_custom) })
+}
+
+// Keeps the manifest's PuG mode state in step with the switch made in `tx`
+fn record_pug_mode(game_dir: &Path, tx: &mut Transaction, enabled: bool, verbose: bool) -> Result<(), EnforcerError> {
+	let mut manifest = Manifest::load(game_dir, verbose)?;
+	manifest.pug_mode = Some(enabled);
+	manifest.save(game_dir, tx)
 }
\ No newline at end of file
@@ -1,164 +1,81 @@
 // SPDX-License-Identifier: LGPL-3.0-only
 use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::{Gameinfo, is_core_component};
+use crate::install_addon::validate_addon_name;
+use crate::transaction::{PlannedStep, Transaction};
 
-pub fn rename_addon(
+// What renaming an addon did
+#[derive(Debug, Clone)]
+pub struct Renamed {
+	pub from: String,
+	pub to: String,
+	// Whether the addon directory was moved along with the entry
+	pub moved_dir: bool,
+	// The steps a dry run would have performed
+	pub plan: Vec<PlannedStep>,
+}
+
+// Renames an addon's entry in gameinfo.txt and the manifest, and its directory
+pub fn rename(
+	game_dir: &Path,
 	ren_name: &str,
 	new_name: &str,
+	dry_run: bool,
 	verbose: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-	if ren_name.is_empty() || new_name.is_empty() {
-		let err = format!("No addon names provided for renaming");
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
-	}
-	// Validate addon name
-	if new_name.contains(char::is_whitespace)
-		|| new_name.contains('/')
-		|| new_name.contains('\\')
-		|| new_name.contains(':')
-		|| new_name.contains('*')
-		|| new_name.contains('?')
-		|| new_name.contains('"')
-		|| new_name.contains('<')
-		|| new_name.contains('>')
-		|| new_name.contains('|')
-	{
-		let err = format!(
-			"Invalid addon name! \n\tNew name cannot be empty, contain whitespace, or special characters \n\tthat are known to cause problems with file managers or filesystems."
-		);
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
-	}
-
-	// Locate the Left 4 Dead 2 directory ... LEAVE THIS WITH THE OLD WAY FOR NOW ...
-	if var_os("DEBUG").is_some() || verbose {
-		println!("{} Locating L4D2 directory...", "[D]".blue());
+) -> Result<Renamed, EnforcerError> {
+	if ren_name.is_empty() {
+		return Err(EnforcerError::InvalidName(ren_name.to_string()));
 	}
-	let l4d2_dir = l4d2_path().expect("Failed to locate Left 4 Dead 2 directory");
-	let gameinfo_path = l4d2_dir.join("left4dead2/gameinfo.txt");
-
-	if !gameinfo_path.exists() {
-		let err = format!("Unable to locate gameinfo.txt file. Is the game installation broken?");
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
+	if new_name.is_empty() {
+		return Err(EnforcerError::InvalidName(new_name.to_string()));
 	}
-
-	if var_os("DEBUG").is_some() || verbose {
-		println!(
-			"{} {} {:?}",
-			"[D]".blue(),
-			"Gameinfo.txt path:".bold(),
-			gameinfo_path
-		);
+	if is_core_component(ren_name) {
+		return Err(EnforcerError::CoreComponentProtected(ren_name.to_string()));
 	}
+	// Validate addon name
+	validate_addon_name(new_name)?;
 
-	// Read the gameinfo.txt file
-	let contents = read_to_string(&gameinfo_path)?;
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
 
-	// Split the file into lines
-	let mut lines: Vec<&str> = contents.lines().collect();
+	// Read and parse the gameinfo.txt file
+	let mut gameinfo = Gameinfo::read(&gameinfo_path)?;
+	let mut manifest = Manifest::load(game_dir, verbose)?;
 
-	let excl_addons = vec![
-		"update",
-		"left4dead2_dlc3",
-		"left4dead2_dlc2",
-		"left4dead2_dlc1",
-		"hl2",
-		"|gameinfo_path|.",
-	];
+	// Rename the selected addon (only on an exact match of its "Game <name>" entry)
+	if gameinfo.has_addon(new_name) || gameinfo.is_disabled(new_name) {
+		return Err(EnforcerError::AlreadyInstalled(new_name.to_string()));
+	}
 
-	// Delete the selected addon
-	if !ren_name.is_empty()
-		&& !new_name.is_empty()
-		&& !excl_addons.iter().any(|&s| ren_name.contains(s))
-		&& !excl_addons.iter().any(|&s| new_name.contains(s))
-	{
-		if lines
-			.iter()
-			.any(|line| line.contains("Game") && line.contains(&new_name))
-		{
-			let err = format!(
-				"{} is already installed! \n\t Please choose a different name.",
-				new_name
-			);
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
-		}
+	let mut tx = Transaction::new(dry_run, verbose);
 
-		if lines
-			.iter()
-			.any(|line| line.contains("Game") && line.contains(&ren_name))
-		{
-			// The line to insert
-			let new_line = format!("\t\t\tGame\t\t\t\t{}", new_name);
-			// Find the line with "Game {ren_name}"
-			let index = lines
-				.iter()
-				.position(|line| line.contains("Game") && line.contains(&ren_name))
-				.unwrap();
-			lines.remove(index);
-			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Removing line \n{} \nfrom gameinfo.txt",
-					"[D]".blue(),
-					index
-				);
-			}
-			// Insert the new line above it
-			lines.insert(index, &new_line);
-			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Inserting line \n{} \n in the line \n {} \n in gameinfo.txt",
-					"[D]".blue(),
-					new_line,
-					index
-				);
-			}
-			let new_contents = lines.join("\n");
-			write(&gameinfo_path, new_contents)?;
-		} else {
-			let err = format!("{} not found in the gameinfo.txt file!", ren_name);
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
-		}
-		let ren_addon_dir = l4d2_dir.join(format!("{}", ren_name));
-		let ren_new_addon_dir = l4d2_dir.join(format!("{}", new_name));
-		if ren_addon_dir.exists() {
-			if ren_addon_dir.is_dir() {
-				std::fs::rename(&ren_addon_dir, &ren_new_addon_dir)?;
-				println!(
-					"Renamed {} to {} successfully.",
-					ren_name.italic(),
-					new_name.italic()
-				);
-			} else {
-				if var_os("DEBUG").is_some() || verbose {
-					println!(
-						"{} {} appears to not be a directory! (filesystem damaged? installation failed?)",
-						"[D]".blue(),
-						ren_addon_dir.display()
-					);
-				}
-			}
+	// Rewrite the "Game {ren_name}" entry in place
+	if gameinfo.rename_addon(ren_name, new_name) {
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Renaming entry \n{} \nto \n{} \nin gameinfo.txt", ren_name, new_name);
 		}
-		return Ok(());
+		crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "rename", verbose)?;
+		tx.write(&gameinfo_path, gameinfo.doc.to_string())?;
+		manifest.rename(ren_name, new_name);
+		manifest.save(game_dir, &mut tx)?;
 	} else {
-		if excl_addons.iter().any(|&s| ren_name.contains(s)) {
-			let err = format!(
-				"Core game components cannot be renamed! \n\t Found: \"{}\"",
-				ren_name
-			);
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
-		}
-		if excl_addons.iter().any(|&s| new_name.contains(s)) {
-			let err = format!(
-				"New name \"{}\" conflicts with one of the core game components!",
-				new_name
+		return Err(gameinfo.missing_addon(ren_name));
+	}
+	let ren_addon_dir = game_dir.join(ren_name);
+	let ren_new_addon_dir = game_dir.join(new_name);
+	let mut moved_dir = false;
+	if ren_addon_dir.exists() {
+		if ren_addon_dir.is_dir() {
+			tx.rename(&ren_addon_dir, &ren_new_addon_dir)?;
+			moved_dir = true;
+		} else if var_os("DEBUG").is_some() || verbose {
+			log::debug!(
+				"{} appears to not be a directory! (filesystem damaged? installation failed?)",
+				ren_addon_dir.display(),
 			);
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
 		}
 	}
-	Ok(())
-}
\ No newline at end of file
+	let plan = tx.commit()?;
+	Ok(Renamed { from: ren_name.to_string(), to: new_name.to_string(), moved_dir, plan })
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::addon_manifest::{Manifest, ManifestEntry, vpk_hashes};
+use crate::gameinfo::Gameinfo;
+use crate::transaction::{PlannedStep, Transaction};
+use serde::Serialize;
+
+// What `repair_manifest` did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+	// One line per fix made
+	pub fixes: Vec<String>,
+	// What could not be repaired and is left to the user
+	pub warnings: Vec<String>,
+	// The steps a dry run would have performed
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub plan: Vec<PlannedStep>,
+}
+
+// Reconciles gameinfo.txt, the addon manifest and the addon directories:
+// - addons the manifest knows, whose files are still there but whose gameinfo.txt entry is gone
+//   (e.g. after Steam verified the game files), are put back into gameinfo.txt,
+// - manifest entries whose files are gone are dropped,
+// - addons found only in gameinfo.txt are added to the manifest,
+// - hashes, addoninfo metadata, enabled flags and the PuG mode state are refreshed.
+pub fn repair_manifest(game_dir: &Path, dry_run: bool, verbose: bool) -> Result<RepairReport, EnforcerError> {
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	// While PuG mode is enabled, the user's addons live in the custom copy
+	let gameinfo_custom = gameinfo_path.with_extension("txt.custom");
+	let pug_mode = gameinfo_custom.exists();
+	let target = if pug_mode { gameinfo_custom } else { gameinfo_path };
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Repairing against {:?}", target);
+	}
+	let mut gameinfo = Gameinfo::read(&target)?;
+	let manifest_path = Manifest::path(game_dir);
+	let mut report = RepairReport::default();
+	let mut manifest = if manifest_path.exists() {
+		match Manifest::read(&manifest_path) {
+			Ok(manifest) => manifest,
+			Err(e) => {
+				report.warnings.push(format!("Unable to use the addon manifest ({}), rebuilding it.", e));
+				Manifest::default()
+			},
+		}
+	} else {
+		Manifest::default()
+	};
+	let vpk_of = |name: &str| game_dir.join(name).join("pak01_dir.vpk");
+
+	let fixes = &mut report.fixes;
+	let mut gameinfo_changed = false;
+	let states = gameinfo.addon_states();
+
+	// Manifest entries without a gameinfo.txt entry
+	let mut restored = Vec::new();
+	for entry in manifest.addons.clone() {
+		if states.iter().any(|(addon, _)| *addon == entry.name) {
+			continue;
+		}
+		if vpk_of(&entry.name).is_file() {
+			gameinfo.add_addon(&entry.name)?;
+			if !entry.enabled {
+				gameinfo.disable_addon(&entry.name);
+			}
+			gameinfo_changed = true;
+			fixes.push(format!(
+				"Restored the gameinfo.txt entry of {}{}",
+				entry.name,
+				if entry.enabled { "" } else { " (disabled)" }
+			));
+			restored.push(entry.name);
+		} else {
+			manifest.remove(&entry.name);
+			fixes.push(format!("Dropped {} from the manifest, its files are gone", entry.name));
+		}
+	}
+
+	// gameinfo.txt entries (restored ones included), with or without a manifest entry
+	for (addon, enabled) in &gameinfo.addon_states() {
+		let vpk = vpk_of(addon);
+		if !vpk.is_file() {
+			report.warnings.push(format!(
+				"{} is listed in gameinfo.txt, but {} is missing. Reinstall or uninstall it.",
+				addon,
+				vpk.display()
+			));
+			continue;
+		}
+		match manifest.addons.iter_mut().find(|entry| entry.name == *addon) {
+			None => {
+				manifest.addons.push(ManifestEntry::scan(addon, &vpk, verbose)?);
+				fixes.push(format!("Added {} to the manifest", addon));
+			},
+			Some(entry) => {
+				let (md5, sha256) = vpk_hashes(&vpk)?;
+				if entry.md5.as_ref() != Some(&md5) || entry.sha256.as_ref() != Some(&sha256) {
+					let scanned = ManifestEntry::scan(addon, &vpk, verbose)?;
+					entry.md5 = scanned.md5;
+					entry.sha256 = scanned.sha256;
+					entry.info = scanned.info;
+					fixes.push(format!("Updated the hashes of {}, its VPK changed outside of {}", addon, env!("CARGO_PKG_NAME")));
+				}
+				if entry.enabled != *enabled {
+					fixes.push(format!("Marked {} as {} (as in gameinfo.txt)", addon, if *enabled { "enabled" } else { "disabled" }));
+				}
+			},
+		}
+	}
+
+	// Restored addons take back their place in the load order
+	if !restored.is_empty() {
+		let mut order = gameinfo.addons();
+		order.sort_by_key(|addon| manifest.addons.iter().position(|entry| entry.name == *addon).unwrap_or(usize::MAX));
+		gameinfo.reorder_addons(&order)?;
+	}
+
+	if manifest.pug_mode != Some(pug_mode) {
+		manifest.pug_mode = Some(pug_mode);
+		fixes.push(format!("Recorded PuG mode as {}", if pug_mode { "enabled" } else { "disabled" }));
+	}
+	manifest.sync(&gameinfo);
+
+	let mut tx = Transaction::new(dry_run, verbose);
+	if gameinfo_changed {
+		// The custom copy kept by PuG mode has no history of its own
+		if !pug_mode {
+			crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &target, "repair", verbose)?;
+		}
+		tx.write(&target, gameinfo.doc.to_string())?;
+	}
+	if !fixes.is_empty() || !manifest_path.exists() {
+		manifest.save(game_dir, &mut tx)?;
+	}
+	report.plan = tx.commit()?;
+	Ok(report)
+}
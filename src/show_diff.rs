@@ -0,0 +1,8 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use similar::TextDiff;
+
+// A unified diff of two versions of a text file, like `git diff` shows
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+	let diff = TextDiff::from_lines(old, new);
+	diff.unified_diff().context_radius(3).header(old_name, new_name).to_string()
+}
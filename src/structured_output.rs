@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use serde::Serialize;
+
+// The machine-readable output of `--format json` and `--format tsv`.
+// Field names are part of the output, so scripts can rely on them: rename nothing.
+
+// How status commands (list, inspect, pug --check) print their results
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+	// For people, colored
+	#[default]
+	Text,
+	// One JSON document, for scripts
+	Json,
+	// Tab-separated values with a header line, tabs and newlines in values escaped as \t and \n
+	Tsv,
+}
+
+// Writes `value` as one pretty-printed JSON document
+pub fn write_json(out: &mut impl Write, value: &impl Serialize) -> Result<(), EnforcerError> {
+	serde_json::to_writer_pretty(&mut *out, value)?;
+	writeln!(out)?;
+	Ok(())
+}
+
+// Writes one line of tab-separated values
+pub fn write_tsv_row<S: AsRef<str>>(out: &mut impl Write, fields: &[S]) -> Result<(), EnforcerError> {
+	let fields: Vec<String> = fields.iter().map(|field| tsv_field(field.as_ref())).collect();
+	writeln!(out, "{}", fields.join("\t"))?;
+	Ok(())
+}
+
+// Backslashes, tabs and line breaks are escaped, so that every row stays on one line
+fn tsv_field(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('\t', "\\t").replace('\r', "").replace('\n', "\\n")
+}
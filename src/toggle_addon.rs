@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::{Gameinfo, is_core_component};
+use crate::transaction::{PlannedStep, Transaction};
+
+// What enabling or disabling addons did
+#[derive(Debug, Clone, Default)]
+pub struct Toggled {
+	pub changed: Vec<String>,
+	// The addons that already were enabled (or disabled)
+	pub already: Vec<String>,
+	// What a dry run would have done
+	pub plan: Vec<PlannedStep>,
+}
+
+// Enables or disables addons without touching their files: a disabled addon's `Game` line
+// is commented out in gameinfo.txt, and its directory is kept for when it is enabled again.
+pub fn set_addons_enabled(game_dir: &Path, names: &[String], enable: bool, dry_run: bool, verbose: bool) -> Result<Toggled, EnforcerError> {
+	if names.is_empty() {
+		let err = format!("No addon names provided to {}", if enable { "enable" } else { "disable" });
+		return Err(EnforcerError::Other(err));
+	}
+	if let Some(name) = names.iter().find(|name| is_core_component(name)) {
+		return Err(EnforcerError::CoreComponentProtected(name.to_string()));
+	}
+
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
+	let mut gameinfo = Gameinfo::read(&gameinfo_path)?;
+	let mut manifest = Manifest::load(game_dir, verbose)?;
+	let mut toggled = Toggled::default();
+	for name in names {
+		let already = if enable { gameinfo.has_addon(name) } else { gameinfo.is_disabled(name) };
+		if already {
+			toggled.already.push(name.clone());
+			continue;
+		}
+		let done = if enable { gameinfo.enable_addon(name)? } else { gameinfo.disable_addon(name) };
+		if !done {
+			return Err(gameinfo.missing_addon(name));
+		}
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!(
+				"{} the Game entry of {} in gameinfo.txt",
+				if enable { "Uncommenting" } else { "Commenting out" },
+				name,
+			);
+		}
+		toggled.changed.push(name.clone());
+	}
+
+	if !toggled.changed.is_empty() {
+		let mut tx = Transaction::new(dry_run, verbose);
+		crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, if enable { "enable" } else { "disable" }, verbose)?;
+		tx.write(&gameinfo_path, gameinfo.doc.to_string())?;
+		manifest.sync(&gameinfo);
+		manifest.save(game_dir, &mut tx)?;
+		toggled.plan = tx.commit()?;
+	}
+	Ok(toggled)
+}
@@ -0,0 +1,394 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use serde::Serialize;
+use std::fs::{rename, remove_dir_all};
+
+// Bigger files (e.g. VPKs) are never shown as a diff in the dry run plan
+const DIFF_SIZE_LIMIT: u64 = 1024 * 1024;
+
+// A journal of filesystem steps performed by a mutating command.
+//
+// Nothing is destroyed until `commit()`: overwritten and removed files/directories are moved
+// aside next to their original location and only deleted once every step succeeded.
+// If the transaction is dropped without being committed (e.g. a `?` returned early),
+// every recorded step is undone in reverse order.
+//
+// A dry run transaction (`--dry-run`) touches nothing: it only plans its steps, and `commit()`
+// returns them for the front-ends to show.
+enum Step {
+	CreatedDir(PathBuf),
+	Placed { path: PathBuf, previous: Option<PathBuf> },
+	Renamed { from: PathBuf, to: PathBuf },
+	Removed { path: PathBuf, aside: PathBuf },
+}
+
+// A step a dry run would have performed
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedStep {
+	pub description: String,
+	// The path, previous and new contents of a replaced text file
+	pub diff: Option<(PathBuf, String, String)>,
+}
+
+pub struct Transaction {
+	steps: Vec<Step>,
+	plan: Vec<PlannedStep>,
+	dry_run: bool,
+	verbose: bool,
+	finished: bool,
+}
+
+impl Transaction {
+	pub fn new(dry_run: bool, verbose: bool) -> Transaction {
+		Transaction { steps: Vec::new(), plan: Vec::new(), dry_run, verbose, finished: false }
+	}
+
+	fn plan(&mut self, description: String, diff: Option<(PathBuf, String, String)>) {
+		self.debug(format!("Dry run: {}", description));
+		self.plan.push(PlannedStep { description, diff });
+	}
+
+	fn debug(&self, msg: String) {
+		if var_os("DEBUG").is_some() || self.verbose {
+			log::debug!("{}", msg);
+		}
+	}
+
+	pub fn create_dir(&mut self, path: &Path) -> Result<(), std::io::Error> {
+		// Remember the outermost directory that did not exist yet, so undo removes all of it
+		let Some(first_missing) = path.ancestors().take_while(|p| !p.exists()).last() else {
+			return Ok(());
+		};
+		if self.dry_run {
+			self.plan(format!("mkdir {}", path.display()), None);
+			return Ok(());
+		}
+		let first_missing = first_missing.to_path_buf();
+		create_dir_all(path)?;
+		self.debug(format!("Created directory {:?}", path));
+		self.steps.push(Step::CreatedDir(first_missing));
+		Ok(())
+	}
+
+	// Copies `from` over `to`. The destination only appears once the copy is complete.
+	pub fn copy(&mut self, from: &Path, to: &Path) -> Result<(), std::io::Error> {
+		if self.dry_run {
+			if !from.is_file() {
+				return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} does not exist", from.display())));
+			}
+			let diff = text_contents(to).zip(text_contents(from)).map(|(old, new)| (to.to_path_buf(), old, new));
+			self.plan(format!("copy {} -> {}", from.display(), to.display()), diff);
+			return Ok(());
+		}
+		let temp = temp_sibling(to);
+		if let Err(e) = copy(from, &temp) {
+			let _ = remove_file(&temp);
+			return Err(e);
+		}
+		self.place(&temp, to)?;
+		self.debug(format!("Copied {:?} to {:?}", from, to));
+		Ok(())
+	}
+
+	// Atomically replaces the contents of `path` (temp file + rename)
+	pub fn write(&mut self, path: &Path, contents: impl AsRef<[u8]>) -> Result<(), std::io::Error> {
+		if self.dry_run {
+			let new = Some(contents.as_ref())
+				.filter(|contents| contents.len() as u64 <= DIFF_SIZE_LIMIT)
+				.and_then(|contents| String::from_utf8(contents.to_vec()).ok());
+			let diff = text_contents(path).zip(new).map(|(old, new)| (path.to_path_buf(), old, new));
+			self.plan(format!("write {}", path.display()), diff);
+			return Ok(());
+		}
+		let temp = temp_sibling(path);
+		if let Err(e) = write_synced(&temp, contents.as_ref()) {
+			let _ = remove_file(&temp);
+			return Err(e);
+		}
+		self.place(&temp, path)?;
+		self.debug(format!("Wrote {:?}", path));
+		Ok(())
+	}
+
+	// Moves a finished temp file into place, keeping whatever was there before
+	fn place(&mut self, temp: &Path, path: &Path) -> Result<(), std::io::Error> {
+		let previous = if path.exists() {
+			let aside = aside_path(path);
+			if let Err(e) = rename(path, &aside) {
+				let _ = remove_file(temp);
+				return Err(e);
+			}
+			Some(aside)
+		} else {
+			None
+		};
+		if let Err(e) = rename(temp, path) {
+			let _ = remove_file(temp);
+			if let Some(aside) = &previous {
+				let _ = rename(aside, path);
+			}
+			return Err(e);
+		}
+		self.steps.push(Step::Placed { path: path.to_path_buf(), previous });
+		Ok(())
+	}
+
+	pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), std::io::Error> {
+		if self.dry_run {
+			std::fs::symlink_metadata(from)?;
+			self.plan(format!("rename {} -> {}", from.display(), to.display()), None);
+			return Ok(());
+		}
+		rename(from, to)?;
+		self.debug(format!("Renamed {:?} to {:?}", from, to));
+		self.steps.push(Step::Renamed { from: from.to_path_buf(), to: to.to_path_buf() });
+		Ok(())
+	}
+
+	// Removes a file or a whole directory (moved aside until commit)
+	pub fn remove(&mut self, path: &Path) -> Result<(), std::io::Error> {
+		if self.dry_run {
+			let is_dir = std::fs::symlink_metadata(path)?.is_dir();
+			self.plan(format!("{} {}", if is_dir { "remove_dir_all" } else { "remove" }, path.display()), None);
+			return Ok(());
+		}
+		let aside = aside_path(path);
+		rename(path, &aside)?;
+		self.debug(format!("Removed {:?}", path));
+		self.steps.push(Step::Removed { path: path.to_path_buf(), aside });
+		Ok(())
+	}
+
+	// Makes every step permanent by deleting what was moved aside, and returns the planned
+	// steps of a dry run (none otherwise).
+	// What cannot be deleted is left behind and returned as the error, the steps stay done.
+	pub fn commit(mut self) -> Result<Vec<PlannedStep>, std::io::Error> {
+		// Once a first aside is deleted the steps cannot be undone, so dropping must not try to
+		self.finished = true;
+		if self.dry_run {
+			return Ok(std::mem::take(&mut self.plan));
+		}
+		let mut leftovers = Vec::new();
+		for step in self.steps.drain(..) {
+			match step {
+				Step::Placed { previous: Some(aside), .. } | Step::Removed { aside, .. } => {
+					let result = if aside.is_dir() { remove_dir_all(&aside) } else { remove_file(&aside) };
+					if let Err(e) = result {
+						leftovers.push(format!("{} ({})", aside.display(), e));
+					}
+				},
+				_ => {},
+			}
+		}
+		if !leftovers.is_empty() {
+			let err = format!("The changes were made, but these leftovers could not be deleted: {}", leftovers.join(", "));
+			return Err(std::io::Error::other(err));
+		}
+		Ok(Vec::new())
+	}
+
+	// Marks the current position in the journal, see `rollback_to()`
+	pub fn savepoint(&self) -> usize {
+		if self.dry_run { self.plan.len() } else { self.steps.len() }
+	}
+
+	// Undoes only the steps recorded after `savepoint`, the transaction stays usable
+	pub fn rollback_to(&mut self, savepoint: usize) {
+		if self.dry_run {
+			self.plan.truncate(savepoint);
+			return;
+		}
+		while self.steps.len() > savepoint {
+			let step = self.steps.pop().unwrap();
+			let result = match &step {
+				Step::CreatedDir(path) => {
+					self.debug(format!("Rolling back: removing {:?}", path));
+					remove_dir_all(path)
+				},
+				Step::Placed { path, previous } => {
+					self.debug(format!("Rolling back: restoring {:?}", path));
+					match previous {
+						Some(aside) => rename(aside, path),
+						None => remove_file(path),
+					}
+				},
+				Step::Renamed { from, to } => {
+					self.debug(format!("Rolling back: renaming {:?} back to {:?}", to, from));
+					rename(to, from)
+				},
+				Step::Removed { path, aside } => {
+					self.debug(format!("Rolling back: restoring {:?}", path));
+					rename(aside, path)
+				},
+			};
+			if let Err(e) = result {
+				log::warn!("Failed to roll back a change: {}", e);
+			}
+		}
+	}
+
+	pub fn rollback(&mut self) {
+		self.finished = true;
+		self.rollback_to(0);
+	}
+}
+
+impl Drop for Transaction {
+	fn drop(&mut self) {
+		if !self.finished && !self.steps.is_empty() {
+			log::warn!("The operation failed, reverting the changes made so far...");
+			self.rollback();
+		}
+	}
+}
+
+// The contents of a file small enough and textual enough to be shown as a diff
+fn text_contents(path: &Path) -> Option<String> {
+	let metadata = std::fs::metadata(path).ok()?;
+	if !metadata.is_file() || metadata.len() > DIFF_SIZE_LIMIT {
+		return None;
+	}
+	String::from_utf8(std::fs::read(path).ok()?).ok()
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+	let mut file = File::create(path)?;
+	file.write_all(contents)?;
+	file.sync_all()
+}
+
+fn temp_sibling(path: &Path) -> PathBuf {
+	unique_sibling(path, "tmp")
+}
+
+fn aside_path(path: &Path) -> PathBuf {
+	unique_sibling(path, "old")
+}
+
+// `.<name>.l4ae-<kind>[-N]` next to `path`, picking a name that is not taken
+fn unique_sibling(path: &Path, kind: &str) -> PathBuf {
+	let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+	let mut candidate = path.with_file_name(format!(".{}.l4ae-{}", name, kind));
+	let mut n = 1;
+	while candidate.exists() {
+		candidate = path.with_file_name(format!(".{}.l4ae-{}-{}", name, kind, n));
+		n += 1;
+	}
+	candidate
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	// A directory holding "kept.txt" and "gone.txt"
+	fn workspace() -> TempDir {
+		let temp = tempfile::tempdir().unwrap();
+		std::fs::write(temp.path().join("kept.txt"), "kept").unwrap();
+		std::fs::write(temp.path().join("gone.txt"), "gone").unwrap();
+		temp
+	}
+
+	// Every file below `dir` with its contents, sorted by path
+	fn snapshot(dir: &Path) -> Vec<(PathBuf, Option<String>)> {
+		let mut entries = Vec::new();
+		for entry in std::fs::read_dir(dir).unwrap() {
+			let path = entry.unwrap().path();
+			if path.is_dir() {
+				entries.push((path.clone(), None));
+				entries.extend(snapshot(&path));
+			} else {
+				entries.push((path.clone(), Some(read_to_string(&path).unwrap())));
+			}
+		}
+		entries.sort();
+		entries
+	}
+
+	// Changes every kind of step can make
+	fn change_everything(tx: &mut Transaction, dir: &Path) -> Result<(), std::io::Error> {
+		tx.create_dir(&dir.join("new/nested"))?;
+		tx.write(&dir.join("new/nested/file.txt"), "new")?;
+		tx.write(&dir.join("kept.txt"), "overwritten")?;
+		tx.copy(&dir.join("kept.txt"), &dir.join("copy.txt"))?;
+		tx.rename(&dir.join("copy.txt"), &dir.join("renamed.txt"))?;
+		tx.remove(&dir.join("gone.txt"))?;
+		Ok(())
+	}
+
+	#[test]
+	fn commit_keeps_the_changes_and_leaves_nothing_aside() {
+		let temp = workspace();
+		let mut tx = Transaction::new(false, false);
+		change_everything(&mut tx, temp.path()).unwrap();
+		tx.commit().unwrap();
+		let files: Vec<String> = snapshot(temp.path())
+			.into_iter()
+			.map(|(path, _)| path.strip_prefix(temp.path()).unwrap().to_string_lossy().replace('\\', "/"))
+			.collect();
+		assert_eq!(files, ["kept.txt", "new", "new/nested", "new/nested/file.txt", "renamed.txt"]);
+		assert_eq!(read_to_string(temp.path().join("kept.txt")).unwrap(), "overwritten");
+	}
+
+	#[test]
+	fn dropping_rolls_back() {
+		let temp = workspace();
+		let before = snapshot(temp.path());
+		let mut tx = Transaction::new(false, false);
+		change_everything(&mut tx, temp.path()).unwrap();
+		assert_ne!(snapshot(temp.path()), before);
+		drop(tx);
+		assert_eq!(snapshot(temp.path()), before);
+	}
+
+	#[test]
+	fn a_failed_step_rolls_back_the_previous_ones() {
+		let temp = workspace();
+		let before = snapshot(temp.path());
+		let run = |dir: &Path| -> Result<Vec<PlannedStep>, std::io::Error> {
+			let mut tx = Transaction::new(false, false);
+			change_everything(&mut tx, dir)?;
+			tx.copy(&dir.join("missing.txt"), &dir.join("kept.txt"))?;
+			tx.commit()
+		};
+		assert!(run(temp.path()).is_err());
+		assert_eq!(snapshot(temp.path()), before);
+	}
+
+	#[test]
+	fn rollback_to_a_savepoint_keeps_the_earlier_steps() {
+		let temp = workspace();
+		let mut tx = Transaction::new(false, false);
+		tx.write(&temp.path().join("kept.txt"), "first").unwrap();
+		let savepoint = tx.savepoint();
+		tx.write(&temp.path().join("kept.txt"), "second").unwrap();
+		tx.remove(&temp.path().join("gone.txt")).unwrap();
+		tx.rollback_to(savepoint);
+		tx.commit().unwrap();
+		assert_eq!(read_to_string(temp.path().join("kept.txt")).unwrap(), "first");
+		assert_eq!(read_to_string(temp.path().join("gone.txt")).unwrap(), "gone");
+	}
+
+	#[test]
+	fn a_dry_run_only_plans() {
+		let temp = workspace();
+		let before = snapshot(temp.path());
+		let dir = temp.path();
+		let mut tx = Transaction::new(true, false);
+		tx.create_dir(&dir.join("new")).unwrap();
+		tx.write(&dir.join("new/file.txt"), "new").unwrap();
+		tx.write(&dir.join("kept.txt"), "overwritten").unwrap();
+		tx.copy(&dir.join("kept.txt"), &dir.join("copy.txt")).unwrap();
+		tx.rename(&dir.join("kept.txt"), &dir.join("renamed.txt")).unwrap();
+		tx.remove(&dir.join("gone.txt")).unwrap();
+		let plan = tx.commit().unwrap();
+		assert_eq!(snapshot(temp.path()), before);
+		let descriptions: Vec<&str> = plan.iter().map(|step| step.description.split(' ').next().unwrap()).collect();
+		assert_eq!(descriptions, ["mkdir", "write", "write", "copy", "rename", "remove"]);
+		let (path, old, new) = plan[2].diff.clone().unwrap();
+		assert_eq!((path, old.as_str(), new.as_str()), (temp.path().join("kept.txt"), "kept", "overwritten"));
+		// Each transaction plans for itself
+		assert!(Transaction::new(true, false).commit().unwrap().is_empty());
+	}
+}
@@ -1,78 +1,62 @@
 // SPDX-License-Identifier: LGPL-3.0-only
 use crate::*;
+use crate::addon_manifest::Manifest;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::{Gameinfo, is_core_component};
+use crate::transaction::{PlannedStep, Transaction};
 
-pub fn uninstall_addon(del_name: &str, verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+// What uninstalling an addon did
+#[derive(Debug, Clone)]
+pub struct Uninstalled {
+	pub name: String,
+	// The addon directory, when there was one to delete
+	pub removed_dir: Option<PathBuf>,
+	// The steps a dry run would have performed
+	pub plan: Vec<PlannedStep>,
+}
+
+// Removes an addon's entry from gameinfo.txt and the manifest, and its directory
+pub fn uninstall(game_dir: &Path, del_name: &str, dry_run: bool, verbose: bool) -> Result<Uninstalled, EnforcerError> {
 	if del_name.is_empty() {
-		let err = format!("No addon name provided for uninstallation");
-		eprintln!("{} {}", "Error:".red(), err);
-		return Err(Box::new(QuietErr(Some(err))));
+		return Err(EnforcerError::InvalidName(del_name.to_string()));
+	}
+	if is_core_component(del_name) {
+		return Err(EnforcerError::CoreComponentProtected(del_name.to_string()));
 	}
 
 	// Locate the gameinfo.txt file
-	let gameinfo_path = gameinfo_path(verbose)?;
-
-	// Read the gameinfo.txt file
-	let contents = read_to_string(&gameinfo_path)?;
+	let gameinfo_path = gameinfo_path(game_dir, verbose)?;
 
-	// Split the file into lines
-	let mut lines: Vec<&str> = contents.lines().collect();
+	// Read and parse the gameinfo.txt file
+	let mut gameinfo = Gameinfo::read(&gameinfo_path)?;
+	let mut manifest = Manifest::load(game_dir, verbose)?;
 
-	let excl_addons = vec![
-		"update",
-		"left4dead2_dlc3",
-		"left4dead2_dlc2",
-		"left4dead2_dlc1",
-		"hl2",
-		"|gameinfo_path|.",
-	];
-
-	// Delete the selected addon
-	if !del_name.is_empty() && !excl_addons.iter().any(|&s| del_name.contains(s)) {
-		if lines
-			.iter()
-			.any(|line| line.contains("Game") && line.contains(&del_name))
-		{
-			let index = lines
-				.iter()
-				.position(|line| line.contains("Game") && line.contains(&del_name))
-				.unwrap();
-			lines.remove(index);
-			let new_contents = lines.join("\n");
-			write(&gameinfo_path, new_contents)?;
-			if var_os("DEBUG").is_some() || verbose {
-				println!(
-					"{} Removing line \n{} \nfrom gameinfo.txt",
-					"[D]".blue(),
-					index
-				);
-			}
-		} else {
-			let err = format!("{} not found in the gameinfo.txt file!", del_name);
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
-		}
-		let del_addon_dir = l4d2_path()?.join(format!("{}", del_name));
-		if del_addon_dir.exists() {
-			if del_addon_dir.is_dir() {
-				std::fs::remove_dir_all(&del_addon_dir)?;
-				println!("Uninstalled {} successfully.", del_name.italic());
-			} else {
-				if var_os("DEBUG").is_some() || verbose {
-					println!(
-						"{} {} appears to not be a directory! (filesystem damaged? installation failed?)",
-						"[D]".blue(),
-						del_addon_dir.display()
-					);
-				}
-			}
+	// Delete the selected addon (only on an exact match of its "Game <name>" entry)
+	let mut tx = Transaction::new(dry_run, verbose);
+	if gameinfo.remove_addon(del_name) {
+		crate::gameinfo_history::save_snapshot(game_dir, &mut tx, &gameinfo_path, "uninstall", verbose)?;
+		tx.write(&gameinfo_path, gameinfo.doc.to_string())?;
+		manifest.remove(del_name);
+		manifest.save(game_dir, &mut tx)?;
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("Removing entry \n{} \nfrom gameinfo.txt", del_name);
 		}
-		return Ok(());
 	} else {
-		if excl_addons.iter().any(|&s| del_name.contains(s)) {
-			let err = format!("Core game components cannot be uninstalled!");
-			eprintln!("{} {}", "Error:".red(), err);
-			return Err(Box::new(QuietErr(Some(err))));
+		return Err(gameinfo.missing_addon(del_name));
+	}
+	let del_addon_dir = game_dir.join(del_name);
+	let mut removed_dir = None;
+	if del_addon_dir.exists() {
+		if del_addon_dir.is_dir() {
+			tx.remove(&del_addon_dir)?;
+			removed_dir = Some(del_addon_dir);
+		} else if var_os("DEBUG").is_some() || verbose {
+			log::debug!(
+				"{} appears to not be a directory! (filesystem damaged? installation failed?)",
+				del_addon_dir.display(),
+			);
 		}
 	}
-	Ok(())
+	let plan = tx.commit()?;
+	Ok(Uninstalled { name: del_name.to_string(), removed_dir, plan })
 }
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use flate2::read::GzDecoder;
+use std::path::Component;
+use tempfile::TempDir;
+
+// VPK files extracted from a downloaded archive.
+// They live in a temporary directory that is deleted once this is dropped.
+pub struct UnpackedArchive {
+	pub name: String,
+	pub dir: TempDir,
+	pub vpks: Vec<PathBuf>,
+	pub skipped: Vec<String>,
+}
+
+enum ArchiveKind {
+	Zip,
+	TarGz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+	let name = path.file_name()?.to_string_lossy().to_lowercase();
+	if name.ends_with(".zip") {
+		Some(ArchiveKind::Zip)
+	} else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+		Some(ArchiveKind::TarGz)
+	} else {
+		None
+	}
+}
+
+pub fn is_archive(path: &Path) -> bool {
+	archive_kind(path).is_some()
+}
+
+// Archive formats we recognize but cannot read
+pub fn is_unsupported_archive(path: &Path) -> bool {
+	path.extension()
+		.is_some_and(|ext| ["7z", "rar"].iter().any(|u| ext.eq_ignore_ascii_case(u)))
+}
+
+// Streams every *.vpk entry of a zip or tar.gz archive out to a temporary directory,
+// keeping the archive's directory layout. Anything else is skipped and reported.
+pub fn unpack_archive(
+	archive: &Path,
+	verbose: bool,
+) -> Result<UnpackedArchive, EnforcerError> {
+	let name = archive.file_name().map_or(archive.to_string_lossy(), |n| n.to_string_lossy()).to_string();
+	let dir = tempfile::Builder::new().prefix("l4ae-").tempdir()?;
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("Unpacking {} to {:?}", name, dir.path());
+	}
+
+	let mut unpacked = UnpackedArchive { name, dir, vpks: Vec::new(), skipped: Vec::new() };
+	match archive_kind(archive) {
+		Some(ArchiveKind::Zip) => {
+			let mut zip = zip::ZipArchive::new(File::open(archive)?)?;
+			for i in 0..zip.len() {
+				let mut entry = zip.by_index(i)?;
+				if entry.is_dir() {
+					continue;
+				}
+				let entry_name = entry.name().to_string();
+				match entry.enclosed_name() {
+					Some(relative) => unpacked.unpack_entry(&mut entry, &entry_name, &relative)?,
+					None => unpacked.skipped.push(entry_name),
+				}
+			}
+		},
+		Some(ArchiveKind::TarGz) => {
+			let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+			for entry in tar.entries()? {
+				let mut entry = entry?;
+				let entry_path = entry.path()?.to_path_buf();
+				let entry_name = entry_path.to_string_lossy().to_string();
+				if entry.header().entry_type().is_dir() {
+					continue;
+				}
+				match enclosed_path(&entry_path) {
+					Some(relative) if entry.header().entry_type().is_file() => {
+						unpacked.unpack_entry(&mut entry, &entry_name, &relative)?
+					},
+					_ => unpacked.skipped.push(entry_name),
+				}
+			}
+		},
+		None => {
+			let err = format!("{} is not a supported archive (.zip, .tar.gz)", unpacked.name);
+			return Err(EnforcerError::Other(err));
+		},
+	}
+
+	// Skipped entries are reported by the install summary
+	unpacked.vpks.sort();
+	Ok(unpacked)
+}
+
+impl UnpackedArchive {
+	fn unpack_entry(
+		&mut self,
+		entry: &mut impl Read,
+		entry_name: &str,
+		relative: &Path,
+	) -> Result<(), std::io::Error> {
+		let is_vpk = relative.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("vpk"));
+		if !is_vpk {
+			self.skipped.push(entry_name.to_string());
+			return Ok(());
+		}
+		let destination = self.dir.path().join(relative);
+		if let Some(parent) = destination.parent() {
+			create_dir_all(parent)?;
+		}
+		std::io::copy(entry, &mut File::create(&destination)?)?;
+		self.vpks.push(destination);
+		Ok(())
+	}
+
+	// "<archive>: <entry>" for an unpacked VPK, used in messages
+	pub fn label(&self, vpk: &Path) -> String {
+		let entry = vpk.strip_prefix(self.dir.path()).unwrap_or(vpk);
+		format!("{}: {}", self.name, entry.to_string_lossy())
+	}
+}
+
+// Refuses absolute paths and ".." so an entry cannot escape the temporary directory
+fn enclosed_path(path: &Path) -> Option<PathBuf> {
+	let mut enclosed = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::Normal(part) => enclosed.push(part),
+			Component::CurDir => {},
+			_ => return None,
+		}
+	}
+	if enclosed.as_os_str().is_empty() { None } else { Some(enclosed) }
+}
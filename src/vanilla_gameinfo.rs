@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::gameinfo::{CORE_GAME_PATHS, Gameinfo};
+use crate::keyvalues::KvDocument;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEndings {
+	Crlf,
+	Lf,
+	// Both kinds in the same file
+	Mixed,
+	// Not recorded for this file
+	Unknown,
+}
+
+impl LineEndings {
+	pub fn of(contents: &[u8]) -> LineEndings {
+		let lf = contents.iter().filter(|byte| **byte == b'\n').count();
+		let crlf = contents.windows(2).filter(|pair| pair == b"\r\n").count();
+		match (crlf, lf) {
+			(_, 0) => LineEndings::Unknown,
+			(0, _) => LineEndings::Lf,
+			(crlf, lf) if crlf == lf => LineEndings::Crlf,
+			_ => LineEndings::Mixed,
+		}
+	}
+}
+
+impl fmt::Display for LineEndings {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let name = match self {
+			LineEndings::Crlf => "CRLF",
+			LineEndings::Lf => "LF",
+			LineEndings::Mixed => "mixed",
+			LineEndings::Unknown => "unknown",
+		};
+		write!(f, "{}", name)
+	}
+}
+
+// A gameinfo.txt shipped with the game
+#[derive(Debug, PartialEq, Eq)]
+pub struct KnownGameinfo {
+	// MD5 of the file as shipped
+	pub md5: &'static str,
+	// MD5 of its normalized contents (see `normalized_md5`), when it was recorded
+	pub normalized_md5: Option<&'static str>,
+	// Game version (PatchVersion of left4dead2/steam.inf) the file was found in
+	pub build: &'static str,
+	pub platform: &'static str,
+	pub line_endings: LineEndings,
+}
+
+impl KnownGameinfo {
+	pub fn describe(&self) -> String {
+		format!("build {}, {}, {} line endings", self.build, self.platform, self.line_endings)
+	}
+}
+
+// Add new entries when Valve ships a new gameinfo.txt: `doctor` prints both hashes and the line endings
+// of the current file. Files matching none are still recognised by their SearchPaths (see `check_vanilla`).
+pub static KNOWN_GAMEINFOS: [KnownGameinfo; 1] = [
+	// The file this tool was first written against. Only its MD5 was recorded, so it is only
+	// recognised byte for byte; its build, line endings and normalized MD5 are still to be filled in.
+	KnownGameinfo {
+		md5: "586b3b0b39bc44ddfb07792b1932c479",
+		normalized_md5: None,
+		build: "unknown",
+		platform: "Steam",
+		line_endings: LineEndings::Unknown,
+	},
+];
+
+// How a gameinfo.txt compares to the vanilla one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vanilla {
+	// Byte for byte one of `KNOWN_GAMEINFOS`
+	Exact(&'static KnownGameinfo),
+	// One of `KNOWN_GAMEINFOS` once line endings and trailing whitespace are ignored
+	Normalized(&'static KnownGameinfo),
+	// An unknown file, but with exactly the vanilla search paths and no addons (disabled ones included)
+	SearchPaths,
+	Modified,
+}
+
+impl Vanilla {
+	pub fn is_vanilla(&self) -> bool {
+		*self != Vanilla::Modified
+	}
+
+	pub fn describe(&self) -> String {
+		match self {
+			Vanilla::Exact(known) => format!("vanilla ({})", known.describe()),
+			Vanilla::Normalized(known) => format!("vanilla ({}, but different line endings or whitespace)", known.describe()),
+			Vanilla::SearchPaths => "vanilla search paths (unknown file version)".to_string(),
+			Vanilla::Modified => "modified".to_string(),
+		}
+	}
+}
+
+pub fn check_vanilla(path: &Path) -> Result<Vanilla, std::io::Error> {
+	Ok(check_vanilla_contents(&std::fs::read(path)?))
+}
+
+pub fn check_vanilla_contents(contents: &[u8]) -> Vanilla {
+	check_against(contents, &KNOWN_GAMEINFOS)
+}
+
+fn check_against(contents: &[u8], known_gameinfos: &'static [KnownGameinfo]) -> Vanilla {
+	let md5 = format!("{:x}", Md5::digest(contents));
+	if let Some(known) = known_gameinfos.iter().find(|known| known.md5 == md5) {
+		return Vanilla::Exact(known);
+	}
+	let normalized = normalized_md5(contents);
+	if let Some(known) = known_gameinfos.iter().find(|known| known.normalized_md5 == Some(normalized.as_str())) {
+		return Vanilla::Normalized(known);
+	}
+	// Parsed quietly, a file that cannot be parsed is simply not vanilla
+	let Some(doc) = std::str::from_utf8(contents).ok().and_then(|text| KvDocument::parse(text).ok()) else {
+		return Vanilla::Modified;
+	};
+	let gameinfo = Gameinfo { doc };
+	// The order of the vanilla search paths is not checked, only the set
+	let entries = gameinfo.game_entries();
+	let core_only = entries.len() == CORE_GAME_PATHS.len()
+		&& CORE_GAME_PATHS.iter().all(|core| entries.iter().any(|entry| entry.eq_ignore_ascii_case(core)));
+	if core_only && gameinfo.addon_states().is_empty() { Vanilla::SearchPaths } else { Vanilla::Modified }
+}
+
+// MD5 of the contents with LF line endings, no trailing whitespace on any line,
+// no byte order mark and no blank lines at the end
+pub fn normalized_md5(contents: &[u8]) -> String {
+	let contents = contents.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(contents);
+	let mut lines: Vec<&[u8]> = contents.split(|byte| *byte == b'\n').map(|line| line.trim_ascii_end()).collect();
+	while lines.last().is_some_and(|line| line.is_empty()) {
+		lines.pop();
+	}
+	let mut hasher = Md5::new();
+	for line in lines {
+		hasher.update(line);
+		hasher.update(b"\n");
+	}
+	format!("{:x}", hasher.finalize())
+}
+
+// A vanilla gameinfo.txt rebuilt from a modified one, for when there is no backup to restore:
+// every addon line, enabled or disabled, is removed and everything else is kept as is
+pub fn rebuild_vanilla(contents: &str) -> Result<String, EnforcerError> {
+	let mut gameinfo = Gameinfo::parse(contents)?;
+	for (addon, _) in gameinfo.addon_states() {
+		gameinfo.remove_addon(&addon);
+	}
+	let rebuilt = gameinfo.doc.to_string();
+	if !check_vanilla_contents(rebuilt.as_bytes()).is_vanilla() {
+		let err = format!(
+			"Unable to rebuild a vanilla gameinfo.txt, some of the game's own search paths are missing! \n\tExpected: {} \n\tVerify the integrity of the game files in Steam instead.",
+			CORE_GAME_PATHS.join(", ")
+		);
+		return Err(EnforcerError::Other(err));
+	}
+	Ok(rebuilt)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn line_endings() {
+		assert_eq!(LineEndings::of(b"a\r\nb\r\n"), LineEndings::Crlf);
+		assert_eq!(LineEndings::of(b"a\nb\n"), LineEndings::Lf);
+		assert_eq!(LineEndings::of(b"a\r\nb\n"), LineEndings::Mixed);
+		assert_eq!(LineEndings::of(b"a"), LineEndings::Unknown);
+	}
+
+	#[test]
+	fn normalized_md5_ignores_line_endings_and_trailing_whitespace() {
+		let lf = normalized_md5(b"\"GameInfo\"\n{\n\tgame\t\"L4D2\"\n}\n");
+		assert_eq!(normalized_md5(b"\xEF\xBB\xBF\"GameInfo\"\r\n{\r\n\tgame\t\"L4D2\" \r\n}\r\n\r\n"), lf);
+		assert_ne!(normalized_md5(b"\"GameInfo\"\n{\n\tgame\t\"L4D\"\n}\n"), lf);
+	}
+
+	const GAMEINFO: &str = "\"GameInfo\"\n{\n\tgame\t\"Left 4 Dead 2\"\n\tFileSystem\n\t{\n\t\tSteamAppId\t550\n\t\tSearchPaths\n\t\t{\n\t\t\tGame\tupdate\n\t\t\tGame\tleft4dead2_dlc3\n\t\t\tGame\tleft4dead2_dlc2\n\t\t\tGame\tleft4dead2_dlc1\n\t\t\tGame\t|gameinfo_path|.\n\t\t\tGame\thl2\n\t\t}\n\t}\n}\n";
+
+	// GAMEINFO as if it shipped with LF line endings on Linux and CRLF ones on Windows
+	static TEST_GAMEINFOS: [KnownGameinfo; 2] = [
+		KnownGameinfo {
+			md5: "7e3955d5846c061c04195e4f78e02e61",
+			normalized_md5: Some("7e3955d5846c061c04195e4f78e02e61"),
+			build: "test",
+			platform: "Linux",
+			line_endings: LineEndings::Lf,
+		},
+		KnownGameinfo {
+			md5: "ea88312a3f15d14eebcd06e12a293678",
+			normalized_md5: Some("7e3955d5846c061c04195e4f78e02e61"),
+			build: "test",
+			platform: "Windows",
+			line_endings: LineEndings::Crlf,
+		},
+	];
+
+	#[test]
+	fn known_files_match_exactly() {
+		assert_eq!(check_against(GAMEINFO.as_bytes(), &TEST_GAMEINFOS), Vanilla::Exact(&TEST_GAMEINFOS[0]));
+		let crlf = GAMEINFO.replace('\n', "\r\n");
+		assert_eq!(check_against(crlf.as_bytes(), &TEST_GAMEINFOS), Vanilla::Exact(&TEST_GAMEINFOS[1]));
+	}
+
+	#[test]
+	fn converted_line_endings_are_normalized() {
+		// CRLF -> LF on a Windows install, LF -> CRLF on a Linux one
+		let lf = GAMEINFO;
+		let crlf = GAMEINFO.replace('\n', "\r\n");
+		assert_eq!(check_against(lf.as_bytes(), &TEST_GAMEINFOS[1..]), Vanilla::Normalized(&TEST_GAMEINFOS[1]));
+		assert_eq!(check_against(crlf.as_bytes(), &TEST_GAMEINFOS[..1]), Vanilla::Normalized(&TEST_GAMEINFOS[0]));
+		let trailing = GAMEINFO.replace("\tGame\thl2\n", "\tGame\thl2 \t\n");
+		assert_eq!(check_against(trailing.as_bytes(), &TEST_GAMEINFOS), Vanilla::Normalized(&TEST_GAMEINFOS[0]));
+	}
+
+	#[test]
+	fn unknown_files_fall_back_to_the_search_paths() {
+		let unknown = GAMEINFO.replace("Left 4 Dead 2", "L4D2");
+		assert_eq!(check_against(unknown.as_bytes(), &TEST_GAMEINFOS), Vanilla::SearchPaths);
+		let modded = GAMEINFO.replace("\t\t\tGame\tupdate\n", "\t\t\tGame\tion_hud\n\t\t\tGame\tupdate\n");
+		assert_eq!(check_against(modded.as_bytes(), &TEST_GAMEINFOS), Vanilla::Modified);
+	}
+}
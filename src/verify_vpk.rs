@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: LGPL-3.0-only
+use crate::*;
+use crate::enforcer_error::EnforcerError;
+use crate::inspect_addon::{read_game, target_vpk};
+use crate::pack_addon::md5_of_range;
+use crate::vpk_getdata::{EMBEDDED_ARCHIVE, Vpk, chunk_path, entry_path, open_vpk, read_entry};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use serde::Serialize;
+use sourcepak::pak::v2::format::VPKArchiveMD5SectionEntry;
+use std::io::{Seek, SeekFrom};
+
+// Size of an archive MD5 section entry: archive index, offset, length and the 16 bytes MD5
+const ARCHIVE_MD5_ENTRY_SIZE: usize = 28;
+// Size of the other MD5 section: the MD5 of the tree, of the archive MD5 section and of the file
+const OTHER_MD5_SECTION_SIZE: usize = 48;
+
+// A damaged part of a VPK: a file it holds, or one of the v2 checksum sections
+#[derive(Debug, Clone, Serialize)]
+pub struct Corruption {
+	pub part: String,
+	pub problem: String,
+}
+
+// What verifying a VPK found
+#[derive(Debug, Clone, Serialize)]
+pub struct Verification {
+	pub vpk: PathBuf,
+	pub version: u32,
+	// Files checked against their CRC32
+	pub entries: usize,
+	// Checksums of the v2 MD5 sections checked
+	pub checksums: usize,
+	pub corrupt: Vec<Corruption>,
+}
+
+impl Verification {
+	pub fn is_intact(&self) -> bool {
+		self.corrupt.is_empty()
+	}
+
+	// The VpkCorrupt error listing the damaged parts, if there are any
+	pub fn into_result(self) -> Result<Verification, EnforcerError> {
+		if self.is_intact() {
+			return Ok(self);
+		}
+		let problems = self.corrupt.iter().map(|c| format!("{}: {}", c.part, c.problem)).collect();
+		Err(EnforcerError::VpkCorrupt { path: self.vpk, problems })
+	}
+}
+
+// Reads every file of a VPK and checks it against the CRC32 of its directory entry, then,
+// for version 2, the MD5 checksums of the chunks, of the directory tree and of the file.
+// A VPK that cannot even be opened is an error, damaged parts are listed in the result.
+pub fn verify_vpk(vpk_file: &Path, verbose: bool) -> Result<Verification, EnforcerError> {
+	let (mut file, vpk) = open_vpk(vpk_file)?;
+	let crc32 = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+	let mut verification = Verification {
+		vpk: vpk_file.to_path_buf(),
+		version: vpk.version,
+		entries: vpk.tree.files.len(),
+		checksums: 0,
+		corrupt: Vec::new(),
+	};
+
+	let mut keys: Vec<&String> = vpk.tree.files.keys().collect();
+	keys.sort();
+	for key in keys {
+		let expected = vpk.tree.files[key].crc;
+		let problem = match read_entry(vpk_file, &mut file, &vpk, key) {
+			Ok(data) => {
+				let crc = crc32.checksum(&data);
+				if crc == expected {
+					continue;
+				}
+				format!("CRC32 is {:08x} instead of {:08x}", crc, expected)
+			},
+			Err(EnforcerError::VpkParse { reason, .. }) => reason,
+			Err(e) => e.to_string(),
+		};
+		if var_os("DEBUG").is_some() || verbose {
+			log::debug!("{} is corrupt: {}", entry_path(key), problem);
+		}
+		verification.corrupt.push(Corruption { part: entry_path(key), problem });
+	}
+
+	if vpk.header_v2.is_some() {
+		verify_md5_sections(vpk_file, &mut file, &vpk, &mut verification)?;
+	}
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!(
+			"Verified {:?}: {} entries, {} checksums, {} problem(s)",
+			vpk_file,
+			verification.entries,
+			verification.checksums,
+			verification.corrupt.len(),
+		);
+	}
+	Ok(verification)
+}
+
+// The sections after the data of a v2 file: the MD5 of ranges of the chunks (archive MD5
+// section), then the MD5 of the tree, of the archive MD5 section and of the whole file up to there
+fn verify_md5_sections(
+	vpk_file: &Path,
+	file: &mut File,
+	vpk: &Vpk,
+	verification: &mut Verification,
+) -> Result<(), EnforcerError> {
+	let Some(header) = &vpk.header_v2 else {
+		return Ok(());
+	};
+	// open_vpk made sure every section is there
+	let archive_md5_start = vpk.data_offset() + header.file_data_section_size as u64;
+	let mut archive_md5_section = vec![0u8; header.archive_md5_section_size as usize];
+	file.seek(SeekFrom::Start(archive_md5_start))?;
+	file.read_exact(&mut archive_md5_section)?;
+	let mut other_md5_section = vec![0u8; header.other_md5_section_size as usize];
+	file.read_exact(&mut other_md5_section)?;
+
+	let mut check = |part: String, actual: Result<Vec<u8>, std::io::Error>, expected: &[u8]| {
+		verification.checksums += 1;
+		let problem = match actual {
+			Ok(actual) if actual == expected => return,
+			Ok(actual) => format!("MD5 is {} instead of {}", hex(&actual), hex(expected)),
+			Err(e) => format!("cannot be read ({})", e),
+		};
+		verification.corrupt.push(Corruption { part, problem });
+	};
+
+	for bytes in archive_md5_section.chunks_exact(ARCHIVE_MD5_ENTRY_SIZE) {
+		let field = |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+		let entry = VPKArchiveMD5SectionEntry {
+			archive_index: field(0),
+			starting_offset: field(4),
+			count: field(8),
+			md5_checksum: bytes[12..].to_vec(),
+		};
+		let range = format!("bytes {} to {}", entry.starting_offset, entry.starting_offset as u64 + entry.count as u64);
+		let (part, actual) = if entry.archive_index == EMBEDDED_ARCHIVE as u32 {
+			let start = vpk.data_offset() + entry.starting_offset as u64;
+			(format!("embedded data, {}", range), md5_of_range(vpk_file, start, entry.count as u64))
+		} else {
+			let chunk = u16::try_from(entry.archive_index).ok().and_then(|index| chunk_path(vpk_file, index));
+			match chunk {
+				Some(chunk) if chunk.is_file() => {
+					(format!("chunk {:03}, {}", entry.archive_index, range), md5_of_range(&chunk, entry.starting_offset as u64, entry.count as u64))
+				},
+				_ => {
+					let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "the chunk is missing");
+					(format!("chunk {:03}, {}", entry.archive_index, range), Err(missing))
+				},
+			}
+		};
+		check(part, actual, &entry.md5_checksum);
+	}
+
+	// Some tools leave the section out, its checksums cannot be checked then
+	match other_md5_section.len() {
+		0 => return Ok(()),
+		OTHER_MD5_SECTION_SIZE => {},
+		size => {
+			let problem = format!("is {} bytes instead of {}, its checksums cannot be checked", size, OTHER_MD5_SECTION_SIZE);
+			verification.corrupt.push(Corruption { part: "other checksums section".to_string(), problem });
+			return Ok(());
+		},
+	}
+	let tree_md5 = md5_of_range(vpk_file, vpk.header_size(), vpk.tree_size as u64);
+	check("directory tree checksum".to_string(), tree_md5, &other_md5_section[..16]);
+	let archive_md5_section_md5 = Ok(Md5::digest(&archive_md5_section).to_vec());
+	check("archive checksums section".to_string(), archive_md5_section_md5, &other_md5_section[16..32]);
+	// Everything before the file checksum, the other two checksums included
+	let file_md5 = md5_of_range(vpk_file, 0, archive_md5_start + header.archive_md5_section_size as u64 + 32);
+	check("file checksum".to_string(), file_md5, &other_md5_section[32..]);
+	Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The verification of a target, a damaged VPK being a `VpkCorrupt` error
+pub type VerifyOutcome = Result<Verification, EnforcerError>;
+
+// Verifies each target: an installed addon name or a path to a VPK file.
+// Without targets, every installed addon is verified. `game_dir` is only needed for addons.
+pub fn verify_addons(game_dir: Option<&Path>, targets: &[String], verbose: bool) -> Result<Vec<(String, VerifyOutcome)>, EnforcerError> {
+	let game = read_game(game_dir, verbose)?;
+	let targets: Vec<String> = match &game {
+		Some((_, gameinfo)) if targets.is_empty() => gameinfo.addon_states().into_iter().map(|(addon, _)| addon).collect(),
+		_ => targets.to_vec(),
+	};
+	Ok(targets
+		.into_iter()
+		.map(|target| {
+			let outcome = target_vpk(&target, game.as_ref())
+				.and_then(|vpk_file| verify_vpk(&vpk_file, verbose))
+				.and_then(|verification| verification.into_result());
+			(target, outcome)
+		})
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_addon::write_vpk;
+	use tempfile::TempDir;
+
+	// A loose addon packed into addon.vpk, in a temporary directory
+	fn packed(v2: bool) -> (TempDir, PathBuf) {
+		let temp = tempfile::tempdir().unwrap();
+		let addon = temp.path().join("addon");
+		create_dir_all(addon.join("scripts/vscripts")).unwrap();
+		std::fs::write(addon.join("addoninfo.txt"), "\"AddonInfo\"\n{\n\taddontitle \"Test\"\n}\n").unwrap();
+		std::fs::write(addon.join("scripts/vscripts/test.nut"), "printl(\"test\")\n").unwrap();
+		let vpk = temp.path().join("addon.vpk");
+		assert_eq!(write_vpk(&addon, &vpk, v2, false).unwrap(), 2);
+		(temp, vpk)
+	}
+
+	fn flip_byte(path: &Path, at: u64) {
+		let mut contents = std::fs::read(path).unwrap();
+		contents[at as usize] ^= 0xff;
+		std::fs::write(path, contents).unwrap();
+	}
+
+	#[test]
+	fn packed_v1_is_intact() {
+		let (_temp, vpk) = packed(false);
+		let verification = verify_vpk(&vpk, false).unwrap();
+		assert_eq!((verification.version, verification.entries, verification.checksums), (1, 2, 0));
+		assert!(verification.is_intact());
+	}
+
+	#[test]
+	fn packed_v2_is_intact() {
+		let (_temp, vpk) = packed(true);
+		let verification = verify_vpk(&vpk, false).unwrap();
+		assert_eq!((verification.version, verification.entries, verification.checksums), (2, 2, 3));
+		assert!(verification.is_intact());
+	}
+
+	#[test]
+	fn damaged_file_fails_its_crc() {
+		let (_temp, vpk) = packed(false);
+		let (_, parsed) = open_vpk(&vpk).unwrap();
+		// The first byte of the data section belongs to the first file
+		flip_byte(&vpk, parsed.data_offset());
+		let verification = verify_vpk(&vpk, false).unwrap();
+		assert_eq!(verification.corrupt.len(), 1);
+		assert!(verification.corrupt[0].problem.starts_with("CRC32 is"));
+		assert!(matches!(verification.into_result(), Err(EnforcerError::VpkCorrupt { .. })));
+	}
+
+	#[test]
+	fn damaged_checksum_is_reported() {
+		let (_temp, vpk) = packed(true);
+		// The last byte of the file checksum
+		flip_byte(&vpk, std::fs::metadata(&vpk).unwrap().len() - 1);
+		let verification = verify_vpk(&vpk, false).unwrap();
+		assert_eq!(verification.corrupt.len(), 1);
+		assert_eq!(verification.corrupt[0].part, "file checksum");
+		assert!(verification.corrupt[0].problem.starts_with("MD5 is"));
+	}
+
+	// Cuts the other MD5 section down to `size` bytes
+	fn shrink_other_md5_section(vpk: &Path, size: usize) {
+		let mut contents = std::fs::read(vpk).unwrap();
+		contents.truncate(contents.len() - OTHER_MD5_SECTION_SIZE + size);
+		// OtherMD5SectionSize in the header
+		contents[20..24].copy_from_slice(&(size as u32).to_le_bytes());
+		std::fs::write(vpk, contents).unwrap();
+	}
+
+	#[test]
+	fn missing_other_md5_section_is_skipped() {
+		let (_temp, vpk) = packed(true);
+		shrink_other_md5_section(&vpk, 0);
+		let verification = verify_vpk(&vpk, false).unwrap();
+		assert_eq!(verification.checksums, 0);
+		assert!(verification.is_intact());
+	}
+
+	#[test]
+	fn odd_other_md5_section_is_reported() {
+		let (_temp, vpk) = packed(true);
+		shrink_other_md5_section(&vpk, 32);
+		let verification = verify_vpk(&vpk, false).unwrap();
+		assert_eq!(verification.corrupt.len(), 1);
+		assert_eq!(verification.corrupt[0].part, "other checksums section");
+	}
+}
@@ -2,117 +2,689 @@
 
 #![allow(non_snake_case)]
 use sourcepak::common::file::VPKFileReader;
-use sourcepak::{
-	common::format::{PakReader, VPKDirectoryEntry},
-	pak::v1::format::VPKVersion1,
-};
+use sourcepak::common::format::{VPK_ENTRY_TERMINATOR, VPKDirectoryEntry, VPKTree};
+use sourcepak::pak::v1::format::{VPK_SIGNATURE_V1, VPK_VERSION_V1};
+use sourcepak::pak::v2::format::{VPK_VERSION_V2, VPKHeaderV2};
+use std::collections::BTreeSet;
 use std::env::var_os;
-use std::{fs::File, io::{Seek, SeekFrom}, path::Path, error::Error};
-use regex::Regex;
+use std::{fs::File, io::{Read, Seek, SeekFrom}, path::{Path, PathBuf}};
+use crate::enforcer_error::EnforcerError;
+use crate::keyvalues::{KvDocument, KvError, KvValue};
+use serde::{Deserialize, Serialize};
 
-pub struct ExtractedData {
-	pub title: String,
-	pub version: String,
-	pub description: String,
+// Everything an addon tells about itself in its addoninfo.txt
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddonInfo {
+	pub title: Option<String>,
+	pub version: Option<String>,
+	pub tagline: Option<String>,
+	pub author: Option<String>,
+	pub author_steam_id: Option<String>,
+	pub steam_group_name: Option<String>,
+	pub steam_app_id: Option<u32>,
+	pub description: Option<String>,
+	// addonURL0, addonURL1...
+	pub urls: Vec<String>,
+	pub content: AddonContent,
+	// Any other key, as written in the file
+	pub other: Vec<(String, String)>,
+}
+
+// The addonContent_* flags, telling what kind of content the addon brings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddonContent {
+	pub campaign: bool,
+	pub map: bool,
+	pub survivor: bool,
+	pub boss_infected: bool,
+	pub common_infected: bool,
+	pub weapon: bool,
+	pub weapon_model: bool,
+	pub prop: bool,
+	pub prefab: bool,
+	pub skin: bool,
+	pub spray: bool,
+	pub script: bool,
+	pub sound: bool,
+	pub music: bool,
+	pub background_movie: bool,
+}
+
+impl AddonContent {
+	// The flag for an "addonContent_<kind>" key (the kind lowercased)
+	fn flag_mut(&mut self, kind: &str) -> Option<&mut bool> {
+		match kind {
+			"campaign" => Some(&mut self.campaign),
+			"map" => Some(&mut self.map),
+			"survivor" => Some(&mut self.survivor),
+			"bossinfected" => Some(&mut self.boss_infected),
+			"commoninfected" => Some(&mut self.common_infected),
+			"weapon" => Some(&mut self.weapon),
+			"weaponmodel" => Some(&mut self.weapon_model),
+			"prop" => Some(&mut self.prop),
+			"prefab" => Some(&mut self.prefab),
+			"skin" => Some(&mut self.skin),
+			"spray" => Some(&mut self.spray),
+			"script" => Some(&mut self.script),
+			"sound" => Some(&mut self.sound),
+			"music" => Some(&mut self.music),
+			"backgroundmovie" => Some(&mut self.background_movie),
+			_ => None,
+		}
+	}
+
+	// Names of the kinds of content the addon has
+	pub fn kinds(&self) -> Vec<&'static str> {
+		[
+			("Campaign", self.campaign),
+			("Map", self.map),
+			("Survivor", self.survivor),
+			("Boss Infected", self.boss_infected),
+			("Common Infected", self.common_infected),
+			("Weapon", self.weapon),
+			("Weapon Model", self.weapon_model),
+			("Prop", self.prop),
+			("Prefab", self.prefab),
+			("Skin", self.skin),
+			("Spray", self.spray),
+			("Script", self.script),
+			("Sound", self.sound),
+			("Music", self.music),
+			("Background Movie", self.background_movie),
+		]
+		.into_iter()
+		.filter_map(|(name, set)| set.then_some(name))
+		.collect()
+	}
+}
+
+impl AddonInfo {
+	pub fn parse(text: &str) -> Result<AddonInfo, KvError> {
+		let doc = KvDocument::parse(text)?;
+		// Everything is normally inside an "AddonInfo" block, but some files skip it
+		let block = doc.root.pairs().find_map(|pair| pair.value.as_block()).unwrap_or(&doc.root);
+
+		let mut info = AddonInfo::default();
+		let mut urls: Vec<(u32, String)> = Vec::new();
+		for pair in block.pairs() {
+			let KvValue::Str(value) = &pair.value else {
+				continue;
+			};
+			// Descriptions often quote something, as \"...\"
+			let value = value.unescaped().trim().to_string();
+			let key = pair.key.text.to_lowercase();
+			match key.as_str() {
+				"addontitle" => info.title = Some(value),
+				"addonversion" => info.version = Some(value),
+				"addontagline" => info.tagline = Some(value),
+				"addonauthor" => info.author = Some(value),
+				"addonauthorsteamid" => info.author_steam_id = Some(value),
+				"addonsteamgroupname" => info.steam_group_name = Some(value),
+				"addonsteamappid" => info.steam_app_id = value.parse().ok(),
+				"addondescription" => info.description = Some(value),
+				_ => {
+					if let Some(Ok(index)) = key.strip_prefix("addonurl").map(str::parse::<u32>) {
+						urls.push((index, value));
+					} else if let Some(flag) = key.strip_prefix("addoncontent_").and_then(|kind| info.content.flag_mut(kind)) {
+						*flag = value.parse::<i64>().map_or(value.eq_ignore_ascii_case("true"), |n| n != 0);
+					} else {
+						info.other.push((pair.key.text.clone(), value));
+					}
+				},
+			}
+		}
+		urls.sort_by_key(|(index, _)| *index);
+		info.urls = urls.into_iter().map(|(_, url)| url).filter(|url| !url.is_empty()).collect();
+		Ok(info)
+	}
+
+	// The fields set in addoninfo.txt as (label, value), in a readable order
+	pub fn fields(&self) -> Vec<(String, String)> {
+		let mut fields = Vec::new();
+		let known = [
+			("Title", &self.title),
+			("Version", &self.version),
+			("Tagline", &self.tagline),
+			("Author", &self.author),
+			("Author Steam ID", &self.author_steam_id),
+			("Steam group", &self.steam_group_name),
+		];
+		for (label, value) in known {
+			if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+				fields.push((label.to_string(), value.clone()));
+			}
+		}
+		if let Some(app_id) = self.steam_app_id {
+			fields.push(("Steam App ID".to_string(), app_id.to_string()));
+		}
+		for url in &self.urls {
+			fields.push(("URL".to_string(), url.clone()));
+		}
+		let kinds = self.content.kinds();
+		if !kinds.is_empty() {
+			fields.push(("Content".to_string(), kinds.join(", ")));
+		}
+		if let Some(description) = self.description.as_ref().filter(|v| !v.is_empty()) {
+			fields.push(("Description".to_string(), description.clone()));
+		}
+		for (key, value) in &self.other {
+			fields.push((key.clone(), value.clone()));
+		}
+		fields
+	}
 }
 
 //read_single_file_vpk_v1
 pub fn main(
 	addon_file: &String,
 	verbose: bool,
-) -> Result<ExtractedData, Box<dyn std::error::Error>> {
+) -> Result<AddonInfo, EnforcerError> {
 	let path = Path::new(addon_file);
-	let mut file = File::open(path)?;
-	let vpk = VPKVersion1::try_from(&mut file).expect("Failed to read VPK file");
+	let (mut file, vpk) = open_vpk(path)?;
 
 	// Need to know the number of entries (files and directories) in the vpk?
 	if var_os("DEBUG").is_some() || verbose {
-		let len = vpk.tree.files.len();
-		println!("{:?}", len);
+		log::debug!("{:?} holds {} entries", path, vpk.tree.files.len());
 	}
 
 	// The key for "addoninfo.txt" at the root of the VPK is likely " /addoninfo.txt"
 	// because the root path is a space, and sourcepak builds keys as "{path}/{file_name}.{extension}".
-	let addoninfo_key = " /addoninfo.txt";
-	let entry = vpk.tree.files.get(addoninfo_key);
+	let addoninfo_key = vpk
+		.tree
+		.files
+		.keys()
+		.find(|key| key.eq_ignore_ascii_case(" /addoninfo.txt"))
+		.cloned()
+		.unwrap_or_default();
+	let entry = vpk.tree.files.get(&addoninfo_key);
 	// Print out the addoninfo
 	if var_os("DEBUG").is_some() || verbose {
-		println!("{:?}", entry);
-	}
-
-	let archive_dir = path.parent().unwrap_or_else(|| Path::new(".")).to_string_lossy();
-	let vpk_name = path.file_stem().unwrap_or_default().to_string_lossy();
-	let base_vpk_name = vpk_name.strip_suffix("_dir").unwrap_or(&vpk_name);
-
-	// The sourcepak::read_file function fails silently by returning None, likely due to an
-	// I/O error after an incorrect seek. We can work around this by manually reading the
-	// data for entries stored in the main _dir.vpk file (archive_index == 0x7FFF).
-	let test_file = entry.and_then(|e| {
-		if e.archive_index == 0x7FFF {
-			// For VPK v1, the tree starts immediately after the header. The data block for
-			// embedded files starts immediately after the tree. The header's size is the
-			// tree's offset from the start of the file.
-			let tree_offset = std::mem::size_of_val(&vpk.header) as u64;
-			let seek_pos = tree_offset + vpk.header.tree_size as u64 + e.entry_offset as u64;
-			file.seek(SeekFrom::Start(seek_pos)).ok()?;
-			file.read_bytes(e.entry_length as usize).ok()
-		} else {
-			// Fallback to sourcepak for other archive types (e.g., pak01_001.vpk)
-			vpk.read_file(&archive_dir.to_string(), &base_vpk_name.to_string(), &addoninfo_key.to_string())
-		}
-	});
-
-	// Convert the bytes to a string, handling potential UTF-8 errors,
-	// and return an error if the file content could not be read.
-	let content = test_file
-		.map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
-		.ok_or_else(|| {
-			Box::<dyn Error>::from(format!("Failed to read addoninfo.txt content from VPK: {}", addon_file))
+		log::debug!("addoninfo.txt entry: {:?}", entry);
+	}
+
+	if entry.is_none() {
+		return Err(EnforcerError::VpkParse { path: path.to_path_buf(), reason: "it has no addoninfo.txt".to_string() });
+	}
+	let bytes = read_entry(path, &mut file, &vpk, &addoninfo_key)?;
+	// Convert the bytes to a string, handling potential UTF-8 errors
+	let content = String::from_utf8_lossy(&bytes).into_owned();
+
+	let info = AddonInfo::parse(&content).map_err(|e| EnforcerError::VpkParse {
+		path: path.to_path_buf(),
+		reason: format!("unable to parse addoninfo.txt ({})", e),
+	})?;
+	if var_os("DEBUG").is_some() || verbose {
+		log::debug!("{:?}", info);
+	}
+	Ok(info)
+}
+
+pub const HEADER_SIZE_V1: u64 = 12;
+pub const HEADER_SIZE_V2: u64 = 28;
+
+// The archive index of data stored in the _dir.vpk itself, right after the directory tree
+pub const EMBEDDED_ARCHIVE: u16 = 0x7FFF;
+
+// The header and directory tree of a VPK. L4D2 itself reads version 1 files, but the tools of
+// the later Source games write version 2, which adds checksum and signature sections after the data.
+pub struct Vpk {
+	pub version: u32,
+	pub tree_size: u32,
+	// Only for version 2
+	pub header_v2: Option<VPKHeaderV2>,
+	pub tree: VPKTree<VPKDirectoryEntry>,
+}
+
+impl Vpk {
+	pub fn header_size(&self) -> u64 {
+		if self.version == VPK_VERSION_V2 { HEADER_SIZE_V2 } else { HEADER_SIZE_V1 }
+	}
+
+	// Where the data of the entries embedded in the _dir.vpk starts
+	pub fn data_offset(&self) -> u64 {
+		self.header_size() + self.tree_size as u64
+	}
+
+	// Where the data of the embedded entries ends: the end of a version 1 file, or of the data
+	// section of a version 2 one
+	pub fn data_end(&self, file_size: u64) -> u64 {
+		match &self.header_v2 {
+			Some(header) => self.data_offset() + header.file_data_section_size as u64,
+			None => file_size,
+		}
+	}
+}
+
+// Opens a VPK and reads its header and directory tree. Anything that is not a VPK, or
+// a damaged one, is a VpkParse error telling what is wrong and where.
+pub fn open_vpk(path: &Path) -> Result<(File, Vpk), EnforcerError> {
+	let parse_error = |reason: String| EnforcerError::VpkParse { path: path.to_path_buf(), reason };
+	let mut file = File::open(path)?;
+	let file_size = file.metadata()?.len();
+
+	let mut start = [0u8; 8];
+	if file_size < HEADER_SIZE_V1 {
+		return Err(parse_error(format!("the file is only {} bytes, too small for a VPK header", file_size)));
+	}
+	file.read_exact(&mut start)?;
+	let signature = u32::from_le_bytes([start[0], start[1], start[2], start[3]]);
+	let version = u32::from_le_bytes([start[4], start[5], start[6], start[7]]);
+	if signature != VPK_SIGNATURE_V1 {
+		return Err(parse_error(format!(
+			"not a VPK file (it starts with {:#010x} instead of the VPK signature {:#010x})",
+			signature, VPK_SIGNATURE_V1
+		)));
+	}
+
+	let (tree_size, header_v2) = match version {
+		VPK_VERSION_V1 => (file.read_u32()?, None),
+		VPK_VERSION_V2 => {
+			if file_size < HEADER_SIZE_V2 {
+				return Err(parse_error(format!(
+					"the file is truncated: a VPK v2 header is {} bytes, but the file is only {}",
+					HEADER_SIZE_V2, file_size
+				)));
+			}
+			// Read field by field: sourcepak rejects files without the other MD5 section,
+			// which `verify_vpk` reports instead
+			let header = VPKHeaderV2 {
+				signature,
+				version,
+				tree_size: file.read_u32()?,
+				file_data_section_size: file.read_u32()?,
+				archive_md5_section_size: file.read_u32()?,
+				other_md5_section_size: file.read_u32()?,
+				signature_section_size: file.read_u32()?,
+			};
+			if header.archive_md5_section_size % 28 != 0 {
+				return Err(parse_error(format!(
+					"bad VPK v2 header (the archive MD5 section is {} bytes, not a multiple of 28)",
+					header.archive_md5_section_size
+				)));
+			}
+			(header.tree_size, Some(header))
+		},
+		_ => return Err(parse_error(format!("unsupported VPK version {} (only versions 1 and 2 are supported)", version))),
+	};
+	let header_size = if header_v2.is_some() { HEADER_SIZE_V2 } else { HEADER_SIZE_V1 };
+
+	if header_size + tree_size as u64 > file_size {
+		return Err(parse_error(format!(
+			"the header gives a directory tree of {} bytes, but only {} bytes follow the header (truncated file or bad tree size)",
+			tree_size,
+			file_size - header_size
+		)));
+	}
+	let mut tree_bytes = vec![0u8; tree_size as usize];
+	file.seek(SeekFrom::Start(header_size))?;
+	file.read_exact(&mut tree_bytes)?;
+	let tree = parse_tree(&tree_bytes, header_size).map_err(parse_error)?;
+	let vpk = Vpk { version, tree_size, header_v2, tree };
+
+	// Every section of a v2 file has its size in the header
+	if let Some(header) = &vpk.header_v2 {
+		let expected = vpk.data_offset()
+			+ header.file_data_section_size as u64
+			+ header.archive_md5_section_size as u64
+			+ header.other_md5_section_size as u64
+			+ header.signature_section_size as u64;
+		if expected > file_size {
+			return Err(parse_error(format!(
+				"the file is truncated: its header describes {} bytes, but the file is only {}",
+				expected, file_size
+			)));
+		}
+	}
+	// The data embedded after the tree has to be there
+	let data_end = vpk.data_end(file_size);
+	for (key, entry) in &vpk.tree.files {
+		if entry.archive_index == EMBEDDED_ARCHIVE && entry.entry_length > 0 {
+			let entry_end = vpk.data_offset() + entry.entry_offset as u64 + entry.entry_length as u64;
+			if entry_end > data_end {
+				return Err(parse_error(format!(
+					"the file is truncated: the data of {} ends at byte {}, past the end of the data at byte {}",
+					entry_path(key),
+					entry_end,
+					data_end
+				)));
+			}
+		}
+	}
+	Ok((file, vpk))
+}
+
+// Reads the directory tree: for each extension, for each directory, for each file name,
+// a directory entry followed by its preload bytes. Each list ends with an empty string.
+// `offset` is where the tree starts in the file, so that errors tell the byte in the file.
+fn parse_tree(bytes: &[u8], offset: u64) -> Result<VPKTree<VPKDirectoryEntry>, String> {
+	let mut tree = VPKTree::new();
+	let mut pos = 0;
+	loop {
+		let extension = tree_string(bytes, &mut pos, offset)?;
+		if extension.is_empty() {
+			break;
+		}
+		loop {
+			let dir = tree_string(bytes, &mut pos, offset)?;
+			if dir.is_empty() {
+				break;
+			}
+			loop {
+				let file_name = tree_string(bytes, &mut pos, offset)?;
+				if file_name.is_empty() {
+					break;
+				}
+				let key = format!("{}/{}.{}", dir, file_name, extension);
+				let at = offset + pos as u64;
+				let fields = tree_bytes(bytes, &mut pos, 18, offset).map_err(|e| format!("{} of {}", e, entry_path(&key)))?;
+				let entry = VPKDirectoryEntry {
+					crc: u32::from_le_bytes([fields[0], fields[1], fields[2], fields[3]]),
+					preload_length: u16::from_le_bytes([fields[4], fields[5]]),
+					archive_index: u16::from_le_bytes([fields[6], fields[7]]),
+					entry_offset: u32::from_le_bytes([fields[8], fields[9], fields[10], fields[11]]),
+					entry_length: u32::from_le_bytes([fields[12], fields[13], fields[14], fields[15]]),
+					terminator: u16::from_le_bytes([fields[16], fields[17]]),
+				};
+				if entry.terminator != VPK_ENTRY_TERMINATOR {
+					return Err(format!(
+						"the directory entry of {} at byte {} is damaged (it ends with {:#06x} instead of {:#06x})",
+						entry_path(&key),
+						at,
+						entry.terminator,
+						VPK_ENTRY_TERMINATOR
+					));
+				}
+				if entry.preload_length > 0 {
+					let preload = tree_bytes(bytes, &mut pos, entry.preload_length as usize, offset)
+						.map_err(|e| format!("{} of the preload data of {}", e, entry_path(&key)))?;
+					tree.preload.insert(key.clone(), preload.to_vec());
+				}
+				tree.files.insert(key, entry);
+			}
+		}
+	}
+	if pos != bytes.len() {
+		return Err(format!(
+			"bad tree size: the directory tree ends at byte {}, but the header says it ends at byte {}",
+			offset + pos as u64,
+			offset + bytes.len() as u64
+		));
+	}
+	Ok(tree)
+}
+
+// A null-terminated string of the directory tree
+fn tree_string(bytes: &[u8], pos: &mut usize, offset: u64) -> Result<String, String> {
+	let at = offset + *pos as u64;
+	let rest = &bytes[*pos..];
+	let Some(len) = rest.iter().position(|b| *b == 0) else {
+		return Err(format!(
+			"bad tree size: the directory tree is cut off at byte {}, in the middle of a name",
+			offset + bytes.len() as u64
+		));
+	};
+	let text = std::str::from_utf8(&rest[..len]).map_err(|_| format!("the directory tree has a name that is not UTF-8 at byte {}", at))?;
+	*pos += len + 1;
+	Ok(text.to_string())
+}
+
+fn tree_bytes<'a>(bytes: &'a [u8], pos: &mut usize, count: usize, offset: u64) -> Result<&'a [u8], String> {
+	let Some(slice) = bytes.get(*pos..*pos + count) else {
+		return Err(format!("bad tree size: the directory tree is cut off at byte {}, in the middle", offset + bytes.len() as u64));
+	};
+	*pos += count;
+	Ok(slice)
+}
+
+// The plain path of a directory tree key: " /addoninfo.txt" -> "addoninfo.txt",
+// "scripts/foo. " (no extension) -> "scripts/foo"
+pub fn entry_path(key: &str) -> String {
+	let path = key.strip_prefix(" /").unwrap_or(key);
+	path.strip_suffix(". ").unwrap_or(path).to_string()
+}
+
+// Reads the full contents of a VPK entry: its preload bytes (kept in the directory tree),
+// followed by the rest of the data, either embedded in the _dir.vpk or in a numbered chunk.
+//
+// The sourcepak::read_file function fails silently by returning None, likely due to an
+// I/O error after an incorrect seek (it expects the embedded index to be 0xFF7F),
+// so the data is read manually here.
+pub fn read_entry(path: &Path, file: &mut File, vpk: &Vpk, key: &str) -> Result<Vec<u8>, EnforcerError> {
+	let parse_error = |reason: String| EnforcerError::VpkParse { path: path.to_path_buf(), reason };
+	let entry = vpk.tree.files.get(key).ok_or_else(|| parse_error(format!("there is no {} in it", entry_path(key))))?;
+	let mut data = match entry.preload_length {
+		0 => Vec::new(),
+		_ => vpk.tree.preload.get(key).cloned().unwrap_or_default(),
+	};
+	if entry.entry_length == 0 {
+		return Ok(data);
+	}
+	// The length is only allocated once the data is known to be there, so that a damaged
+	// entry cannot claim gigabytes
+	if entry.archive_index == EMBEDDED_ARCHIVE {
+		// The data block for embedded files starts immediately after the tree.
+		// open_vpk checked it already, but the file may have changed since.
+		let entry_start = vpk.data_offset() + entry.entry_offset as u64;
+		let entry_end = entry_start + entry.entry_length as u64;
+		let data_end = vpk.data_end(file.metadata()?.len());
+		if entry_end > data_end {
+			return Err(parse_error(format!(
+				"the file is truncated: the data of {} ends at byte {}, past the end of the data at byte {}",
+				entry_path(key),
+				entry_end,
+				data_end
+			)));
+		}
+		let mut rest = vec![0u8; entry.entry_length as usize];
+		file.seek(SeekFrom::Start(entry_start))?;
+		file.read_exact(&mut rest)?;
+		data.extend(rest);
+	} else {
+		let chunk = chunk_path(path, entry.archive_index).ok_or_else(|| {
+			parse_error(format!(
+				"{} is stored in chunk {}, but the VPK is not named <name>_dir.vpk, so there are no chunks",
+				entry_path(key),
+				entry.archive_index
+			))
 		})?;
+		let mut chunk_file = File::open(&chunk).map_err(|e| {
+			parse_error(format!("{} is stored in chunk {}, which cannot be opened ({})", entry_path(key), chunk.display(), e))
+		})?;
+		let entry_end = entry.entry_offset as u64 + entry.entry_length as u64;
+		let chunk_size = chunk_file.metadata()?.len();
+		if entry_end > chunk_size {
+			return Err(parse_error(format!(
+				"chunk {} is truncated: the data of {} ends at byte {}, but the chunk is only {} bytes",
+				chunk.display(),
+				entry_path(key),
+				entry_end,
+				chunk_size
+			)));
+		}
+		let mut rest = vec![0u8; entry.entry_length as usize];
+		chunk_file.seek(SeekFrom::Start(entry.entry_offset as u64))?;
+		chunk_file.read_exact(&mut rest)?;
+		data.extend(rest);
+	}
+	Ok(data)
+}
+
+// "pak01" for ".../pak01_dir.vpk"
+fn vpk_base_name(dir_vpk: &Path) -> Option<String> {
+	let stem = dir_vpk.file_stem()?.to_string_lossy();
+	let base = stem.strip_suffix("_dir").or_else(|| stem.strip_suffix("_DIR"))?;
+	Some(base.to_string())
+}
+
+// Path of the `<name>_NNN.vpk` chunk belonging to `<name>_dir.vpk`
+pub fn chunk_path(dir_vpk: &Path, index: u16) -> Option<PathBuf> {
+	let base = vpk_base_name(dir_vpk)?;
+	Some(dir_vpk.with_file_name(format!("{}_{:03}.vpk", base, index)))
+}
+
+// Parses the chunk index out of a file name like "pak01_003.vpk", given the base name "pak01"
+fn chunk_index(file_name: &str, base: &str) -> Option<u16> {
+	let lower = file_name.to_lowercase();
+	let digits = lower.strip_prefix(&base.to_lowercase())?.strip_prefix('_')?.strip_suffix(".vpk")?;
+	if digits.len() < 3 || !digits.chars().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+	digits.parse().ok()
+}
+
+// The numbered chunk files found next to a `<name>_dir.vpk`, as (archive index, path).
+// A VPK not named `*_dir.vpk` has no chunks.
+pub fn vpk_chunks(dir_vpk: &Path) -> Result<Vec<(u16, PathBuf)>, std::io::Error> {
+	let Some(base) = vpk_base_name(dir_vpk) else {
+		return Ok(Vec::new());
+	};
+	let parent = dir_vpk.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+	let mut chunks = Vec::new();
+	for dir_entry in std::fs::read_dir(parent)? {
+		let chunk = dir_entry?.path();
+		let file_name = chunk.file_name().unwrap_or_default().to_string_lossy().to_string();
+		if let Some(index) = chunk_index(&file_name, &base) {
+			if chunk.is_file() {
+				chunks.push((index, chunk));
+			}
+		}
+	}
+	chunks.sort();
+	Ok(chunks)
+}
+
+// For a `<name>_NNN.vpk` chunk, the `<name>_dir.vpk` next to it (if there is one)
+pub fn dir_vpk_of_chunk(chunk: &Path) -> Option<PathBuf> {
+	let stem = chunk.file_stem()?.to_string_lossy();
+	let (base, digits) = stem.rsplit_once('_')?;
+	if digits.len() < 3 || !digits.chars().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+	let dir_vpk = chunk.with_file_name(format!("{}_dir.vpk", base));
+	dir_vpk.is_file().then_some(dir_vpk)
+}
+
+// Every chunk (archive index) the directory tree stores file data in
+pub fn archive_indices(addon_file: &Path) -> Result<BTreeSet<u16>, EnforcerError> {
+	let (_, vpk) = open_vpk(addon_file)?;
+	Ok(vpk
+		.tree
+		.files
+		.values()
+		.filter(|e| e.entry_length > 0 && e.archive_index != EMBEDDED_ARCHIVE)
+		.map(|e| e.archive_index)
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_addon::write_vpk;
+	use tempfile::TempDir;
 
-	// Initialize variables to store the extracted data
-	let mut title: Option<String> = None;
-	let mut version: Option<String> = None;
-	let mut description: Option<String> = None;
-
-	// Iterate over each line of the content and extract the desired fields
-	for line in content.lines() {
-		if let Some(val) = extract_value(line, "addonTitle") {
-			title = Some(val);
-		} else if let Some(val) = extract_value(line, "addonVersion") {
-			version = Some(val);
-		} else if let Some(val) = extract_value(line, "addonDescription") {
-			description = Some(val);
-		}
-	}
-
-	// Print the extracted information
-	// if var_os("DEBUG").is_some() || verbose {
-	// println!("Addon Title: {}", title.clone().unwrap_or_else(|| "N/A".to_string()));
-	// println!("Addon Version: {}", version.clone().unwrap_or_else(|| "N/A".to_string()));
-	// println!("Addon Description: {}", description.clone().unwrap_or_else(|| "N/A".to_string()));
-	// }
-
-	Ok(ExtractedData{
-		title: title.unwrap_or_else(|| "N/A".to_string()).to_string(),
-		version: version.unwrap_or_else(|| "N/A".to_string()).to_string(),
-		description: description.unwrap_or_else(|| "N/A".to_string()).to_string(),
-	})
-}
-
-// Helper function to extract the string value from a KeyValue formatted line.
-// It uses a regular expression for case-insensitive key matching.
-fn extract_value(line: &str, key: &str) -> Option<String> {
-	// The regex looks for a key (case-insensitive) followed by a value, all in quotes.
-	// It handles both quoted ("addonTitle") and unquoted (addonTitle) keys.
-	// The (?i) flag makes the key match case-insensitive.
-	// \s+ matches one or more whitespace characters between key and value.
-	// "([^"]*)" captures the value string.
-	let pattern = format!(r#"^\s*(?i)(?:"{0}"|{0})\s+"([^"]*)""#, regex::escape(key));
-	// It's safe to unwrap here as we control the regex string.
-	let re = Regex::new(&pattern).unwrap();
-
-	// `captures` returns an Option, and we can map over it.
-	re.captures(line)
-		.and_then(|caps| caps.get(1).map(|m| m.as_str().to_string()))
-}
\ No newline at end of file
+	// A directory tree holding "scripts/test.nut", 4 bytes embedded at offset 0
+	fn tree(terminator: u16) -> Vec<u8> {
+		let mut bytes = b"nut\0scripts\0test\0".to_vec();
+		bytes.extend(0x1234_5678u32.to_le_bytes());
+		bytes.extend(0u16.to_le_bytes());
+		bytes.extend(EMBEDDED_ARCHIVE.to_le_bytes());
+		bytes.extend(0u32.to_le_bytes());
+		bytes.extend(4u32.to_le_bytes());
+		bytes.extend(terminator.to_le_bytes());
+		bytes.extend(b"\0\0\0");
+		bytes
+	}
+
+	fn tree_error(bytes: &[u8]) -> String {
+		match parse_tree(bytes, 12) {
+			Err(reason) => reason,
+			Ok(..) => panic!("expected a bad directory tree"),
+		}
+	}
+
+	fn parse_error(result: Result<(File, Vpk), EnforcerError>) -> String {
+		match result {
+			Err(EnforcerError::VpkParse { reason, .. }) => reason,
+			Err(e) => panic!("expected a VPK parse error, got {}", e),
+			Ok(..) => panic!("expected a VPK parse error"),
+		}
+	}
+
+	// A loose addon packed into addon.vpk, in a temporary directory
+	fn packed() -> (TempDir, PathBuf) {
+		let temp = tempfile::tempdir().unwrap();
+		let addon = temp.path().join("addon");
+		std::fs::create_dir_all(&addon).unwrap();
+		std::fs::write(addon.join("addoninfo.txt"), "\"AddonInfo\"\n{\n\taddontitle \"Test\"\n}\n").unwrap();
+		let vpk = temp.path().join("addon.vpk");
+		write_vpk(&addon, &vpk, false, false).unwrap();
+		(temp, vpk)
+	}
+
+	#[test]
+	fn parses_a_tree() {
+		let Ok(tree) = parse_tree(&tree(VPK_ENTRY_TERMINATOR), 12) else {
+			panic!("expected a directory tree");
+		};
+		let entry = &tree.files["scripts/test.nut"];
+		assert_eq!((entry.crc, entry.entry_length), (0x1234_5678, 4));
+	}
+
+	#[test]
+	fn truncated_tree_is_an_error() {
+		let bytes = tree(VPK_ENTRY_TERMINATOR);
+		// In the middle of a name, then of a directory entry
+		let err = tree_error(&bytes[..6]);
+		assert!(err.contains("cut off at byte 18, in the middle of a name"), "{}", err);
+		let err = tree_error(&bytes[..25]);
+		assert!(err.contains("cut off at byte 37") && err.contains("scripts/test.nut"), "{}", err);
+	}
+
+	#[test]
+	fn damaged_entry_is_an_error() {
+		let err = tree_error(&tree(0x1234));
+		assert!(err.contains("directory entry of scripts/test.nut at byte 29 is damaged"), "{}", err);
+	}
+
+	#[test]
+	fn trailing_bytes_are_an_error() {
+		let mut bytes = tree(VPK_ENTRY_TERMINATOR);
+		bytes.push(0);
+		let err = tree_error(&bytes);
+		assert!(err.starts_with("bad tree size"), "{}", err);
+	}
+
+	#[test]
+	fn bad_signature_is_refused() {
+		let temp = tempfile::tempdir().unwrap();
+		let zip = temp.path().join("addon.zip");
+		std::fs::write(&zip, b"PK\x03\x04 not a VPK at all").unwrap();
+		assert!(parse_error(open_vpk(&zip)).starts_with("not a VPK file"));
+	}
+
+	#[test]
+	fn truncated_file_is_refused() {
+		let (_temp, vpk) = packed();
+		let contents = std::fs::read(&vpk).unwrap();
+		std::fs::write(&vpk, &contents[..8]).unwrap();
+		assert!(parse_error(open_vpk(&vpk)).contains("too small for a VPK header"));
+		std::fs::write(&vpk, &contents[..HEADER_SIZE_V1 as usize + 10]).unwrap();
+		assert!(parse_error(open_vpk(&vpk)).starts_with("the header gives a directory tree"));
+		// The tree is whole, the embedded data is not
+		std::fs::write(&vpk, &contents[..contents.len() - 1]).unwrap();
+		assert!(parse_error(open_vpk(&vpk)).contains("past the end of the data"));
+	}
+
+	#[test]
+	fn oversized_entry_is_refused_before_reading() {
+		let (_temp, path) = packed();
+		let (mut file, mut vpk) = open_vpk(&path).unwrap();
+		let key = " /addoninfo.txt";
+		vpk.tree.files.get_mut(key).unwrap().entry_length = u32::MAX;
+		let result = read_entry(&path, &mut file, &vpk, key).map(|data| data.len());
+		match result {
+			Err(EnforcerError::VpkParse { reason, .. }) => assert!(reason.contains("past the end of the data"), "{}", reason),
+			other => panic!("expected a VPK parse error, got {:?}", other),
+		}
+	}
+}